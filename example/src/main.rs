@@ -1,16 +1,16 @@
 use std::env;
 
 use jupiter_swap_api_client::{
-    quote::QuoteRequest, swap::SwapRequest, transaction_config::TransactionConfig,
+    mints::{NATIVE_MINT, USDC_MINT},
+    quote::QuoteRequest,
+    swap::SwapRequest,
+    transaction_config::TransactionConfig,
     JupiterSwapApiClient,
 };
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{pubkey, transaction::VersionedTransaction};
 use solana_sdk::{pubkey::Pubkey, signature::NullSigner};
 
-const USDC_MINT: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
-const NATIVE_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
-
 pub const TEST_WALLET: Pubkey = pubkey!("2AQdpHJ2JpcEgPiATUXjQxA8QmafFegfQwSLWSprPicm"); // Coinbase 2 wallet
 
 #[tokio::main]
@@ -68,11 +68,14 @@ async fn main() {
 
     // POST /swap-instructions
     let swap_instructions = jupiter_swap_api_client
-        .swap_instructions(&SwapRequest {
-            user_public_key: TEST_WALLET,
-            quote_response,
-            config: TransactionConfig::default(),
-        })
+        .swap_instructions(
+            &SwapRequest {
+                user_public_key: TEST_WALLET,
+                quote_response,
+                config: TransactionConfig::default(),
+            },
+            None,
+        )
         .await
         .unwrap();
     println!("swap_instructions: {swap_instructions:?}");
@@ -0,0 +1,148 @@
+//! Conversions between raw token amounts and UI amounts
+
+use rust_decimal::Decimal;
+use thiserror::Error;
+
+/// Largest scale supported by [`Decimal`]
+pub const MAX_DECIMALS: u8 = 28;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum AmountError {
+    #[error("Amount {0} is negative")]
+    Negative(Decimal),
+    #[error("Amount {ui_amount} with {decimals} decimals does not fit in a u64")]
+    Overflow { ui_amount: Decimal, decimals: u8 },
+    #[error("{0} decimals exceeds the maximum of {MAX_DECIMALS}")]
+    UnsupportedDecimals(u8),
+}
+
+/// Convert a UI amount into a raw amount, have to factor in the token decimals.
+/// Digits beyond the token decimals are rounded down, so the raw amount never exceeds the UI amount.
+pub fn ui_to_raw(ui_amount: Decimal, decimals: u8) -> Result<u64, AmountError> {
+    if decimals > MAX_DECIMALS {
+        return Err(AmountError::UnsupportedDecimals(decimals));
+    }
+    let overflow = || AmountError::Overflow {
+        ui_amount,
+        decimals,
+    };
+
+    let mantissa =
+        u128::try_from(ui_amount.mantissa()).map_err(|_| AmountError::Negative(ui_amount))?;
+    let scale = ui_amount.scale();
+    let decimals = u32::from(decimals);
+    let raw = if decimals >= scale {
+        mantissa
+            .checked_mul(10u128.pow(decimals - scale))
+            .ok_or_else(overflow)?
+    } else {
+        mantissa / 10u128.pow(scale - decimals)
+    };
+    u64::try_from(raw).map_err(|_| overflow())
+}
+
+/// Convert a raw amount into a UI amount, the conversion is exact.
+///
+/// # Panics
+///
+/// Panics if `decimals` exceeds [`MAX_DECIMALS`]
+pub fn raw_to_ui(raw_amount: u64, decimals: u8) -> Decimal {
+    Decimal::from_i128_with_scale(i128::from(raw_amount), u32::from(decimals))
+}
+
+/// Same as [`raw_to_ui`], failing with [`AmountError::UnsupportedDecimals`] rather than panicking,
+/// for decimals coming from the server
+pub fn checked_raw_to_ui(raw_amount: u64, decimals: u8) -> Result<Decimal, AmountError> {
+    if decimals > MAX_DECIMALS {
+        return Err(AmountError::UnsupportedDecimals(decimals));
+    }
+    Ok(raw_to_ui(raw_amount, decimals))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::random::random_u64;
+
+    fn ui(amount: &str) -> Decimal {
+        Decimal::from_str(amount).unwrap()
+    }
+
+    #[test]
+    fn ui_to_raw_factors_in_decimals() {
+        assert_eq!(ui_to_raw(ui("1.5"), 6), Ok(1_500_000));
+        assert_eq!(ui_to_raw(ui("0.000000001"), 9), Ok(1));
+        assert_eq!(ui_to_raw(ui("42"), 0), Ok(42));
+        assert_eq!(ui_to_raw(Decimal::ZERO, 9), Ok(0));
+    }
+
+    #[test]
+    fn ui_to_raw_rounds_down() {
+        assert_eq!(ui_to_raw(ui("1.2345679"), 6), Ok(1_234_567));
+        assert_eq!(ui_to_raw(ui("0.0000009"), 6), Ok(0));
+        assert_eq!(ui_to_raw(ui("9.99"), 0), Ok(9));
+    }
+
+    #[test]
+    fn ui_to_raw_rejects_what_does_not_fit() {
+        assert_eq!(
+            ui_to_raw(ui("18446744073709551616"), 0),
+            Err(AmountError::Overflow {
+                ui_amount: ui("18446744073709551616"),
+                decimals: 0,
+            })
+        );
+        assert!(matches!(
+            ui_to_raw(ui("18446744073.709551616"), 9),
+            Err(AmountError::Overflow { .. })
+        ));
+        assert_eq!(ui_to_raw(ui("18446744073.709551615"), 9), Ok(u64::MAX));
+        assert_eq!(ui_to_raw(ui("-1"), 6), Err(AmountError::Negative(ui("-1"))));
+        assert_eq!(
+            ui_to_raw(ui("1"), MAX_DECIMALS + 1),
+            Err(AmountError::UnsupportedDecimals(MAX_DECIMALS + 1))
+        );
+    }
+
+    #[test]
+    fn eighteen_decimals_go_through_u128() {
+        assert_eq!(ui_to_raw(ui("18.446744073709551615"), 18), Ok(u64::MAX));
+        assert_eq!(ui_to_raw(ui("0.000000000000000001"), 18), Ok(1));
+        assert!(matches!(
+            ui_to_raw(ui("19"), 18),
+            Err(AmountError::Overflow { .. })
+        ));
+        assert_eq!(raw_to_ui(u64::MAX, 18), ui("18.446744073709551615"));
+    }
+
+    #[test]
+    fn raw_to_ui_is_exact() {
+        assert_eq!(raw_to_ui(1_500_000, 6), ui("1.5"));
+        assert_eq!(raw_to_ui(1, 9), ui("0.000000001"));
+        assert_eq!(raw_to_ui(u64::MAX, 0), Decimal::from(u64::MAX));
+    }
+
+    #[test]
+    fn random_amounts_round_trip() {
+        for _ in 0..1_000 {
+            let raw = random_u64();
+            let decimals = (random_u64() % 19) as u8;
+            assert_eq!(ui_to_raw(raw_to_ui(raw, decimals), decimals), Ok(raw));
+        }
+    }
+
+    #[test]
+    fn checked_raw_to_ui_rejects_unsupported_decimals() {
+        assert_eq!(checked_raw_to_ui(1_500_000, 6), Ok(ui("1.5")));
+        assert_eq!(
+            checked_raw_to_ui(1, MAX_DECIMALS),
+            Ok(raw_to_ui(1, MAX_DECIMALS))
+        );
+        assert_eq!(
+            checked_raw_to_ui(1, MAX_DECIMALS + 1),
+            Err(AmountError::UnsupportedDecimals(MAX_DECIMALS + 1))
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Trait over the Swap API, to swap the client for a mock in tests
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::{
+    quote::{QuoteRequest, QuoteResponse},
+    swap::{SwapInstructionsResponse, SwapRequest, SwapResponse},
+    ClientError, JupiterSwapApiClient,
+};
+
+/// Implemented by [`JupiterSwapApiClient`], a `MockJupiterApi` is generated with the `mockall` feature
+#[cfg_attr(feature = "mockall", mockall::automock)]
+#[async_trait]
+pub trait JupiterApi: Send + Sync {
+    async fn quote(&self, quote_request: &QuoteRequest) -> Result<QuoteResponse, ClientError>;
+
+    async fn swap(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+    ) -> Result<SwapResponse, ClientError>;
+
+    async fn swap_instructions(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+    ) -> Result<SwapInstructionsResponse, ClientError>;
+}
+
+#[async_trait]
+impl JupiterApi for JupiterSwapApiClient {
+    async fn quote(&self, quote_request: &QuoteRequest) -> Result<QuoteResponse, ClientError> {
+        JupiterSwapApiClient::quote(self, quote_request).await
+    }
+
+    async fn swap(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+    ) -> Result<SwapResponse, ClientError> {
+        JupiterSwapApiClient::swap(self, swap_request, extra_args).await
+    }
+
+    async fn swap_instructions(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+    ) -> Result<SwapInstructionsResponse, ClientError> {
+        JupiterSwapApiClient::swap_instructions(self, swap_request, extra_args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_pubkey::Pubkey;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::mints::{NATIVE_MINT, USDC_MINT};
+
+    fn quote_request() -> QuoteRequest {
+        QuoteRequest {
+            input_mint: NATIVE_MINT,
+            output_mint: USDC_MINT,
+            amount: 1_000,
+            ..QuoteRequest::default()
+        }
+    }
+
+    /// Code under test only knowing the trait: quote then swap
+    async fn quote_and_swap(
+        api: &dyn JupiterApi,
+        user_public_key: Pubkey,
+    ) -> Result<SwapResponse, ClientError> {
+        let quote_response = api.quote(&quote_request()).await?;
+        let swap_request = SwapRequest {
+            user_public_key,
+            quote_response,
+            ..SwapRequest::default()
+        };
+        api.swap(
+            &swap_request,
+            Some(HashMap::from([("flag".to_string(), "1".to_string())])),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn client_implements_the_trait_with_its_own_methods() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .and(query_param("amount", "1000"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(QuoteResponse {
+                out_amount: 146,
+                ..QuoteResponse::default()
+            }))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/swap"))
+            .and(query_param("flag", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(SwapResponse {
+                swap_transaction: vec![1, 2, 3],
+                ..SwapResponse::default()
+            }))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        let swap_response = quote_and_swap(&client, Pubkey::new_unique()).await.unwrap();
+        assert_eq!(swap_response.swap_transaction, [1, 2, 3]);
+        let requests = server.received_requests().await.unwrap();
+        let swap_request: SwapRequest = requests[1].body_json().unwrap();
+        assert_eq!(swap_request.quote_response.out_amount, 146);
+    }
+
+    #[cfg(feature = "mockall")]
+    #[tokio::test]
+    async fn mock_stands_in_for_the_client() {
+        use mockall::predicate::always;
+
+        let user_public_key = Pubkey::new_unique();
+        let mut api = MockJupiterApi::new();
+        api.expect_quote()
+            .withf(|quote_request| quote_request.amount == 1_000)
+            .times(1)
+            .returning(|_| {
+                Ok(QuoteResponse {
+                    out_amount: 146,
+                    ..QuoteResponse::default()
+                })
+            });
+        api.expect_swap()
+            .withf(move |swap_request, extra_args| {
+                swap_request.user_public_key == user_public_key
+                    && swap_request.quote_response.out_amount == 146
+                    && extra_args.as_ref().is_some_and(|args| args["flag"] == "1")
+            })
+            .times(1)
+            .returning(|_, _| {
+                Ok(SwapResponse {
+                    swap_transaction: vec![1, 2, 3],
+                    ..SwapResponse::default()
+                })
+            });
+        api.expect_swap_instructions()
+            .with(always(), always())
+            .never();
+
+        let swap_response = quote_and_swap(&api, user_public_key).await.unwrap();
+        assert_eq!(swap_response.swap_transaction, [1, 2, 3]);
+    }
+}
@@ -0,0 +1,272 @@
+//! Associated token accounts of the swapped mints
+//!
+//! Mints owned by the Token-2022 program have their associated token accounts derived with that program id,
+//! the quote does not tell which program owns a mint so it has to be provided or, with the `rpc` feature, looked up.
+
+use std::collections::HashMap;
+
+use solana_pubkey::{pubkey, Pubkey};
+
+use crate::{mints::NATIVE_MINT, quote::QuoteResponse, swap::SwapRequest};
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// Associated token account of `wallet` for `mint`, `token_program` being the owner of the mint
+pub fn associated_token_address(wallet: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+impl QuoteResponse {
+    /// Associated token accounts of `user` for the input then the output mint
+    pub fn required_token_accounts(
+        &self,
+        user: &Pubkey,
+        input_token_program: &Pubkey,
+        output_token_program: &Pubkey,
+    ) -> Vec<Pubkey> {
+        vec![
+            associated_token_address(user, &self.input_mint, input_token_program),
+            associated_token_address(user, &self.output_mint, output_token_program),
+        ]
+    }
+}
+
+impl SwapRequest {
+    /// Receive the output in the user's token account for the output mint from `token_accounts`, a map of
+    /// mints to token accounts, instead of the associated token account the API would otherwise create
+    /// when missing. The API does not take a list of existing accounts, it only checks the associated one.
+    ///
+    /// The token account must be initialized, hold the output mint and accept transfers for the whole swap,
+    /// the API assumes so without checking. Native SOL output is left unchanged while `wrap_and_unwrap_sol`
+    /// is set, as a destination token account disables unwrapping.
+    pub fn with_existing_token_account(mut self, token_accounts: &HashMap<Pubkey, Pubkey>) -> Self {
+        let output_mint = self.quote_response.output_mint;
+        if output_mint == NATIVE_MINT && self.config.wrap_and_unwrap_sol {
+            return self;
+        }
+        if let Some(token_account) = token_accounts.get(&output_mint) {
+            self.config.destination_token_account = Some(*token_account);
+        }
+        self
+    }
+}
+
+#[cfg(feature = "rpc")]
+mod rpc {
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use solana_pubkey::Pubkey;
+
+    use super::TOKEN_PROGRAM_ID;
+    use crate::{quote::QuoteResponse, ClientError, ErrorKind};
+
+    /// Owner program of each mint, SPL Token for mints which do not exist
+    pub async fn token_programs(
+        rpc: &RpcClient,
+        mints: &[Pubkey],
+    ) -> Result<Vec<Pubkey>, ClientError> {
+        let accounts = rpc
+            .get_multiple_accounts(mints)
+            .await
+            .map_err(|e| ErrorKind::RpcError(Box::new(e)))?;
+        Ok(accounts
+            .into_iter()
+            .map(|account| account.map_or(TOKEN_PROGRAM_ID, |account| account.owner))
+            .collect())
+    }
+
+    impl QuoteResponse {
+        /// Required token accounts of `user` which do not exist yet, looking up the token program of each mint
+        pub async fn missing_token_accounts(
+            &self,
+            rpc: &RpcClient,
+            user: &Pubkey,
+        ) -> Result<Vec<Pubkey>, ClientError> {
+            let token_programs = token_programs(rpc, &[self.input_mint, self.output_mint]).await?;
+            let token_accounts =
+                self.required_token_accounts(user, &token_programs[0], &token_programs[1]);
+            let accounts = rpc
+                .get_multiple_accounts(&token_accounts)
+                .await
+                .map_err(|e| ErrorKind::RpcError(Box::new(e)))?;
+            Ok(token_accounts
+                .into_iter()
+                .zip(accounts)
+                .filter(|(_, account)| account.is_none())
+                .map(|(token_account, _)| token_account)
+                .collect())
+        }
+    }
+}
+
+#[cfg(feature = "rpc")]
+pub use rpc::token_programs;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mints::USDC_MINT;
+
+    const WALLET: Pubkey = pubkey!("8kzGq5Mp1Dc6rjh8YNBEWPvt3XrkTNnBSoDRLTc8Xr1B");
+    /// PayPal USD, a Token-2022 mint
+    const PYUSD_MINT: Pubkey = pubkey!("2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo");
+
+    #[test]
+    fn associated_token_address_is_derived_with_the_token_program() {
+        assert_eq!(
+            associated_token_address(&WALLET, &USDC_MINT, &TOKEN_PROGRAM_ID),
+            pubkey!("8tjcvNYa4QW15nWXZwiNfgWZgeYwQU6VWrUFKaMThTbs")
+        );
+        assert_eq!(
+            associated_token_address(&WALLET, &NATIVE_MINT, &TOKEN_PROGRAM_ID),
+            pubkey!("CNZ1RqQAvgD23z2SRYbwcgnhNwdHb5miH1Vo2ZrvVsP4")
+        );
+        assert_eq!(
+            associated_token_address(&WALLET, &PYUSD_MINT, &TOKEN_2022_PROGRAM_ID),
+            pubkey!("38WuQZnuBuKqmHHncALuhj8FPJDaY8jPSPQf2QFXkSCG")
+        );
+        // The same mint under the other program is another account
+        assert_eq!(
+            associated_token_address(&WALLET, &USDC_MINT, &TOKEN_2022_PROGRAM_ID),
+            pubkey!("DFgT2UifvwkhPNtRgxfpFHSgnZe1YpVC2xTz9U2wogRu")
+        );
+    }
+
+    #[test]
+    fn required_token_accounts_are_the_input_then_the_output_one() {
+        let quote_response = QuoteResponse {
+            input_mint: USDC_MINT,
+            output_mint: PYUSD_MINT,
+            ..QuoteResponse::default()
+        };
+        assert_eq!(
+            quote_response.required_token_accounts(
+                &WALLET,
+                &TOKEN_PROGRAM_ID,
+                &TOKEN_2022_PROGRAM_ID
+            ),
+            [
+                pubkey!("8tjcvNYa4QW15nWXZwiNfgWZgeYwQU6VWrUFKaMThTbs"),
+                pubkey!("38WuQZnuBuKqmHHncALuhj8FPJDaY8jPSPQf2QFXkSCG"),
+            ]
+        );
+    }
+
+    #[cfg(feature = "rpc")]
+    #[tokio::test]
+    async fn missing_token_accounts_use_the_owner_of_each_mint() {
+        use serde_json::json;
+        use solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::RpcRequest};
+
+        let mint = |owner: Pubkey| {
+            json!({
+                "lamports": 1_461_600,
+                "data": ["", "base64"],
+                "owner": owner.to_string(),
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 0
+            })
+        };
+        // Answers the lookup of the mints, then the default of the mock: no token account exists
+        let rpc = RpcClient::new_mock_with_mocks(
+            "succeeds".to_string(),
+            HashMap::from([(
+                RpcRequest::GetMultipleAccounts,
+                json!({
+                    "context": { "slot": 1 },
+                    "value": [mint(TOKEN_PROGRAM_ID), mint(TOKEN_2022_PROGRAM_ID)]
+                }),
+            )]),
+        );
+        let quote_response = QuoteResponse {
+            input_mint: USDC_MINT,
+            output_mint: PYUSD_MINT,
+            ..QuoteResponse::default()
+        };
+
+        let missing = quote_response
+            .missing_token_accounts(&rpc, &WALLET)
+            .await
+            .unwrap();
+        assert_eq!(
+            missing,
+            quote_response.required_token_accounts(
+                &WALLET,
+                &TOKEN_PROGRAM_ID,
+                &TOKEN_2022_PROGRAM_ID
+            )
+        );
+    }
+
+    fn swap_request(output_mint: Pubkey) -> SwapRequest {
+        SwapRequest {
+            user_public_key: Pubkey::new_unique(),
+            quote_response: QuoteResponse {
+                input_mint: NATIVE_MINT,
+                output_mint,
+                ..QuoteResponse::default()
+            },
+            ..SwapRequest::default()
+        }
+    }
+
+    #[test]
+    fn existing_token_account_of_the_output_mint_is_the_destination() {
+        let token_account = Pubkey::new_unique();
+        let token_accounts = HashMap::from([
+            (NATIVE_MINT, Pubkey::new_unique()),
+            (USDC_MINT, token_account),
+        ]);
+
+        let swap_request = swap_request(USDC_MINT).with_existing_token_account(&token_accounts);
+        assert_eq!(
+            swap_request.config.destination_token_account,
+            Some(token_account)
+        );
+        let body = serde_json::to_value(&swap_request).unwrap();
+        assert_eq!(body["destinationTokenAccount"], token_account.to_string());
+    }
+
+    #[test]
+    fn output_mint_without_a_token_account_is_left_unchanged() {
+        let token_accounts = HashMap::from([(NATIVE_MINT, Pubkey::new_unique())]);
+        let swap_request = swap_request(USDC_MINT);
+        assert_eq!(
+            swap_request
+                .clone()
+                .with_existing_token_account(&token_accounts),
+            swap_request
+        );
+        let body = serde_json::to_value(&swap_request).unwrap();
+        assert!(body.get("destinationTokenAccount").is_none());
+    }
+
+    #[test]
+    fn native_sol_output_is_only_redirected_without_unwrapping() {
+        let token_account = Pubkey::new_unique();
+        let token_accounts = HashMap::from([(NATIVE_MINT, token_account)]);
+        let mut swap_request = swap_request(NATIVE_MINT);
+        swap_request.quote_response.input_mint = USDC_MINT;
+        assert!(swap_request.config.wrap_and_unwrap_sol);
+        assert_eq!(
+            swap_request
+                .clone()
+                .with_existing_token_account(&token_accounts),
+            swap_request
+        );
+
+        swap_request.config.wrap_and_unwrap_sol = false;
+        let swap_request = swap_request.with_existing_token_account(&token_accounts);
+        assert_eq!(
+            swap_request.config.destination_token_account,
+            Some(token_account)
+        );
+    }
+}
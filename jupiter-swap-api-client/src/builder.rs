@@ -0,0 +1,959 @@
+//! Builder for [`JupiterSwapApiClient`]
+
+use std::{sync::Arc, time::Duration};
+
+#[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+use reqwest::Certificate;
+use reqwest::{
+    header::{HeaderName, HeaderValue, InvalidHeaderValue},
+    redirect::Policy,
+    Client, Proxy, Url,
+};
+use rust_decimal::Decimal;
+
+#[cfg(feature = "quote-cache")]
+use crate::quote_cache::QuoteCache;
+#[cfg(feature = "record-replay")]
+use crate::recording::Recorder;
+use crate::{
+    capture::LastExchange,
+    metrics::{ClientMetrics, MetricsHook},
+    quote::MultiValueStyle,
+    retry::RetryPolicy,
+    tokens::TokenCache,
+    ClientError, ErrorKind, JupiterSwapApiClient,
+};
+
+/// Base path of the Swap API for requests carrying an api key
+pub const KEYED_BASE_PATH: &str = "https://api.jup.ag/swap/v1";
+
+/// Public rate limited hosts which do not serve keyed requests
+const PUBLIC_UNKEYED_HOSTS: [&str; 2] = ["lite-api.jup.ag", "quote-api.jup.ag"];
+
+/// How the api key is sent, `x-api-key` by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AuthScheme {
+    /// `x-api-key: <key>`, expected by the Jupiter APIs
+    #[default]
+    ApiKey,
+    /// `Authorization: Bearer <key>`
+    Bearer,
+    /// `<name>: <prefix><key>`, e.g. for a gateway in front of the API
+    Header {
+        name: HeaderName,
+        prefix: Option<String>,
+    },
+}
+
+impl AuthScheme {
+    pub(crate) fn header_name(&self) -> HeaderName {
+        match self {
+            Self::ApiKey => HeaderName::from_static("x-api-key"),
+            Self::Bearer => reqwest::header::AUTHORIZATION,
+            Self::Header { name, .. } => name.clone(),
+        }
+    }
+
+    /// Marked sensitive so that it is left out of `Debug` output
+    pub(crate) fn header_value(&self, api_key: &str) -> Result<HeaderValue, InvalidHeaderValue> {
+        let mut value = match self {
+            Self::ApiKey => HeaderValue::from_str(api_key)?,
+            Self::Bearer => HeaderValue::from_str(&format!("Bearer {api_key}"))?,
+            Self::Header { prefix, .. } => HeaderValue::from_str(&format!(
+                "{}{api_key}",
+                prefix.as_deref().unwrap_or_default()
+            ))?,
+        };
+        value.set_sensitive(true);
+        Ok(value)
+    }
+}
+
+/// The Ultra, Price, Trigger, Recurring and Tokens base paths default to [`LITE_API_HOST`](crate::LITE_API_HOST),
+/// or [`KEYED_API_HOST`](crate::KEYED_API_HOST) when an api key is set
+#[derive(Debug)]
+pub struct JupiterSwapApiClientBuilder {
+    base_path: String,
+    api_key: Option<String>,
+    auth_scheme: AuthScheme,
+    multi_value_style: MultiValueStyle,
+    ultra_base_path: Option<String>,
+    price_base_path: Option<String>,
+    trigger_base_path: Option<String>,
+    recurring_base_path: Option<String>,
+    tokens_base_path: Option<String>,
+    allow_misconfiguration: bool,
+    deny_misconfiguration: bool,
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    connect_timeout: Option<Duration>,
+    tcp_keepalive: Option<Duration>,
+    tcp_nodelay: Option<bool>,
+    http2_prior_knowledge: bool,
+    http2_keep_alive_interval: Option<Duration>,
+    http1_only: bool,
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    root_certificates: Vec<Certificate>,
+    proxy: Option<String>,
+    proxy_auth: Option<(String, String)>,
+    no_proxy: bool,
+    redirect: Option<Policy>,
+    metrics: MetricsHook,
+    debug_capture: bool,
+    compression: bool,
+    token_cache: Option<(Duration, usize)>,
+    #[cfg(feature = "quote-cache")]
+    quote_cache: Option<(Duration, usize)>,
+    max_price_impact_pct: Option<Decimal>,
+    max_response_size: Option<usize>,
+    warn_on_field_drift: bool,
+    retry_policy: RetryPolicy,
+    #[cfg(feature = "record-replay")]
+    recorder: Option<Arc<dyn Recorder>>,
+}
+
+/// Stop at redirects to another host, leaving the others to `redirect`
+fn same_host_redirects(redirect: Policy) -> Policy {
+    Policy::custom(move |attempt| {
+        let cross_host = attempt.previous().last().is_some_and(|previous| {
+            previous.host_str() != attempt.url().host_str()
+                || previous.port_or_known_default() != attempt.url().port_or_known_default()
+        });
+        if cross_host {
+            attempt.stop()
+        } else {
+            redirect.redirect(attempt)
+        }
+    })
+}
+
+impl JupiterSwapApiClientBuilder {
+    pub fn new(base_path: String) -> Self {
+        Self {
+            base_path,
+            api_key: None,
+            auth_scheme: AuthScheme::default(),
+            multi_value_style: MultiValueStyle::default(),
+            ultra_base_path: None,
+            price_base_path: None,
+            trigger_base_path: None,
+            recurring_base_path: None,
+            tokens_base_path: None,
+            allow_misconfiguration: false,
+            deny_misconfiguration: false,
+            pool_idle_timeout: None,
+            pool_max_idle_per_host: None,
+            connect_timeout: None,
+            tcp_keepalive: None,
+            tcp_nodelay: None,
+            http2_prior_knowledge: false,
+            http2_keep_alive_interval: None,
+            http1_only: false,
+            #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+            root_certificates: Vec::new(),
+            proxy: None,
+            proxy_auth: None,
+            no_proxy: false,
+            redirect: None,
+            metrics: MetricsHook::default(),
+            debug_capture: false,
+            compression: true,
+            token_cache: None,
+            #[cfg(feature = "quote-cache")]
+            quote_cache: None,
+            max_price_impact_pct: None,
+            max_response_size: None,
+            warn_on_field_drift: false,
+            retry_policy: RetryPolicy::none(),
+            #[cfg(feature = "record-replay")]
+            recorder: None,
+        }
+    }
+
+    pub fn api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// Send the api key as `Authorization: Bearer <key>` or in a custom header instead of `x-api-key`,
+    /// for gateways in front of the API
+    pub fn auth_scheme(mut self, auth_scheme: AuthScheme) -> Self {
+        self.auth_scheme = auth_scheme;
+        self
+    }
+
+    /// Send `dexes` and `excluded_dexes` of quote requests as repeated keys rather than comma lists,
+    /// for self-hosted versions which only parse one form
+    pub fn multi_value_style(mut self, multi_value_style: MultiValueStyle) -> Self {
+        self.multi_value_style = multi_value_style;
+        self
+    }
+
+    pub fn ultra_base_path(mut self, ultra_base_path: String) -> Self {
+        self.ultra_base_path = Some(ultra_base_path);
+        self
+    }
+
+    pub fn price_base_path(mut self, price_base_path: String) -> Self {
+        self.price_base_path = Some(price_base_path);
+        self
+    }
+
+    pub fn trigger_base_path(mut self, trigger_base_path: String) -> Self {
+        self.trigger_base_path = Some(trigger_base_path);
+        self
+    }
+
+    pub fn recurring_base_path(mut self, recurring_base_path: String) -> Self {
+        self.recurring_base_path = Some(recurring_base_path);
+        self
+    }
+
+    pub fn tokens_base_path(mut self, tokens_base_path: String) -> Self {
+        self.tokens_base_path = Some(tokens_base_path);
+        self
+    }
+
+    /// Skip the check warning about an api key paired with a public unkeyed host,
+    /// for setups where the key is consumed by something in between
+    pub fn allow_misconfiguration(mut self, allow_misconfiguration: bool) -> Self {
+        self.allow_misconfiguration = allow_misconfiguration;
+        self
+    }
+
+    /// Fail [`build`](Self::build) with [`ErrorKind::Misconfiguration`] instead of logging a
+    /// warning when an api key is paired with a public unkeyed host
+    pub fn deny_misconfiguration(mut self, deny_misconfiguration: bool) -> Self {
+        self.deny_misconfiguration = deny_misconfiguration;
+        self
+    }
+
+    /// How long an idle connection is kept in the pool, defaults to reqwest's 90 seconds
+    pub fn pool_idle_timeout(mut self, pool_idle_timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(pool_idle_timeout);
+        self
+    }
+
+    /// Maximum number of idle connections kept per host, unbounded by default
+    pub fn pool_max_idle_per_host(mut self, pool_max_idle_per_host: usize) -> Self {
+        self.pool_max_idle_per_host = Some(pool_max_idle_per_host);
+        self
+    }
+
+    /// Timeout for establishing a connection, none by default
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Interval of the TCP keepalive probes of pooled connections, disabled by default
+    pub fn tcp_keepalive(mut self, tcp_keepalive: Duration) -> Self {
+        self.tcp_keepalive = Some(tcp_keepalive);
+        self
+    }
+
+    /// Set `TCP_NODELAY` on connections, enabled by reqwest by default
+    pub fn tcp_nodelay(mut self, tcp_nodelay: bool) -> Self {
+        self.tcp_nodelay = Some(tcp_nodelay);
+        self
+    }
+
+    /// Only speak HTTP/2 without negotiating it first, e.g. for a self-hosted API behind an h2c proxy.
+    /// Disabled by default, HTTP/2 is still used when negotiated over TLS.
+    pub fn http2_prior_knowledge(mut self, http2_prior_knowledge: bool) -> Self {
+        self.http2_prior_knowledge = http2_prior_knowledge;
+        self
+    }
+
+    /// Interval of the HTTP/2 pings keeping connections alive, disabled by default
+    pub fn http2_keep_alive_interval(mut self, http2_keep_alive_interval: Duration) -> Self {
+        self.http2_keep_alive_interval = Some(http2_keep_alive_interval);
+        self
+    }
+
+    /// Only use HTTP/1.1, for proxies which do not speak HTTP/2. Conflicts with [`Self::http2_prior_knowledge`]
+    pub fn http1_only(mut self, http1_only: bool) -> Self {
+        self.http1_only = http1_only;
+        self
+    }
+
+    /// Trust `certificate` on top of the default roots, e.g. for a private CA of a self-hosted API
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    pub fn add_root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /// Send every request through the proxy at `proxy_url`, e.g. `http://proxy:3128`,
+    /// `socks5://` urls need the `socks` feature
+    pub fn proxy(mut self, proxy_url: String) -> Self {
+        self.proxy = Some(proxy_url);
+        self
+    }
+
+    /// Basic auth credentials of the proxy set with [`Self::proxy`]
+    pub fn proxy_auth(mut self, username: String, password: String) -> Self {
+        self.proxy_auth = Some((username, password));
+        self
+    }
+
+    /// Ignore the proxies from the `HTTP_PROXY`, `HTTPS_PROXY` and `ALL_PROXY` environment variables,
+    /// which are used by default
+    pub fn no_proxy(mut self) -> Self {
+        self.no_proxy = true;
+        self
+    }
+
+    /// Redirects are not followed by default as a POST redirected with a 302 is replayed as a GET without its body,
+    /// a redirect response fails with [`ErrorKind::Redirected`].
+    /// Redirects to another host are not followed either unless the api key is sent as `Authorization`,
+    /// the only auth header reqwest strips from them, so that the api key is not forwarded.
+    pub fn redirect(mut self, redirect: Policy) -> Self {
+        self.redirect = Some(redirect);
+        self
+    }
+
+    /// Hook called with the endpoint, status and latency of every request attempt,
+    /// defaults to `MetricsFacade` with the `metrics` feature and to no metrics otherwise
+    pub fn metrics(mut self, metrics: Arc<dyn ClientMetrics>) -> Self {
+        self.metrics = MetricsHook(metrics);
+        self
+    }
+
+    /// Keep the last request and response, see [`JupiterSwapApiClient::last_exchange`].
+    /// Response bodies are buffered while enabled, meant for reproducing issues rather than production use.
+    pub fn debug_capture(mut self, debug_capture: bool) -> Self {
+        self.debug_capture = debug_capture;
+        self
+    }
+
+    /// Accept gzip and brotli compressed responses, enabled by default.
+    /// Has no effect without the `compression` feature, in which case responses are never compressed.
+    pub fn compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Cache token infos for `ttl`, keeping at most `max_entries` mints, see [`JupiterSwapApiClient::cached_token_info`].
+    /// The cache is shared by the clones of the client.
+    pub fn token_cache(mut self, ttl: Duration, max_entries: usize) -> Self {
+        self.token_cache = Some((ttl, max_entries));
+        self
+    }
+
+    /// Serve identical quote requests made within `ttl` from a cache keeping at most `max_entries` quotes,
+    /// e.g. [`DEFAULT_QUOTE_CACHE_TTL`](crate::quote_cache::DEFAULT_QUOTE_CACHE_TTL).
+    /// Keep `ttl` short as cached quotes go stale, see [`RequestOptions::bypass_cache`](crate::request_options::RequestOptions::bypass_cache).
+    #[cfg(feature = "quote-cache")]
+    pub fn quote_cache(mut self, ttl: Duration, max_entries: usize) -> Self {
+        self.quote_cache = Some((ttl, max_entries));
+        self
+    }
+
+    /// Refuse to swap quotes whose `price_impact_pct` is above `max_price_impact_pct`,
+    /// the swap methods then fail with [`ErrorKind::PriceImpactTooHigh`] without sending the request
+    pub fn max_price_impact_pct(mut self, max_price_impact_pct: Decimal) -> Self {
+        self.max_price_impact_pct = Some(max_price_impact_pct);
+        self
+    }
+
+    /// Fail with [`ErrorKind::ResponseTooLarge`] once a response body exceeds `max_response_size` bytes,
+    /// defaults to [`DEFAULT_MAX_RESPONSE_SIZE`](crate::DEFAULT_MAX_RESPONSE_SIZE)
+    pub fn max_response_size(mut self, max_response_size: usize) -> Self {
+        self.max_response_size = Some(max_response_size);
+        self
+    }
+
+    /// Retry requests failing with a timeout, a connection error, 429 or 5xx once every endpoint was tried,
+    /// no retries by default. Every attempt is recorded by the metrics hook.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Collect in `warnings` the fields quote, swap and swap instructions responses do not model,
+    /// and the optional fields the current API always sends but which are missing, e.g. `contextSlot`.
+    /// Responses are then deserialized through [`serde_json::Value`], which is slower.
+    pub fn warn_on_field_drift(mut self, warn_on_field_drift: bool) -> Self {
+        self.warn_on_field_drift = warn_on_field_drift;
+        self
+    }
+
+    /// Hand every successful quote, swap and swap instructions response to `recorder`,
+    /// e.g. a [`JsonlRecorder`](crate::recording::JsonlRecorder) for later replay
+    #[cfg(feature = "record-replay")]
+    pub fn recorder(mut self, recorder: Arc<dyn Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub fn build(mut self) -> Result<JupiterSwapApiClient, ClientError> {
+        if !self.allow_misconfiguration {
+            if let Err(e) = self.check_configuration() {
+                if self.deny_misconfiguration {
+                    return Err(e);
+                }
+                log::warn!("{e}");
+            }
+        }
+        let http_client = self.build_http_client()?;
+        let mut client = JupiterSwapApiClient::new(self.base_path, self.api_key);
+        client.http_client = http_client;
+        client.metrics = self.metrics;
+        client.auth_scheme = self.auth_scheme;
+        client.multi_value_style = self.multi_value_style;
+        client.max_price_impact_pct = self.max_price_impact_pct;
+        client.warn_on_field_drift = self.warn_on_field_drift;
+        client.retry_policy = self.retry_policy;
+        #[cfg(feature = "record-replay")]
+        {
+            client.recorder = self.recorder;
+        }
+        if let Some(max_response_size) = self.max_response_size {
+            client.max_response_size = max_response_size;
+        }
+        if self.debug_capture {
+            client.last_exchange = Some(LastExchange::default());
+        }
+        if let Some(ultra_base_path) = self.ultra_base_path {
+            client.ultra_base_path = ultra_base_path;
+        }
+        if let Some(price_base_path) = self.price_base_path {
+            client.price_base_path = price_base_path;
+        }
+        if let Some(trigger_base_path) = self.trigger_base_path {
+            client.trigger_base_path = trigger_base_path;
+        }
+        if let Some(recurring_base_path) = self.recurring_base_path {
+            client.recurring_base_path = recurring_base_path;
+        }
+        if let Some(tokens_base_path) = self.tokens_base_path {
+            client.tokens_base_path = tokens_base_path;
+        }
+        if let Some((ttl, max_entries)) = self.token_cache {
+            client.token_cache = Some(Arc::new(TokenCache::new(ttl, max_entries)));
+        }
+        #[cfg(feature = "quote-cache")]
+        if let Some((ttl, max_entries)) = self.quote_cache {
+            client.quote_cache = Some(Arc::new(QuoteCache::new(ttl, max_entries)));
+        }
+        Ok(client)
+    }
+
+    fn build_http_client(&mut self) -> Result<Client, ClientError> {
+        let redirect = match self.redirect.take() {
+            Some(redirect) if self.auth_scheme.header_name() != reqwest::header::AUTHORIZATION => {
+                same_host_redirects(redirect)
+            }
+            Some(redirect) => redirect,
+            None => Policy::none(),
+        };
+        let mut http_client = Client::builder().redirect(redirect);
+        if let Some(pool_idle_timeout) = self.pool_idle_timeout {
+            http_client = http_client.pool_idle_timeout(pool_idle_timeout);
+        }
+        if let Some(pool_max_idle_per_host) = self.pool_max_idle_per_host {
+            http_client = http_client.pool_max_idle_per_host(pool_max_idle_per_host);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            http_client = http_client.connect_timeout(connect_timeout);
+        }
+        if let Some(tcp_keepalive) = self.tcp_keepalive {
+            http_client = http_client.tcp_keepalive(tcp_keepalive);
+        }
+        if let Some(tcp_nodelay) = self.tcp_nodelay {
+            http_client = http_client.tcp_nodelay(tcp_nodelay);
+        }
+        #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+        for certificate in self.root_certificates.drain(..) {
+            http_client = http_client.add_root_certificate(certificate);
+        }
+        if self.no_proxy {
+            http_client = http_client.no_proxy();
+        }
+        match (self.proxy.take(), self.proxy_auth.take()) {
+            (Some(proxy_url), proxy_auth) => {
+                let mut proxy = Proxy::all(&proxy_url).map_err(|e| {
+                    ErrorKind::Misconfiguration(format!("Invalid proxy {proxy_url}: {e}"))
+                })?;
+                if let Some((username, password)) = proxy_auth {
+                    proxy = proxy.basic_auth(&username, &password);
+                }
+                http_client = http_client.proxy(proxy);
+            }
+            (None, Some(_)) => {
+                return Err(ErrorKind::Misconfiguration(
+                    "Proxy credentials were provided without a proxy".to_string(),
+                )
+                .into())
+            }
+            (None, None) => {}
+        }
+        if self.http2_prior_knowledge && self.http1_only {
+            return Err(ErrorKind::Misconfiguration(
+                "http2_prior_knowledge and http1_only are mutually exclusive".to_string(),
+            )
+            .into());
+        }
+        if self.http2_prior_knowledge {
+            http_client = http_client.http2_prior_knowledge();
+        }
+        if self.http1_only {
+            http_client = http_client.http1_only();
+        }
+        if let Some(http2_keep_alive_interval) = self.http2_keep_alive_interval {
+            http_client = http_client.http2_keep_alive_interval(http2_keep_alive_interval);
+        }
+        #[cfg(feature = "compression")]
+        {
+            http_client = http_client.gzip(self.compression).brotli(self.compression);
+        }
+        http_client.build().map_err(|e| {
+            ErrorKind::Misconfiguration(format!("Failed to build the http client: {e}")).into()
+        })
+    }
+
+    fn check_configuration(&self) -> Result<(), ClientError> {
+        if self.api_key.is_none() {
+            return Ok(());
+        }
+        let url = Url::parse(&self.base_path).map_err(|e| {
+            ErrorKind::Misconfiguration(format!("Invalid base path {}: {e}", self.base_path))
+        })?;
+        if let Some(host) = url
+            .host_str()
+            .filter(|host| PUBLIC_UNKEYED_HOSTS.contains(host))
+        {
+            return Err(ErrorKind::Misconfiguration(format!(
+                "An api key was provided but {host} is a public endpoint which does not serve keyed requests, use {KEYED_BASE_PATH} instead or call allow_misconfiguration(true) to skip this check"
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLIC_HOST: &str = "https://lite-api.jup.ag/swap/v1";
+
+    fn keyed_builder(base_path: &str) -> JupiterSwapApiClientBuilder {
+        JupiterSwapApiClientBuilder::new(base_path.to_string()).api_key("key".to_string())
+    }
+
+    #[test]
+    fn api_key_on_public_host_only_warns_by_default() {
+        let client = keyed_builder(PUBLIC_HOST).build().unwrap();
+        assert_eq!(client.base_path, PUBLIC_HOST);
+    }
+
+    #[test]
+    fn api_key_on_public_host_is_rejected_when_denied() {
+        let error = keyed_builder(PUBLIC_HOST)
+            .deny_misconfiguration(true)
+            .build()
+            .err()
+            .unwrap();
+        assert!(
+            matches!(error.kind(), ErrorKind::Misconfiguration(message) if message.contains("lite-api.jup.ag"))
+        );
+    }
+
+    #[test]
+    fn allowed_misconfiguration_skips_the_check() {
+        keyed_builder(PUBLIC_HOST)
+            .allow_misconfiguration(true)
+            .deny_misconfiguration(true)
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn keyed_host_and_unkeyed_public_host_are_accepted() {
+        keyed_builder(KEYED_BASE_PATH)
+            .deny_misconfiguration(true)
+            .build()
+            .unwrap();
+        JupiterSwapApiClientBuilder::new(PUBLIC_HOST.to_string())
+            .deny_misconfiguration(true)
+            .build()
+            .unwrap();
+    }
+
+    #[cfg(feature = "compression")]
+    async fn mock_gzipped_quote(server: &wiremock::MockServer) {
+        use std::io::Write;
+
+        use flate2::{write::GzEncoder, Compression};
+        use wiremock::{
+            matchers::{method, path},
+            Mock, ResponseTemplate,
+        };
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let body = serde_json::to_vec(&crate::quote::tests::quote_response_fixture()).unwrap();
+        encoder.write_all(&body).unwrap();
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-encoding", "gzip")
+                    .set_body_raw(encoder.finish().unwrap(), "application/json"),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[cfg(feature = "compression")]
+    fn quote_request() -> crate::quote::QuoteRequest {
+        crate::quote::QuoteRequest {
+            amount: 1_000_000_000,
+            ..crate::quote::QuoteRequest::default()
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn gzipped_quote_is_decoded() {
+        let server = wiremock::MockServer::start().await;
+        mock_gzipped_quote(&server).await;
+        let client = JupiterSwapApiClientBuilder::new(server.uri())
+            .build()
+            .unwrap();
+
+        let quote_response = client.quote(&quote_request()).await.unwrap();
+        assert_eq!(quote_response.out_amount, 146_829_450);
+        assert_eq!(quote_response.route_plan.len(), 2);
+
+        let requests = server.received_requests().await.unwrap();
+        let accept_encoding = requests[0].headers["accept-encoding"].to_str().unwrap();
+        assert!(accept_encoding.contains("gzip"), "{accept_encoding}");
+        assert!(accept_encoding.contains("br"), "{accept_encoding}");
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn compression_can_be_disabled() {
+        let server = wiremock::MockServer::start().await;
+        mock_gzipped_quote(&server).await;
+        let client = JupiterSwapApiClientBuilder::new(server.uri())
+            .compression(false)
+            .build()
+            .unwrap();
+
+        // The compressed body is left as is
+        assert!(client.quote(&quote_request()).await.is_err());
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests[0].headers.get("accept-encoding").is_none());
+    }
+
+    async fn mock_health() -> wiremock::MockServer {
+        use wiremock::{
+            matchers::{method, path},
+            Mock, ResponseTemplate,
+        };
+
+        let server = wiremock::MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/health"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn pool_options_are_applied() {
+        let server = mock_health().await;
+        let client = JupiterSwapApiClientBuilder::new(server.uri())
+            .pool_max_idle_per_host(4)
+            .pool_idle_timeout(Duration::from_secs(30))
+            .connect_timeout(Duration::from_secs(1))
+            .tcp_keepalive(Duration::from_secs(15))
+            .tcp_nodelay(false)
+            .http2_keep_alive_interval(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        for _ in 0..2 {
+            client
+                .request::<(), (), serde_json::Value>(reqwest::Method::GET, "/health", None, None)
+                .await
+                .unwrap();
+        }
+    }
+
+    /// The mock server accepts HTTP/2 without negotiation, as an h2c proxy would
+    #[tokio::test]
+    async fn http2_prior_knowledge_reaches_an_h2c_server() {
+        let server = mock_health().await;
+        let client = JupiterSwapApiClientBuilder::new(server.uri())
+            .http2_prior_knowledge(true)
+            .build()
+            .unwrap();
+        client
+            .request::<(), (), serde_json::Value>(reqwest::Method::GET, "/health", None, None)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn http2_prior_knowledge_conflicts_with_http1_only() {
+        let error = JupiterSwapApiClientBuilder::new(PUBLIC_HOST.to_string())
+            .http2_prior_knowledge(true)
+            .http1_only(true)
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(error.kind(), ErrorKind::Misconfiguration(_)));
+    }
+
+    /// Self signed CA, only parsed
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    const TEST_CA_PEM: &str = "\
+-----BEGIN CERTIFICATE-----\n\
+MIIBqjCCAVGgAwIBAgIUNmjIHNKdrvmNtZuAAOflN02uRGIwCgYIKoZIzj0EAwIw\n\
+KjEoMCYGA1UEAwwfanVwaXRlci1zd2FwLWFwaS1jbGllbnQgdGVzdCBDQTAgFw0y\n\
+NjEwMTQwODM0MTJaGA8yMTI2MDkyMDA4MzQxMlowKjEoMCYGA1UEAwwfanVwaXRl\n\
+ci1zd2FwLWFwaS1jbGllbnQgdGVzdCBDQTBZMBMGByqGSM49AgEGCCqGSM49AwEH\n\
+A0IABNEqDpFUtmzkZrH+JJGvUcwmXuIeAlb5l0a7B28/rJb0SuQPdxEdW5iA+uGm\n\
+0bl5H0kEKicAU2uWbuAS7MKV4pujUzBRMB0GA1UdDgQWBBSUcmKodNu2Jhkzl4T1\n\
+0gWqZzfd2DAfBgNVHSMEGDAWgBSUcmKodNu2Jhkzl4T10gWqZzfd2DAPBgNVHRMB\n\
+Af8EBTADAQH/MAoGCCqGSM49BAMCA0cAMEQCIEvqsIseNngB5MJj+ehPLD89Hc9v\n\
+9HAUC1vmFMWd1dFSAiANQadA5EVEP65P5E+pOIrHd3cgREWTXDMH/RIIbovfWw==\n\
+-----END CERTIFICATE-----\n";
+
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    #[tokio::test]
+    async fn root_certificates_are_added_whatever_the_tls_backend() {
+        let server = mock_health().await;
+        let certificate = Certificate::from_pem(TEST_CA_PEM.as_bytes()).unwrap();
+        let client = JupiterSwapApiClientBuilder::new(server.uri())
+            .add_root_certificate(certificate.clone())
+            .add_root_certificate(certificate)
+            .build()
+            .unwrap();
+        client
+            .request::<(), (), serde_json::Value>(reqwest::Method::GET, "/health", None, None)
+            .await
+            .unwrap();
+    }
+
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    #[test]
+    fn invalid_root_certificate_is_rejected() {
+        // Depending on the backend the certificate is parsed right away or when building the client
+        let built = Certificate::from_der(b"not a certificate").map(|certificate| {
+            JupiterSwapApiClientBuilder::new(PUBLIC_HOST.to_string())
+                .add_root_certificate(certificate)
+                .build()
+        });
+        match built {
+            Err(_) => {}
+            Ok(Err(error)) => assert!(matches!(error.kind(), ErrorKind::Misconfiguration(_))),
+            Ok(Ok(_)) => panic!("an invalid certificate was accepted"),
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_go_through_the_proxy() {
+        // The mock server plays the proxy, plain HTTP requests are forwarded to it in absolute form
+        let proxy = mock_health().await;
+        let client = JupiterSwapApiClientBuilder::new("http://jupiter.invalid".to_string())
+            .proxy(proxy.uri())
+            .proxy_auth("user".to_string(), "pass".to_string())
+            .build()
+            .unwrap();
+        client
+            .request::<(), (), serde_json::Value>(reqwest::Method::GET, "/health", None, None)
+            .await
+            .unwrap();
+
+        let requests = proxy.received_requests().await.unwrap();
+        assert_eq!(requests[0].url.host_str(), Some("jupiter.invalid"));
+        assert_eq!(
+            requests[0].headers["proxy-authorization"],
+            "Basic dXNlcjpwYXNz"
+        );
+    }
+
+    #[test]
+    fn proxy_misconfigurations_are_rejected() {
+        for builder in [
+            JupiterSwapApiClientBuilder::new(PUBLIC_HOST.to_string())
+                .proxy("not a url".to_string()),
+            JupiterSwapApiClientBuilder::new(PUBLIC_HOST.to_string())
+                .proxy_auth("user".to_string(), "pass".to_string()),
+        ] {
+            let error = builder.build().err().unwrap();
+            assert!(matches!(error.kind(), ErrorKind::Misconfiguration(_)));
+        }
+        JupiterSwapApiClientBuilder::new(PUBLIC_HOST.to_string())
+            .no_proxy()
+            .build()
+            .unwrap();
+    }
+
+    #[cfg(feature = "socks")]
+    #[test]
+    fn socks_proxy_is_accepted() {
+        JupiterSwapApiClientBuilder::new(PUBLIC_HOST.to_string())
+            .proxy("socks5://127.0.0.1:1080".to_string())
+            .proxy_auth("user".to_string(), "pass".to_string())
+            .build()
+            .unwrap();
+    }
+
+    async fn mock_sized_body(status: u16, size: usize) -> wiremock::MockServer {
+        use wiremock::{
+            matchers::{method, path},
+            Mock, ResponseTemplate,
+        };
+
+        let server = wiremock::MockServer::start().await;
+        // A JSON string of `size` bytes
+        let body = format!("\"{}\"", "a".repeat(size - 2));
+        Mock::given(method("GET"))
+            .and(path("/health"))
+            .respond_with(ResponseTemplate::new(status).set_body_raw(body, "application/json"))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    async fn get_sized_body(
+        server: &wiremock::MockServer,
+        limit: usize,
+    ) -> Result<(), ClientError> {
+        let client = JupiterSwapApiClientBuilder::new(server.uri())
+            .max_response_size(limit)
+            .build()
+            .unwrap();
+        client
+            .request::<(), (), String>(reqwest::Method::GET, "/health", None, None)
+            .await
+            .map(drop)
+    }
+
+    #[tokio::test]
+    async fn body_over_max_response_size_is_rejected() {
+        let server = mock_sized_body(200, 1_024).await;
+        get_sized_body(&server, 1_024).await.unwrap();
+
+        let error = get_sized_body(&server, 1_023).await.unwrap_err();
+        assert!(
+            matches!(
+                error.kind(),
+                ErrorKind::ResponseTooLarge { limit: 1_023, .. }
+            ),
+            "{error:?}"
+        );
+        assert_eq!(error.status(), Some(reqwest::StatusCode::OK));
+    }
+
+    #[tokio::test]
+    async fn error_body_over_max_response_size_is_rejected() {
+        let server = mock_sized_body(500, 1_024).await;
+        let error = get_sized_body(&server, 1_023).await.unwrap_err();
+        assert!(
+            matches!(error.kind(), ErrorKind::ResponseTooLarge { .. }),
+            "{error:?}"
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn decompressed_body_over_max_response_size_is_rejected() {
+        let server = wiremock::MockServer::start().await;
+        mock_gzipped_quote(&server).await;
+        // Above the compressed size but below the decompressed one, only known while streaming
+        let client = JupiterSwapApiClientBuilder::new(server.uri())
+            .max_response_size(1_024)
+            .build()
+            .unwrap();
+
+        let error = client.quote(&quote_request()).await.unwrap_err();
+        assert!(
+            matches!(error.kind(), ErrorKind::ResponseTooLarge { .. }),
+            "{error:?}"
+        );
+    }
+
+    async fn mock_redirect(server: &wiremock::MockServer, from: &str, to: String) {
+        use wiremock::{matchers::path, Mock, ResponseTemplate};
+
+        Mock::given(path(from))
+            .respond_with(ResponseTemplate::new(307).insert_header("location", to))
+            .mount(server)
+            .await;
+    }
+
+    async fn get_health(
+        builder: JupiterSwapApiClientBuilder,
+        path: &str,
+    ) -> Result<serde_json::Value, ClientError> {
+        builder
+            .build()
+            .unwrap()
+            .request::<(), (), _>(reqwest::Method::GET, path, None, None)
+            .await
+    }
+
+    #[tokio::test]
+    async fn redirects_are_not_followed_by_default() {
+        let server = mock_health().await;
+        let location = format!("{}/health", server.uri());
+        mock_redirect(&server, "/old", location.clone()).await;
+
+        let error = get_health(JupiterSwapApiClientBuilder::new(server.uri()), "/old")
+            .await
+            .unwrap_err();
+        let ErrorKind::Redirected {
+            status,
+            location: Some(redirected_to),
+        } = error.kind()
+        else {
+            panic!("{error:?}");
+        };
+        assert_eq!(*status, reqwest::StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(*redirected_to, location);
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn redirects_to_the_same_host_keep_the_api_key() {
+        let server = mock_health().await;
+        mock_redirect(&server, "/old", format!("{}/health", server.uri())).await;
+
+        let builder = keyed_builder(&server.uri()).redirect(Policy::limited(3));
+        get_health(builder, "/old").await.unwrap();
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[1].url.path(), "/health");
+        assert_eq!(requests[1].headers.get("x-api-key").unwrap(), "key");
+    }
+
+    #[tokio::test]
+    async fn api_key_is_not_forwarded_to_another_host() {
+        let server = wiremock::MockServer::start().await;
+        let other_host = mock_health().await;
+        mock_redirect(&server, "/health", format!("{}/health", other_host.uri())).await;
+
+        let builder = keyed_builder(&server.uri()).redirect(Policy::limited(3));
+        let error = get_health(builder, "/health").await.unwrap_err();
+        assert!(
+            matches!(error.kind(), ErrorKind::Redirected { .. }),
+            "{error:?}"
+        );
+        assert!(other_host.received_requests().await.unwrap().is_empty());
+
+        // reqwest strips the authorization header from redirects to another host
+        let builder = keyed_builder(&server.uri())
+            .auth_scheme(AuthScheme::Bearer)
+            .redirect(Policy::limited(3));
+        get_health(builder, "/health").await.unwrap();
+        let requests = other_host.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(!requests[0].headers.contains_key("authorization"));
+    }
+}
@@ -0,0 +1,96 @@
+//! TTL cache shared by the clones of a client, backing the token and quote caches
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{PoisonError, RwLock},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+#[derive(Debug)]
+pub(crate) struct TtlCache<K, V> {
+    ttl: Duration,
+    max_entries: usize,
+    entries: RwLock<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> TtlCache<K, V> {
+    pub(crate) fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            entries: RwLock::default(),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &K) -> Option<V> {
+        let entries = self.entries.read().unwrap_or_else(PoisonError::into_inner);
+        entries
+            .get(key)
+            .filter(|(inserted_at, _)| inserted_at.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Expired entries are evicted first then the oldest ones once `max_entries` is reached
+    pub(crate) fn insert(&self, key: K, value: V) {
+        if self.max_entries == 0 || self.ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.write().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            entries.retain(|_, (inserted_at, _)| inserted_at.elapsed() < self.ttl);
+            while entries.len() >= self.max_entries {
+                let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, (inserted_at, _))| *inserted_at)
+                    .map(|(key, _)| key.clone())
+                else {
+                    break;
+                };
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, (Instant::now(), value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_expire() {
+        let cache = TtlCache::new(Duration::from_millis(20), 4);
+        cache.insert("key", 1);
+        assert_eq!(cache.get(&"key"), Some(1));
+        std::thread::sleep(Duration::from_millis(40));
+        assert_eq!(cache.get(&"key"), None);
+    }
+
+    #[test]
+    fn oldest_entry_is_evicted_when_full() {
+        let cache = TtlCache::new(Duration::from_secs(60), 2);
+        cache.insert("first", 1);
+        cache.insert("second", 2);
+        // Replacing a key does not evict
+        cache.insert("first", 3);
+        assert_eq!(cache.get(&"second"), Some(2));
+        cache.insert("third", 4);
+        assert_eq!(cache.get(&"second"), None);
+        assert_eq!(cache.get(&"first"), Some(3));
+        assert_eq!(cache.get(&"third"), Some(4));
+    }
+
+    #[test]
+    fn disabled_cache_stores_nothing() {
+        for cache in [
+            TtlCache::new(Duration::ZERO, 4),
+            TtlCache::new(Duration::from_secs(60), 0),
+        ] {
+            cache.insert("key", 1);
+            assert_eq!(cache.get(&"key"), None);
+        }
+    }
+}
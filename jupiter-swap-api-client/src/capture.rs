@@ -0,0 +1,175 @@
+//! Capture of the last HTTP exchange for support escalations,
+//! see [`JupiterSwapApiClientBuilder::debug_capture`](crate::builder::JupiterSwapApiClientBuilder::debug_capture)
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName},
+    Client, Method, RequestBuilder, Response, StatusCode,
+};
+
+/// Header values replaced by `<redacted>` in captured exchanges
+/// on top of the api key header of the client
+const REDACTED_HEADERS: [&str; 2] = ["x-api-key", "authorization"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpExchange {
+    pub method: Method,
+    pub url: String,
+    pub request_headers: Vec<(String, String)>,
+    pub request_body: Option<String>,
+    /// `None` when no response was received
+    pub status: Option<StatusCode>,
+    pub response_headers: Vec<(String, String)>,
+    pub response_body: Option<String>,
+}
+
+pub(crate) type LastExchange = Arc<Mutex<Option<HttpExchange>>>;
+
+fn redacted(headers: &HeaderMap, api_key_header: &HeaderName) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) || name == api_key_header {
+                "<redacted>".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+/// Send `request` and store the exchange in `last_exchange`,
+/// the response body is buffered to be captured then handed back in a new response
+pub(crate) async fn send_captured(
+    http_client: &Client,
+    request: RequestBuilder,
+    last_exchange: &LastExchange,
+    api_key_header: &HeaderName,
+) -> Result<Response, reqwest::Error> {
+    let request = request.build()?;
+    let mut exchange = HttpExchange {
+        method: request.method().clone(),
+        url: request.url().to_string(),
+        request_headers: redacted(request.headers(), api_key_header),
+        request_body: request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(|body| String::from_utf8_lossy(body).into_owned()),
+        status: None,
+        response_headers: Vec::new(),
+        response_body: None,
+    };
+
+    let response = match http_client.execute(request).await {
+        Ok(response) => {
+            let status = response.status();
+            let version = response.version();
+            let headers = response.headers().clone();
+            exchange.status = Some(status);
+            exchange.response_headers = redacted(&headers, api_key_header);
+            response.bytes().await.map(|body| {
+                exchange.response_body = Some(String::from_utf8_lossy(&body).into_owned());
+                let mut response = http::Response::new(body);
+                *response.status_mut() = status;
+                *response.version_mut() = version;
+                *response.headers_mut() = headers;
+                Response::from(response)
+            })
+        }
+        Err(e) => Err(e),
+    };
+    *last_exchange.lock().unwrap_or_else(PoisonError::into_inner) = Some(exchange);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{
+        builder::{AuthScheme, JupiterSwapApiClientBuilder},
+        quote::{QuoteRequest, QuoteResponse},
+        request_options::RequestOptions,
+    };
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn credentials_are_redacted() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_static("key"));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer key"));
+        headers.insert("x-gateway-key", HeaderValue::from_static("key"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        let redacted = redacted(&headers, &HeaderName::from_static("x-gateway-key"));
+        assert_eq!(header(&redacted, "x-api-key"), Some("<redacted>"));
+        assert_eq!(header(&redacted, "authorization"), Some("<redacted>"));
+        assert_eq!(header(&redacted, "x-gateway-key"), Some("<redacted>"));
+        assert_eq!(header(&redacted, "content-type"), Some("application/json"));
+    }
+
+    #[tokio::test]
+    async fn captured_request_hides_the_credentials() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(QuoteResponse::default()))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClientBuilder::new(server.uri())
+            .api_key("secret".to_string())
+            .auth_scheme(AuthScheme::Header {
+                name: HeaderName::from_static("x-gateway-key"),
+                prefix: None,
+            })
+            .debug_capture(true)
+            .build()
+            .unwrap();
+        let options = RequestOptions::default()
+            .header(
+                HeaderName::from_static("x-api-key"),
+                HeaderValue::from_static("secret"),
+            )
+            .header(AUTHORIZATION, HeaderValue::from_static("Bearer secret"))
+            .header(
+                HeaderName::from_static("x-trace"),
+                HeaderValue::from_static("trace"),
+            );
+
+        client
+            .quote_with_options(
+                &QuoteRequest {
+                    amount: 1_000,
+                    ..QuoteRequest::default()
+                },
+                &options,
+            )
+            .await
+            .unwrap();
+
+        let exchange = client.last_exchange().unwrap();
+        let headers = &exchange.request_headers;
+        for name in ["x-api-key", "authorization", "x-gateway-key"] {
+            assert_eq!(header(headers, name), Some("<redacted>"), "{name}");
+        }
+        assert_eq!(header(headers, "x-trace"), Some("trace"));
+        assert!(!format!("{exchange:?}").contains("secret"));
+        // The credentials still reach the server
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[0].headers["x-gateway-key"], "secret");
+        assert_eq!(exchange.status, Some(StatusCode::OK));
+    }
+}
@@ -0,0 +1,134 @@
+//! Parsing of compute budget program instructions
+
+use solana_compute_budget_interface::{self as compute_budget, ComputeBudgetInstruction};
+use solana_instruction::Instruction;
+
+const SET_COMPUTE_UNIT_LIMIT_DISCRIMINATOR: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE_DISCRIMINATOR: u8 = 3;
+
+/// Little endian payload of a compute budget instruction with the given discriminator,
+/// `None` for other programs, other variants or malformed data
+fn payload<const N: usize>(instruction: &Instruction, discriminator: u8) -> Option<[u8; N]> {
+    if instruction.program_id != compute_budget::id() {
+        return None;
+    }
+    match instruction.data.split_first() {
+        Some((&d, payload)) if d == discriminator => payload.try_into().ok(),
+        _ => None,
+    }
+}
+
+/// Units requested by a `SetComputeUnitLimit` instruction
+pub fn compute_unit_limit(instruction: &Instruction) -> Option<u32> {
+    payload(instruction, SET_COMPUTE_UNIT_LIMIT_DISCRIMINATOR).map(u32::from_le_bytes)
+}
+
+/// Micro lamports per compute unit of a `SetComputeUnitPrice` instruction
+pub fn compute_unit_price_micro_lamports(instruction: &Instruction) -> Option<u64> {
+    payload(instruction, SET_COMPUTE_UNIT_PRICE_DISCRIMINATOR).map(u64::from_le_bytes)
+}
+
+/// Compute unit limit and price requested by a set of compute budget instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ComputeBudget {
+    pub unit_limit: Option<u32>,
+    /// Micro lamports per compute unit
+    pub unit_price: Option<u64>,
+}
+
+impl ComputeBudget {
+    /// The first limit and price found, other instructions are ignored
+    pub fn from_instructions(instructions: &[Instruction]) -> Self {
+        Self {
+            unit_limit: instructions.iter().find_map(compute_unit_limit),
+            unit_price: instructions
+                .iter()
+                .find_map(compute_unit_price_micro_lamports),
+        }
+    }
+
+    /// Compute budget instructions setting the limit and price which are set
+    pub fn instructions(&self) -> Vec<Instruction> {
+        self.unit_limit
+            .map(ComputeBudgetInstruction::set_compute_unit_limit)
+            .into_iter()
+            .chain(
+                self.unit_price
+                    .map(ComputeBudgetInstruction::set_compute_unit_price),
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_pubkey::Pubkey;
+
+    use super::*;
+
+    fn compute_budget_instruction(data: &[u8]) -> Instruction {
+        Instruction::new_with_bytes(compute_budget::id(), data, Vec::new())
+    }
+
+    #[test]
+    fn parses_hand_encoded_instructions() {
+        let limit = compute_budget_instruction(&[2, 0xc0, 0x27, 0x09, 0x00]);
+        assert_eq!(compute_unit_limit(&limit), Some(600_000));
+        assert_eq!(compute_unit_price_micro_lamports(&limit), None);
+
+        let price = compute_budget_instruction(&[3, 0x10, 0x27, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(compute_unit_price_micro_lamports(&price), Some(10_000));
+        assert_eq!(compute_unit_limit(&price), None);
+    }
+
+    #[test]
+    fn encoding_matches_the_compute_budget_interface() {
+        let limit = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+        assert_eq!(limit.data, [2, 0xc0, 0x5c, 0x15, 0x00]);
+        assert_eq!(compute_unit_limit(&limit), Some(1_400_000));
+        let price = ComputeBudgetInstruction::set_compute_unit_price(u64::MAX);
+        assert_eq!(compute_unit_price_micro_lamports(&price), Some(u64::MAX));
+    }
+
+    #[test]
+    fn unknown_or_malformed_instructions_are_none() {
+        // RequestHeapFrame and SetLoadedAccountsDataSizeLimit
+        for data in [&[1, 0, 0, 4, 0][..], &[4, 0, 0, 1, 0]] {
+            let instruction = compute_budget_instruction(data);
+            assert_eq!(compute_unit_limit(&instruction), None);
+            assert_eq!(compute_unit_price_micro_lamports(&instruction), None);
+        }
+        for data in [&[][..], &[9], &[2, 1, 2], &[3, 1, 2, 3, 4, 5, 6, 7, 8, 9]] {
+            let instruction = compute_budget_instruction(data);
+            assert_eq!(compute_unit_limit(&instruction), None);
+            assert_eq!(compute_unit_price_micro_lamports(&instruction), None);
+        }
+        let other_program = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[2, 0xc0, 0x27, 0x09, 0x00],
+            Vec::new(),
+        );
+        assert_eq!(compute_unit_limit(&other_program), None);
+    }
+
+    #[test]
+    fn compute_budget_round_trips_through_instructions() {
+        let budget = ComputeBudget {
+            unit_limit: Some(200_000),
+            unit_price: Some(5_000),
+        };
+        let instructions = budget.instructions();
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(ComputeBudget::from_instructions(&instructions), budget);
+
+        let price_only = ComputeBudget {
+            unit_limit: None,
+            unit_price: Some(1),
+        };
+        assert_eq!(
+            ComputeBudget::from_instructions(&price_only.instructions()),
+            price_only
+        );
+        assert_eq!(ComputeBudget::default().instructions(), Vec::new());
+    }
+}
@@ -0,0 +1,312 @@
+//! Conditional requests of large responses which rarely change, such as the token list
+//!
+//! The client remembers the `ETag` of the last responses and sends it back as `If-None-Match`,
+//! a `304 Not Modified` is then answered with the value parsed from the previous response.
+
+use std::{
+    any::Any,
+    collections::VecDeque,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use reqwest::{
+    header::{HeaderValue, ETAG, IF_NONE_MATCH},
+    Method, StatusCode,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    check_status_code_and_deserialize, metrics::Endpoint, request_options::RequestOptions,
+    ClientError, JupiterSwapApiClient,
+};
+
+/// Responses remembered per client, the least recently used one being evicted first
+const ETAG_CACHE_CAPACITY: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConditionalResponse<T> {
+    pub value: T,
+    /// The server answered `304 Not Modified` and `value` comes from a previous response
+    pub from_cache: bool,
+    /// `ETag` of the response `value` was parsed from, `None` when the server sent none
+    pub etag: Option<String>,
+}
+
+struct Validated {
+    key: String,
+    etag: String,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+/// LRU of the last responses with an `ETag` by request, shared by the clones of a client
+#[derive(Default)]
+pub(crate) struct EtagCache {
+    /// Most recently used last
+    entries: Mutex<VecDeque<Validated>>,
+}
+
+impl EtagCache {
+    fn get<T: Clone + 'static>(&self, key: &str) -> Option<(String, T)> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let position = entries.iter().position(|entry| entry.key == key)?;
+        let entry = entries.remove(position)?;
+        let validated = entry
+            .value
+            .downcast_ref::<T>()
+            .map(|value| (entry.etag.clone(), value.clone()));
+        entries.push_back(entry);
+        validated
+    }
+
+    fn insert<T: Send + Sync + 'static>(&self, key: String, etag: String, value: T) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.retain(|entry| entry.key != key);
+        if entries.len() >= ETAG_CACHE_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(Validated {
+            key,
+            etag,
+            value: Arc::new(value),
+        });
+    }
+
+    fn remove(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.retain(|entry| entry.key != key);
+    }
+}
+
+impl JupiterSwapApiClient {
+    /// GET `path`, revalidating the last response to the same request with `If-None-Match`
+    pub(crate) async fn get_conditional<Q, R>(
+        &self,
+        endpoint: Endpoint,
+        path: &str,
+        query: &Q,
+    ) -> Result<ConditionalResponse<R>, ClientError>
+    where
+        Q: Serialize + ?Sized,
+        R: DeserializeOwned + Clone + Send + Sync + 'static,
+    {
+        let key = serde_json::to_string(query)
+            .map(|query| format!("{endpoint} {path} {query}"))
+            .map_err(|e| ClientError::from(e).with_endpoint(endpoint))?;
+        let cached = self.etag_cache.get::<R>(&key);
+        let mut options = RequestOptions::default();
+        if let Some((etag, _)) = &cached {
+            let etag = HeaderValue::from_str(etag)
+                .map_err(|e| ClientError::from(e).with_endpoint(endpoint))?;
+            options = options.header(IF_NONE_MATCH, etag);
+        }
+        let response = self
+            .send_request(
+                endpoint,
+                Method::GET,
+                path,
+                Some(query),
+                None::<&()>,
+                &options,
+            )
+            .await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some((etag, value)) = cached {
+                return Ok(ConditionalResponse {
+                    value,
+                    from_cache: true,
+                    etag: Some(etag),
+                });
+            }
+        }
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(ToString::to_string);
+        let value: R = check_status_code_and_deserialize(response).await?;
+        match &etag {
+            Some(etag) => self.etag_cache.insert(key, etag.clone(), value.clone()),
+            None => self.etag_cache.remove(&key),
+        }
+        Ok(ConditionalResponse {
+            value,
+            from_cache: false,
+            etag,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, Request, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::tokens::TokenInfo;
+
+    fn labels(label: &str) -> HashMap<String, String> {
+        HashMap::from([(
+            "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4".into(),
+            label.into(),
+        )])
+    }
+
+    fn labels_response(etag: &str, label: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200)
+            .insert_header(ETAG, etag)
+            .set_body_json(labels(label))
+    }
+
+    async fn mock_not_modified(server: &MockServer, etag: &str) {
+        Mock::given(method("GET"))
+            .and(path("/program-id-to-label"))
+            .and(header(IF_NONE_MATCH, etag))
+            .respond_with(ResponseTemplate::new(304).insert_header(ETAG, etag))
+            .mount(server)
+            .await;
+    }
+
+    fn if_none_match(request: &Request) -> Option<&str> {
+        request
+            .headers
+            .get(IF_NONE_MATCH)
+            .and_then(|etag| etag.to_str().ok())
+    }
+
+    #[tokio::test]
+    async fn not_modified_returns_the_cached_value() {
+        let server = MockServer::start().await;
+        mock_not_modified(&server, "\"v1\"").await;
+        Mock::given(method("GET"))
+            .and(path("/program-id-to-label"))
+            .respond_with(labels_response("\"v1\"", "Jupiter"))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        let first = client.program_id_to_label().await.unwrap();
+        assert_eq!(
+            first,
+            ConditionalResponse {
+                value: labels("Jupiter"),
+                from_cache: false,
+                etag: Some("\"v1\"".to_string()),
+            }
+        );
+        // Shared by the clones of the client
+        let second = client.clone().program_id_to_label().await.unwrap();
+        assert_eq!(
+            second,
+            ConditionalResponse {
+                from_cache: true,
+                ..first
+            }
+        );
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(if_none_match(&requests[0]), None);
+        assert_eq!(if_none_match(&requests[1]), Some("\"v1\""));
+    }
+
+    #[tokio::test]
+    async fn changed_etag_replaces_the_cached_value() {
+        let server = MockServer::start().await;
+        mock_not_modified(&server, "\"v2\"").await;
+        Mock::given(method("GET"))
+            .and(path("/program-id-to-label"))
+            .respond_with(labels_response("\"v1\"", "Jupiter"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/program-id-to-label"))
+            .respond_with(labels_response("\"v2\"", "Jupiter v6"))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        let first = client.program_id_to_label().await.unwrap();
+        assert_eq!(first.value, labels("Jupiter"));
+        let changed = client.program_id_to_label().await.unwrap();
+        assert!(!changed.from_cache);
+        assert_eq!(changed.value, labels("Jupiter v6"));
+        assert_eq!(changed.etag.as_deref(), Some("\"v2\""));
+        let cached = client.program_id_to_label().await.unwrap();
+        assert!(cached.from_cache);
+        assert_eq!(cached.value, labels("Jupiter v6"));
+
+        let requests = server.received_requests().await.unwrap();
+        let sent: Vec<_> = requests.iter().map(if_none_match).collect();
+        assert_eq!(sent, [None, Some("\"v1\""), Some("\"v2\"")]);
+    }
+
+    #[tokio::test]
+    async fn response_without_etag_is_not_revalidated() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/program-id-to-label"))
+            .respond_with(labels_response("\"v1\"", "Jupiter"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/program-id-to-label"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(labels("Jupiter")))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        client.program_id_to_label().await.unwrap();
+        let without_etag = client.program_id_to_label().await.unwrap();
+        assert_eq!(without_etag.etag, None);
+        client.program_id_to_label().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let sent: Vec<_> = requests.iter().map(if_none_match).collect();
+        assert_eq!(sent, [None, Some("\"v1\""), None]);
+    }
+
+    #[tokio::test]
+    async fn requests_are_cached_by_query() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/tag"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(ETAG, "\"v1\"")
+                    .set_body_json(Vec::<TokenInfo>::new()),
+            )
+            .mount(&server)
+            .await;
+        let mut client = JupiterSwapApiClient::new(server.uri(), None);
+        client.tokens_base_path = server.uri();
+
+        client.tokens_by_tag("verified").await.unwrap();
+        client.tokens_by_tag("lst").await.unwrap();
+        client.tokens_by_tag("verified").await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let sent: Vec<_> = requests.iter().map(if_none_match).collect();
+        assert_eq!(sent, [None, None, Some("\"v1\"")]);
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used() {
+        let cache = EtagCache::default();
+        for index in 0..ETAG_CACHE_CAPACITY {
+            cache.insert(index.to_string(), "etag".to_string(), index);
+        }
+        // Used, so no longer the least recently used
+        assert_eq!(cache.get::<usize>("0"), Some(("etag".to_string(), 0)));
+        cache.insert("new".to_string(), "etag".to_string(), 0_usize);
+        assert!(cache.get::<usize>("0").is_some());
+        assert!(cache.get::<usize>("1").is_none());
+        assert!(cache.get::<usize>("new").is_some());
+        // Another type under the same key is a miss
+        assert!(cache.get::<String>("new").is_none());
+    }
+}
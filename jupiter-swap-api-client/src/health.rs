@@ -0,0 +1,138 @@
+//! Health probe of a self-hosted API
+
+use reqwest::Method;
+use serde::Deserialize;
+
+use crate::{
+    metrics::Endpoint, read_body, request_options::RequestOptions, ClientError, ErrorKind,
+    JupiterSwapApiClient,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HealthStatus {
+    /// The server answered `/health` with a success status
+    pub up: bool,
+    /// Last slot the market cache was updated at, when reported by the server
+    pub last_indexed_slot: Option<u64>,
+}
+
+impl HealthStatus {
+    /// Whether the server is up and its market cache lags `current_slot` by at most `max_slot_lag`.
+    /// A server not reporting its last indexed slot is considered fresh.
+    pub fn is_ready(&self, current_slot: u64, max_slot_lag: u64) -> bool {
+        match self.last_indexed_slot {
+            Some(slot) => self.up && current_slot.saturating_sub(slot) <= max_slot_lag,
+            None => self.up,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HealthResponseInternal {
+    #[serde(alias = "slot", alias = "contextSlot")]
+    last_indexed_slot: Option<u64>,
+}
+
+impl JupiterSwapApiClient {
+    /// GET /health of the active base path, a non success status or a server which cannot be reached
+    /// is reported as down rather than as an error. Plain text bodies such as `OK` are accepted.
+    pub async fn health(&self) -> Result<HealthStatus, ClientError> {
+        let url = format!("{}/health", self.active_base_path());
+        let response = match self
+            .send(
+                Endpoint::Health,
+                Method::GET,
+                &url,
+                &RequestOptions::default(),
+                |request| request,
+            )
+            .await
+        {
+            Ok(response) => response,
+            Err(e) if is_unreachable(&e) => return Ok(HealthStatus::default()),
+            Err(e) => return Err(e),
+        };
+        if !response.status().is_success() {
+            return Ok(HealthStatus::default());
+        }
+        let body = read_body(response).await?;
+        let last_indexed_slot = serde_json::from_slice::<HealthResponseInternal>(&body)
+            .ok()
+            .and_then(|health| health.last_indexed_slot);
+        Ok(HealthStatus {
+            up: true,
+            last_indexed_slot,
+        })
+    }
+}
+
+fn is_unreachable(error: &ClientError) -> bool {
+    matches!(error.kind(), ErrorKind::DeserializationError(e) if e.is_connect() || e.is_timeout())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    async fn health_of(response: ResponseTemplate) -> HealthStatus {
+        let server = MockServer::start().await;
+        Mock::given(path("/health"))
+            .respond_with(response)
+            .mount(&server)
+            .await;
+        JupiterSwapApiClient::new(server.uri(), None)
+            .health()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn up_with_the_last_indexed_slot() {
+        let health = health_of(
+            ResponseTemplate::new(200).set_body_json(json!({ "contextSlot": 325679512 })),
+        )
+        .await;
+        assert_eq!(
+            health,
+            HealthStatus {
+                up: true,
+                last_indexed_slot: Some(325_679_512),
+            }
+        );
+        assert!(health.is_ready(325_679_542, 30));
+        assert!(!health.is_ready(325_679_543, 30));
+    }
+
+    #[tokio::test]
+    async fn up_with_a_plain_text_body() {
+        let health = health_of(ResponseTemplate::new(200).set_body_string("OK")).await;
+        assert_eq!(
+            health,
+            HealthStatus {
+                up: true,
+                last_indexed_slot: None,
+            }
+        );
+        assert!(health.is_ready(u64::MAX, 0));
+    }
+
+    #[tokio::test]
+    async fn down_on_a_non_success_status() {
+        let health = health_of(ResponseTemplate::new(503)).await;
+        assert_eq!(health, HealthStatus::default());
+        assert!(!health.is_ready(0, u64::MAX));
+    }
+
+    #[tokio::test]
+    async fn down_when_the_server_cannot_be_reached() {
+        let health = JupiterSwapApiClient::new("http://127.0.0.1:9".to_string(), None)
+            .health()
+            .await
+            .unwrap();
+        assert_eq!(health, HealthStatus::default());
+    }
+}
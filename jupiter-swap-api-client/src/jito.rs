@@ -0,0 +1,92 @@
+//! Helpers to submit swaps through Jito bundles
+//!
+//! Bundles pay validators with a tip transfer instead of a compute budget priority fee,
+//! use [`TransactionConfig::for_jito`](crate::transaction_config::TransactionConfig::for_jito) to request a swap without one.
+//! When assembling the transaction from `/swap-instructions`, append the tip after
+//! [`SwapInstructionsResponse::instructions`](crate::swap::SwapInstructionsResponse::instructions) so it is the last instruction.
+
+use solana_instruction::Instruction;
+use solana_pubkey::{pubkey, Pubkey};
+use solana_system_interface::instruction as system_instruction;
+
+use crate::random::random_u64;
+
+/// Canonical Jito tip accounts
+pub const TIP_ACCOUNTS: [Pubkey; 8] = [
+    pubkey!("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"),
+    pubkey!("HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe"),
+    pubkey!("Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY"),
+    pubkey!("ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49"),
+    pubkey!("DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh"),
+    pubkey!("ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt"),
+    pubkey!("DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL"),
+    pubkey!("3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT"),
+];
+
+/// Pick a tip account at random, spreading tips avoids write locking a single account
+pub fn random_tip_account() -> Pubkey {
+    TIP_ACCOUNTS[(random_u64() % TIP_ACCOUNTS.len() as u64) as usize]
+}
+
+/// Build a tip transfer from `payer` to a random tip account
+pub fn build_tip_instruction(payer: &Pubkey, lamports: u64) -> Instruction {
+    build_tip_instruction_to(payer, &random_tip_account(), lamports)
+}
+
+/// Build a tip transfer from `payer` to the given tip account
+pub fn build_tip_instruction_to(
+    payer: &Pubkey,
+    tip_account: &Pubkey,
+    lamports: u64,
+) -> Instruction {
+    system_instruction::transfer(payer, tip_account, lamports)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use solana_instruction::AccountMeta;
+
+    use super::*;
+    use crate::transaction_config::TransactionConfig;
+
+    #[test]
+    fn tip_instruction_is_a_system_transfer() {
+        let payer = Pubkey::new_unique();
+        let instruction = build_tip_instruction_to(&payer, &TIP_ACCOUNTS[0], 1_000_000);
+
+        assert_eq!(instruction.program_id, solana_system_interface::program::ID);
+        assert_eq!(
+            instruction.accounts,
+            [
+                AccountMeta::new(payer, true),
+                AccountMeta::new(TIP_ACCOUNTS[0], false),
+            ]
+        );
+        // Transfer discriminator then the lamports, both little endian
+        assert_eq!(
+            instruction.data,
+            [2, 0, 0, 0, 0x40, 0x42, 0x0f, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn random_tip_goes_to_a_tip_account() {
+        let payer = Pubkey::new_unique();
+        for _ in 0..32 {
+            let instruction = build_tip_instruction(&payer, u64::MAX);
+            assert!(TIP_ACCOUNTS.contains(&instruction.accounts[1].pubkey));
+            assert_eq!(instruction.data[4..], u64::MAX.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn jito_config_only_sends_the_tip() {
+        let config = serde_json::to_value(TransactionConfig::for_jito(10_000)).unwrap();
+        assert_eq!(
+            config["prioritizationFeeLamports"],
+            json!({ "jitoTipLamports": 10_000 })
+        );
+        assert!(config.get("computeUnitPriceMicroLamports").is_none());
+    }
+}
@@ -0,0 +1,246 @@
+//! Quotes of an order split into chunks, to estimate the depth available for a large amount
+
+use rust_decimal::{
+    prelude::{FromPrimitive, ToPrimitive},
+    Decimal,
+};
+use thiserror::Error;
+
+use crate::{
+    metrics::Endpoint,
+    quote::{QuoteRequest, QuoteResponse},
+    ClientError, JupiterSwapApiClient,
+};
+
+/// Tolerance on the sum of the ladder fractions
+const FRACTIONS_SUM_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum LadderError {
+    #[error("A ladder needs at least one fraction")]
+    NoFractions,
+    #[error("Fraction {index} is {fraction}, fractions must be positive")]
+    InvalidFraction { index: usize, fraction: f64 },
+    #[error("Fractions sum to {0} instead of 1")]
+    FractionsSum(f64),
+    #[error("Chunk {index} rounds down to a zero amount")]
+    ZeroAmount { index: usize },
+}
+
+/// Totals of the quotes of a ladder
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LadderSummary {
+    pub in_amount: u128,
+    pub out_amount: u128,
+    /// `out_amount / in_amount` in raw units, `None` for an empty ladder
+    pub blended_price: Option<Decimal>,
+    /// Highest price impact of the chunks
+    pub worst_price_impact_pct: Decimal,
+}
+
+/// Requests for `fractions` of `base.amount`, which must sum to 1.
+/// Amounts are computed in decimal rather than `f64`, which cannot represent every `u64`, rounded down
+/// and the last chunk absorbs the remainder, so that they sum to `base.amount`.
+pub fn ladder_requests(
+    base: &QuoteRequest,
+    fractions: &[f64],
+) -> Result<Vec<QuoteRequest>, LadderError> {
+    if fractions.is_empty() {
+        return Err(LadderError::NoFractions);
+    }
+    if let Some((index, &fraction)) = fractions
+        .iter()
+        .enumerate()
+        .find(|(_, fraction)| !fraction.is_finite() || **fraction <= 0.0)
+    {
+        return Err(LadderError::InvalidFraction { index, fraction });
+    }
+    let sum: f64 = fractions.iter().sum();
+    if (sum - 1.0).abs() > FRACTIONS_SUM_TOLERANCE {
+        return Err(LadderError::FractionsSum(sum));
+    }
+
+    let mut remaining = base.amount;
+    let mut requests = Vec::with_capacity(fractions.len());
+    for (index, fraction) in fractions.iter().enumerate() {
+        let amount = if index == fractions.len() - 1 {
+            remaining
+        } else {
+            chunk_amount(base.amount, *fraction).min(remaining)
+        };
+        if amount == 0 {
+            return Err(LadderError::ZeroAmount { index });
+        }
+        remaining -= amount;
+        requests.push(QuoteRequest {
+            amount,
+            ..base.clone()
+        });
+    }
+    Ok(requests)
+}
+
+/// `fraction` of `amount` rounded down
+fn chunk_amount(amount: u64, fraction: f64) -> u64 {
+    Decimal::from_f64(fraction)
+        .and_then(|fraction| Decimal::from(amount).checked_mul(fraction))
+        .and_then(|chunk| chunk.floor().to_u64())
+        .unwrap_or(0)
+}
+
+pub fn summarize_ladder(quote_responses: &[QuoteResponse]) -> LadderSummary {
+    let in_amount: u128 = quote_responses
+        .iter()
+        .map(|quote_response| u128::from(quote_response.in_amount))
+        .sum();
+    let out_amount: u128 = quote_responses
+        .iter()
+        .map(|quote_response| u128::from(quote_response.out_amount))
+        .sum();
+    let blended_price = (in_amount != 0)
+        .then(|| Decimal::from(out_amount).checked_div(Decimal::from(in_amount)))
+        .flatten();
+    let worst_price_impact_pct = quote_responses
+        .iter()
+        .map(|quote_response| quote_response.price_impact_pct)
+        .max()
+        .unwrap_or_default();
+    LadderSummary {
+        in_amount,
+        out_amount,
+        blended_price,
+        worst_price_impact_pct,
+    }
+}
+
+impl JupiterSwapApiClient {
+    /// Quote the chunks of [`ladder_requests`] with at most `concurrency` requests in flight,
+    /// in the order of `fractions`. Fails with the first failed chunk.
+    pub async fn quote_ladder(
+        &self,
+        base: &QuoteRequest,
+        fractions: &[f64],
+        concurrency: usize,
+    ) -> Result<Vec<QuoteResponse>, ClientError> {
+        let quote_requests = ladder_requests(base, fractions)
+            .map_err(|e| ClientError::from(e).with_endpoint(Endpoint::Quote))?;
+        self.quote_many(&quote_requests, concurrency)
+            .await
+            .into_iter()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amounts(amount: u64, fractions: &[f64]) -> Result<Vec<u64>, LadderError> {
+        let base = QuoteRequest {
+            amount,
+            ..QuoteRequest::default()
+        };
+        Ok(ladder_requests(&base, fractions)?
+            .into_iter()
+            .map(|quote_request| quote_request.amount)
+            .collect())
+    }
+
+    #[test]
+    fn last_chunk_absorbs_rounding() {
+        assert_eq!(amounts(1001, &[0.25, 0.25, 0.5]), Ok(vec![250, 250, 501]));
+        let third = 1.0 / 3.0;
+        assert_eq!(amounts(1001, &[third; 3]), Ok(vec![333, 333, 335]));
+        assert_eq!(
+            amounts(1001, &[0.1, 0.2, 0.3, 0.4]),
+            Ok(vec![100, 200, 300, 401])
+        );
+        assert_eq!(amounts(1001, &[1.0]), Ok(vec![1001]));
+    }
+
+    #[test]
+    fn chunks_of_large_amounts_are_exact() {
+        // u64::MAX as f64 rounds up to 2^64, a quarter of which is one above the exact amount
+        assert_eq!(
+            amounts(u64::MAX, &[0.25, 0.75]),
+            Ok(vec![4_611_686_018_427_387_903, 13_835_058_055_282_163_712])
+        );
+        let chunks = amounts(u64::MAX, &[0.1; 10]).unwrap();
+        assert_eq!(chunks[0], 1_844_674_407_370_955_161);
+        assert_eq!(
+            chunks.iter().map(|chunk| u128::from(*chunk)).sum::<u128>(),
+            u128::from(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn rejects_fractions_not_summing_to_one() {
+        assert_eq!(
+            amounts(1000, &[0.5, 0.4]),
+            Err(LadderError::FractionsSum(0.9))
+        );
+        assert!(matches!(
+            amounts(1000, &[0.5, 0.6]),
+            Err(LadderError::FractionsSum(sum)) if sum > 1.0
+        ));
+        // Floating point error within the tolerance
+        assert_eq!(amounts(1000, &[0.1, 0.2, 0.7]), Ok(vec![100, 200, 700]));
+    }
+
+    #[test]
+    fn rejects_invalid_fractions() {
+        assert_eq!(amounts(1000, &[]), Err(LadderError::NoFractions));
+        assert_eq!(
+            amounts(1000, &[1.5, -0.5]),
+            Err(LadderError::InvalidFraction {
+                index: 1,
+                fraction: -0.5
+            })
+        );
+        assert!(matches!(
+            amounts(1000, &[f64::NAN]),
+            Err(LadderError::InvalidFraction { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_zero_amount_chunks() {
+        assert_eq!(
+            amounts(3, &[0.1, 0.9]),
+            Err(LadderError::ZeroAmount { index: 0 })
+        );
+        assert_eq!(
+            amounts(1, &[0.5, 0.5]),
+            Err(LadderError::ZeroAmount { index: 0 })
+        );
+        // Nothing left for the last chunk
+        assert_eq!(
+            amounts(1000, &[1.0, 1e-10]),
+            Err(LadderError::ZeroAmount { index: 1 })
+        );
+    }
+
+    #[test]
+    fn summarizes_quotes() {
+        assert_eq!(summarize_ladder(&[]), LadderSummary::default());
+        let quote_responses = [
+            QuoteResponse {
+                in_amount: 100,
+                out_amount: 200,
+                price_impact_pct: Decimal::new(1, 2),
+                ..QuoteResponse::default()
+            },
+            QuoteResponse {
+                in_amount: u64::MAX,
+                out_amount: u64::MAX,
+                price_impact_pct: Decimal::new(5, 2),
+                ..QuoteResponse::default()
+            },
+        ];
+        let summary = summarize_ladder(&quote_responses);
+        assert_eq!(summary.in_amount, u128::from(u64::MAX) + 100);
+        assert_eq!(summary.out_amount, u128::from(u64::MAX) + 200);
+        assert!(summary.blended_price.unwrap() > Decimal::ONE);
+        assert_eq!(summary.worst_price_impact_pct, Decimal::new(5, 2));
+    }
+}
@@ -1,125 +1,2385 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, PoisonError,
+    },
+    time::Instant,
+};
 
-use quote::{InternalQuoteRequest, QuoteRequest, QuoteResponse};
+use builder::{AuthScheme, JupiterSwapApiClientBuilder};
+use bytes::{Bytes, BytesMut};
+use capture::{HttpExchange, LastExchange};
+use conditional::{ConditionalResponse, EtagCache};
+use futures::{stream, StreamExt};
+use ladder::LadderError;
+use metrics::{Endpoint, MetricsHook, Outcome};
+use quote::{
+    InternalQuoteRequest, MultiValueStyle, QuoteError, QuoteRequest, QuoteRequestError,
+    QuoteResponse, SwapModeMismatch,
+};
+use request_options::RequestOptions;
 use reqwest::{
-    header::{HeaderMap, HeaderName, HeaderValue, InvalidHeaderValue},
-    Client, Response,
+    header::{HeaderMap, HeaderName, HeaderValue, InvalidHeaderValue, CONTENT_TYPE},
+    Client, Method, Request, RequestBuilder, Response, StatusCode, Url,
 };
-use serde::de::DeserializeOwned;
+use retry::RetryPolicy;
+use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
 use swap::{SwapInstructionsResponse, SwapInstructionsResponseInternal, SwapRequest, SwapResponse};
 use thiserror::Error;
+use tokens::TokenCache;
+use ultra::UltraExecuteErrorCode;
 
+pub mod amount;
+pub mod api;
+pub mod ata;
+pub mod builder;
+pub(crate) mod cache;
+pub mod capture;
+pub mod compute_budget;
+pub mod conditional;
+pub mod health;
+pub mod jito;
+pub mod ladder;
+pub mod metrics;
+pub mod mints;
+pub mod offline;
+pub mod pagination;
+#[cfg(feature = "rpc")]
+pub mod planner;
+pub mod price;
+#[cfg(feature = "rpc")]
+pub mod priority_fee;
 pub mod quote;
+#[cfg(feature = "quote-cache")]
+pub mod quote_cache;
+pub(crate) mod random;
+#[cfg(feature = "record-replay")]
+pub mod recording;
+pub mod recurring;
+pub mod request_options;
+pub mod retry;
 pub mod route_plan_with_metadata;
 pub mod serde_helpers;
+pub mod simulation;
 pub mod swap;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+pub mod tokens;
 pub mod transaction_config;
+pub mod trigger;
+pub mod ultra;
+#[cfg(feature = "solana-sdk")]
+pub mod verify;
+
+/// Full Solana types for building transactions
+#[cfg(feature = "solana-sdk")]
+pub use solana_sdk;
+
+/// Host of the Jupiter APIs for requests without an api key, rate limited
+pub const LITE_API_HOST: &str = "https://lite-api.jup.ag";
+/// Host of the Jupiter APIs for requests carrying an api key
+pub const KEYED_API_HOST: &str = "https://api.jup.ag";
+
+/// Default limit of the response body size, see [`JupiterSwapApiClientBuilder::max_response_size`]
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 8 * 1024 * 1024;
 
 #[derive(Clone)]
 pub struct JupiterSwapApiClient {
     pub base_path: String,
     pub api_key: Option<String>,
+    pub ultra_base_path: String,
+    pub price_base_path: String,
+    pub trigger_base_path: String,
+    pub recurring_base_path: String,
+    pub tokens_base_path: String,
+    pub(crate) http_client: Client,
+    /// Tried in order after `base_path`
+    pub(crate) failover_base_paths: Vec<String>,
+    /// Index of the endpoint that last answered, `0` being `base_path`
+    pub(crate) active_endpoint: Arc<AtomicUsize>,
+    pub(crate) metrics: MetricsHook,
+    pub(crate) auth_scheme: AuthScheme,
+    pub(crate) multi_value_style: MultiValueStyle,
+    /// Set when debug capture is enabled
+    pub(crate) last_exchange: Option<LastExchange>,
+    pub(crate) token_cache: Option<Arc<TokenCache>>,
+    pub(crate) etag_cache: Arc<EtagCache>,
+    #[cfg(feature = "quote-cache")]
+    pub(crate) quote_cache: Option<Arc<quote_cache::QuoteCache>>,
+    pub(crate) max_price_impact_pct: Option<Decimal>,
+    pub(crate) max_response_size: usize,
+    #[cfg(feature = "record-replay")]
+    pub(crate) recorder: Option<Arc<dyn recording::Recorder>>,
+    pub(crate) warn_on_field_drift: bool,
+    pub(crate) retry_policy: RetryPolicy,
 }
 
+/// What went wrong, see [`ClientError::kind`]
 #[derive(Debug, Error)]
-pub enum ClientError {
+#[non_exhaustive]
+pub enum ErrorKind {
     #[error("Request failed with status {status}: {body}")]
     RequestFailed {
         status: reqwest::StatusCode,
         body: String,
     },
+    #[error("Request redirected with status {status} to {}, redirects are not followed", .location.as_deref().unwrap_or("an unknown location"))]
+    Redirected {
+        status: reqwest::StatusCode,
+        location: Option<String>,
+    },
     #[error("Failed to deserialize response: {0}")]
     DeserializationError(#[from] reqwest::Error),
     #[error("Invalid header: {0}")]
     InvalidHeader(#[from] InvalidHeaderValue),
+    #[error("Misconfigured client: {0}")]
+    Misconfiguration(String),
+    #[error("Failed to deserialize response body: {0}")]
+    BodyDeserializationError(#[from] serde_json::Error),
+    /// `path` is the JSON path of the value which failed, e.g. `routePlan[0].swapInfo.feeAmount`,
+    /// `snippet` the body around it
+    #[error("Failed to deserialize response body at {path}: {message}, near `{snippet}`")]
+    DeserializationDetailed {
+        path: String,
+        message: String,
+        snippet: String,
+    },
+    /// A successful response without a body, e.g. from a CDN in front of the API
+    #[error("Response of {} has an empty body", .endpoint.map_or("the request".to_string(), |endpoint| endpoint.to_string()))]
+    EmptyResponse { endpoint: Option<Endpoint> },
+    /// A successful response which is not JSON, e.g. the HTML challenge page of a CDN,
+    /// `body_snippet` being the start of the body
+    #[error("Response has content type {content_type} instead of JSON: `{body_snippet}`")]
+    UnexpectedContentType {
+        content_type: String,
+        body_snippet: String,
+    },
+    #[error("Response contains unknown fields: {}", .0.join(", "))]
+    UnknownFields(Vec<String>),
+    #[error("Quote failed with {error}: {message}")]
+    QuoteFailed { error: QuoteError, message: String },
+    #[error("Invalid quote request: {0}")]
+    InvalidQuoteRequest(#[from] QuoteRequestError),
+    #[error("Invalid quote ladder: {0}")]
+    InvalidLadder(#[from] LadderError),
+    /// The quote was computed with another swap mode than requested, e.g. ExactIn for an ExactOut request
+    #[error(transparent)]
+    SwapModeMismatch(#[from] SwapModeMismatch),
+    #[cfg(feature = "rpc")]
+    #[error("RPC request failed: {0}")]
+    RpcError(Box<solana_client::client_error::ClientError>),
+    #[error("Failed to decode the swap transaction: {0}")]
+    InvalidTransaction(bincode::Error),
+    #[error("Price impact {actual} is above the limit of {limit}")]
+    PriceImpactTooHigh { actual: Decimal, limit: Decimal },
+    #[cfg(feature = "record-replay")]
+    #[error("No recorded response of {endpoint} matches the request")]
+    NotRecorded { endpoint: Endpoint },
+    #[error("Response of {endpoint} is larger than {limit} bytes")]
+    ResponseTooLarge { limit: usize, endpoint: Endpoint },
+    #[error("Ultra execute failed with {code:?}: {message}")]
+    UltraExecuteFailed {
+        code: UltraExecuteErrorCode,
+        message: String,
+        signature: Option<String>,
+    },
+    #[error("Trigger execute failed{}: {message}", .code.map_or(String::new(), |code| format!(" with code {code}")))]
+    TriggerExecuteFailed {
+        code: Option<i32>,
+        message: String,
+        signature: Option<String>,
+    },
+}
+
+/// Error of the client, with the endpoint and url of the request when it was sent, or about to be
+#[derive(Debug)]
+pub struct ClientError {
+    kind: ErrorKind,
+    endpoint: Option<Endpoint>,
+    url: Option<Box<Url>>,
+    status: Option<StatusCode>,
+    request_ids: Vec<String>,
+}
+
+impl ClientError {
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn into_kind(self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Endpoint of the failed request, `None` for errors unrelated to a request such as misconfiguration
+    pub fn endpoint(&self) -> Option<Endpoint> {
+        self.endpoint
+    }
+
+    /// Url of the failed request, `None` when the error happened before the url was known.
+    /// The api key is sent as a header so the url never contains it.
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_deref()
+    }
+
+    /// `x-request-id` and `cf-ray` of every response received for the request, failed over attempts included,
+    /// for support requests to Jupiter
+    pub fn request_ids(&self) -> &[String] {
+        &self.request_ids
+    }
+
+    /// HTTP status of the response the error comes from, `None` when no response was received
+    pub fn status(&self) -> Option<StatusCode> {
+        if self.status.is_some() {
+            return self.status;
+        }
+        match &self.kind {
+            ErrorKind::RequestFailed { status, .. } | ErrorKind::Redirected { status, .. } => {
+                Some(*status)
+            }
+            ErrorKind::DeserializationError(e) => e.status(),
+            _ => None,
+        }
+    }
+
+    /// The API rejected the request with 429 Too Many Requests
+    pub fn is_rate_limited(&self) -> bool {
+        self.status() == Some(StatusCode::TOO_MANY_REQUESTS)
+    }
+
+    /// Sending the same request again may succeed: timeouts, connection errors, 429 and 5xx responses.
+    /// Other responses, invalid requests and bodies which cannot be deserialized are not retryable.
+    pub fn is_retryable(&self) -> bool {
+        if let ErrorKind::DeserializationError(e) = &self.kind {
+            if e.is_timeout() || e.is_connect() {
+                return true;
+            }
+        }
+        self.status().is_some_and(is_retryable_status)
+    }
+
+    /// Set the endpoint unless already known
+    pub(crate) fn with_endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.endpoint.get_or_insert(endpoint);
+        self
+    }
+
+    fn with_request_ids(mut self, request_ids: Vec<String>) -> Self {
+        if self.request_ids.is_empty() {
+            self.request_ids = request_ids;
+        }
+        self
+    }
+
+    /// Set the endpoint, url and status of `response` unless already known
+    fn with_response(mut self, context: &ResponseContext) -> Self {
+        self.endpoint = self.endpoint.or(context.endpoint);
+        self.status.get_or_insert(context.status);
+        if self.request_ids.is_empty() {
+            self.request_ids.clone_from(&context.request_ids);
+        }
+        self.url
+            .get_or_insert_with(|| Box::new(context.url.clone()));
+        self
+    }
 }
 
-async fn check_is_success(response: Response) -> Result<Response, ClientError> {
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)?;
+        if !self.request_ids.is_empty() {
+            write!(f, " (request ids: {})", self.request_ids.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.kind.source()
+    }
+}
+
+impl From<ErrorKind> for ClientError {
+    fn from(kind: ErrorKind) -> Self {
+        let url = match &kind {
+            ErrorKind::DeserializationError(e) => e.url().cloned().map(Box::new),
+            _ => None,
+        };
+        Self {
+            kind,
+            endpoint: None,
+            url,
+            status: None,
+            request_ids: Vec::new(),
+        }
+    }
+}
+
+macro_rules! client_error_from {
+    ($($source:ty),*) => {
+        $(
+            impl From<$source> for ClientError {
+                fn from(e: $source) -> Self {
+                    ErrorKind::from(e).into()
+                }
+            }
+        )*
+    };
+}
+
+client_error_from!(
+    reqwest::Error,
+    InvalidHeaderValue,
+    serde_json::Error,
+    QuoteRequestError,
+    LadderError,
+    SwapModeMismatch
+);
+
+/// Attached to responses by the client
+#[derive(Clone)]
+struct RequestContext {
+    endpoint: Endpoint,
+    max_response_size: usize,
+    /// Of this response and the ones of previous attempts
+    request_ids: Vec<String>,
+}
+
+/// Headers correlating a response with the logs of Jupiter
+const REQUEST_ID_HEADERS: [&str; 2] = ["x-request-id", "cf-ray"];
+
+fn header_request_ids(headers: &HeaderMap) -> Vec<String> {
+    REQUEST_ID_HEADERS
+        .iter()
+        .filter_map(|name| headers.get(*name)?.to_str().ok())
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Request ids of the response and of the attempts before it, see [`ClientError::request_ids`]
+pub(crate) fn request_ids(response: &Response) -> Vec<String> {
+    response
+        .extensions()
+        .get::<RequestContext>()
+        .map(|context| context.request_ids.clone())
+        .unwrap_or_default()
+}
+
+/// Endpoint, url, status and request ids of a response, attached to the errors of reading it
+struct ResponseContext {
+    endpoint: Option<Endpoint>,
+    url: Url,
+    status: StatusCode,
+    request_ids: Vec<String>,
+}
+
+impl ResponseContext {
+    fn of(response: &Response) -> Self {
+        Self {
+            endpoint: response
+                .extensions()
+                .get::<RequestContext>()
+                .map(|context| context.endpoint),
+            url: response.url().clone(),
+            status: response.status(),
+            request_ids: request_ids(response),
+        }
+    }
+}
+
+/// Read the body of `response`, failing with [`ErrorKind::ResponseTooLarge`]
+/// as soon as it exceeds the limit of the client rather than buffering it whole
+pub(crate) async fn read_body(response: Response) -> Result<Bytes, ClientError> {
+    let context = ResponseContext::of(&response);
+    #[cfg(feature = "record-replay")]
+    let (response, pending_recording) = recording::PendingRecording::take(response);
+    let body = read_limited_body(response)
+        .await
+        .map_err(|e| e.with_response(&context))?;
+    #[cfg(feature = "record-replay")]
+    if let Some(pending_recording) = pending_recording {
+        pending_recording.record(&body);
+    }
+    Ok(body)
+}
+
+async fn read_limited_body(mut response: Response) -> Result<Bytes, ClientError> {
+    let Some(&RequestContext {
+        endpoint,
+        max_response_size: limit,
+        ..
+    }) = response.extensions().get::<RequestContext>()
+    else {
+        return Ok(response.bytes().await?);
+    };
+    let too_large = ErrorKind::ResponseTooLarge { limit, endpoint };
+    if response
+        .content_length()
+        .is_some_and(|content_length| content_length > limit as u64)
+    {
+        return Err(too_large.into());
+    }
+    let mut body = BytesMut::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(too_large.into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+/// `application/json`, `text/json` or a `+json` type, whatever their parameters such as the charset
+fn is_json_content_type(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence == "application/json" || essence == "text/json" || essence.ends_with("+json")
+}
+
+/// Same as [`read_body`] for a JSON response, failing with [`ErrorKind::EmptyResponse`]
+/// or [`ErrorKind::UnexpectedContentType`]. A response without content type is assumed to be JSON.
+async fn read_json_body(response: Response) -> Result<Bytes, ClientError> {
+    let context = ResponseContext::of(&response);
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map(|content_type| String::from_utf8_lossy(content_type.as_bytes()).into_owned());
+    let body = read_body(response).await?;
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Err(ClientError::from(ErrorKind::EmptyResponse {
+            endpoint: context.endpoint,
+        })
+        .with_response(&context));
+    }
+    if let Some(content_type) =
+        content_type.filter(|content_type| !is_json_content_type(content_type))
+    {
+        let body_snippet = &body[..body.len().min(2 * SNIPPET_CONTEXT)];
+        return Err(ClientError::from(ErrorKind::UnexpectedContentType {
+            content_type,
+            body_snippet: String::from_utf8_lossy(body_snippet).into_owned(),
+        })
+        .with_response(&context));
+    }
+    Ok(body)
+}
+
+/// Body of an error response, empty if it cannot be read unless it is too large
+async fn read_error_body(response: Response) -> Result<String, ClientError> {
+    match read_body(response).await {
+        Ok(body) => Ok(String::from_utf8_lossy(&body).into_owned()),
+        Err(e) if matches!(e.kind(), ErrorKind::ResponseTooLarge { .. }) => Err(e),
+        Err(_) => Ok(String::new()),
+    }
+}
+
+pub(crate) async fn check_is_success(response: Response) -> Result<Response, ClientError> {
+    let context = ResponseContext::of(&response);
+    if response.status().is_redirection() {
+        return Err(redirected(&response).with_response(&context));
+    }
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = read_error_body(response).await?;
+        return Err(
+            ClientError::from(ErrorKind::RequestFailed { status, body }).with_response(&context)
+        );
+    }
+    Ok(response)
+}
+
+/// 429 and 5xx, the responses worth sending the same request again for
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn redirected(response: &Response) -> ClientError {
+    ErrorKind::Redirected {
+        status: response.status(),
+        location: response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .map(ToString::to_string),
+    }
+    .into()
+}
+
+/// Same as [`check_is_success`] but maps the well known quote errors to [`ErrorKind::QuoteFailed`]
+async fn check_quote_is_success(response: Response) -> Result<Response, ClientError> {
+    let context = ResponseContext::of(&response);
+    if response.status().is_redirection() {
+        return Err(redirected(&response).with_response(&context));
+    }
     if !response.status().is_success() {
         let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        return Err(ClientError::RequestFailed { status, body });
+        let body = read_error_body(response).await?;
+        let kind = match quote::parse_quote_error(&body) {
+            Some((error, message)) => ErrorKind::QuoteFailed { error, message },
+            None => ErrorKind::RequestFailed { status, body },
+        };
+        return Err(ClientError::from(kind).with_response(&context));
     }
     Ok(response)
 }
 
-async fn check_status_code_and_deserialize<T: DeserializeOwned>(
+/// Bytes of the body shown either side of a deserialization error
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Deserialize `body`, failing with [`ErrorKind::DeserializationDetailed`]
+pub(crate) fn deserialize_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, ClientError> {
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    let value = serde_path_to_error::deserialize(&mut deserializer)
+        .map_err(|e| deserialization_detailed(e, body))?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+fn deserialization_detailed(
+    error: serde_path_to_error::Error<serde_json::Error>,
+    body: &[u8],
+) -> ClientError {
+    let path = error.path().to_string();
+    let error = error.into_inner();
+    // Line and column are 1-based
+    let line_start: usize = body
+        .split(|&byte| byte == b'\n')
+        .take(error.line().saturating_sub(1))
+        .map(|line| line.len() + 1)
+        .sum();
+    let position = (line_start + error.column().saturating_sub(1)).min(body.len());
+    let snippet = &body
+        [position.saturating_sub(SNIPPET_CONTEXT)..(position + SNIPPET_CONTEXT).min(body.len())];
+    ErrorKind::DeserializationDetailed {
+        path,
+        message: error.to_string(),
+        snippet: String::from_utf8_lossy(snippet).into_owned(),
+    }
+    .into()
+}
+
+pub(crate) async fn check_status_code_and_deserialize<T: DeserializeOwned>(
     response: Response,
 ) -> Result<T, ClientError> {
+    let context = ResponseContext::of(&response);
     let response = check_is_success(response).await?;
-    response
-        .json::<T>()
-        .await
-        .map_err(ClientError::DeserializationError)
+    let body = read_json_body(response).await?;
+    deserialize_body(&body).map_err(|e| e.with_response(&context))
+}
+
+/// Optional fields of a response which the current API version always sends
+pub(crate) trait ExpectedFields {
+    /// Top level, as named in JSON
+    const EXPECTED_FIELDS: &'static [&'static str];
+}
+
+/// Deserialize the response through JSON, also returning a warning for every expected field
+/// the response is missing and every field the target type does not model
+async fn check_status_code_and_deserialize_with_warnings<T: DeserializeOwned + ExpectedFields>(
+    response: Response,
+) -> Result<(T, Vec<String>), ClientError> {
+    let context = ResponseContext::of(&response);
+    let response = check_is_success(response).await?;
+    let body = read_json_body(response).await?;
+    let deserialize = || -> Result<(T, Vec<String>), ClientError> {
+        let json: Value = deserialize_body(&body)?;
+        let mut warnings: Vec<_> = T::EXPECTED_FIELDS
+            .iter()
+            .filter(|field| json.get(**field).unwrap_or(&Value::Null).is_null())
+            .map(|field| format!("missing field `{field}`"))
+            .collect();
+        let mut unknown_fields = Vec::new();
+        let mut on_unknown_field =
+            |path: serde_ignored::Path| unknown_fields.push(format!("unknown field `{path}`"));
+        let value = match T::deserialize(serde_ignored::Deserializer::new(
+            &json,
+            &mut on_unknown_field,
+        )) {
+            Ok(value) => value,
+            // Deserialize the body itself to locate the error
+            Err(e) => {
+                return Err(deserialize_body::<T>(&body)
+                    .err()
+                    .unwrap_or_else(|| e.into()))
+            }
+        };
+        warnings.extend(unknown_fields);
+        Ok((value, warnings))
+    };
+    deserialize().map_err(|e| e.with_response(&context))
+}
+
+/// Deserialize the response body once into JSON then into the target type, returning both
+async fn check_status_code_and_deserialize_with_json<T: DeserializeOwned>(
+    response: Response,
+) -> Result<(T, Value), ClientError> {
+    let context = ResponseContext::of(&response);
+    let response = check_is_success(response).await?;
+    let body = read_json_body(response).await?;
+    let deserialize = || -> Result<(T, Value), ClientError> {
+        let json: Value = deserialize_body(&body)?;
+        match T::deserialize(&json) {
+            Ok(value) => Ok((value, json)),
+            // Deserialize the body itself to locate the error
+            Err(e) => Err(deserialize_body::<T>(&body)
+                .err()
+                .unwrap_or_else(|| e.into())),
+        }
+    };
+    deserialize().map_err(|e| e.with_response(&context))
+}
+
+/// Deserialize the response body, also returning the body as received
+async fn check_status_code_and_deserialize_with_bytes<T: DeserializeOwned>(
+    response: Response,
+) -> Result<(T, Bytes), ClientError> {
+    let context = ResponseContext::of(&response);
+    let response = check_is_success(response).await?;
+    let body = read_json_body(response).await?;
+    let value = deserialize_body(&body).map_err(|e| e.with_response(&context))?;
+    Ok((value, body))
+}
+
+/// Deserialize the response, failing if it contains fields the target type does not model
+async fn check_status_code_and_deserialize_strict<T: DeserializeOwned>(
+    response: Response,
+) -> Result<T, ClientError> {
+    let context = ResponseContext::of(&response);
+    let response = check_is_success(response).await?;
+    let body = read_json_body(response).await?;
+    let mut unknown_fields = Vec::new();
+    let mut deserializer = serde_json::Deserializer::from_slice(&body);
+    let mut on_unknown_field = |path: serde_ignored::Path| unknown_fields.push(path.to_string());
+    let value = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(
+        &mut deserializer,
+        &mut on_unknown_field,
+    ))
+    .map_err(|e| deserialization_detailed(e, &body).with_response(&context))?;
+    if !unknown_fields.is_empty() {
+        return Err(
+            ClientError::from(ErrorKind::UnknownFields(unknown_fields)).with_response(&context)
+        );
+    }
+    Ok(value)
+}
+
+const QUOTE_PATH: &str = "quote";
+const SWAP_PATH: &str = "swap";
+const SWAP_INSTRUCTIONS_PATH: &str = "swap-instructions";
+const PROGRAM_ID_TO_LABEL_PATH: &str = "program-id-to-label";
+
+fn is_absolute_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+fn join_url(base_path: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base_path.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+fn with_query_and_body<Q: Serialize + ?Sized, B: Serialize + ?Sized>(
+    mut request: RequestBuilder,
+    query: Option<&Q>,
+    body: Option<&B>,
+) -> RequestBuilder {
+    if let Some(query) = query {
+        request = request.query(query);
+    }
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+    request
+}
+
+/// Check that `quote_response` was computed in the requested swap mode and fill in what the client knows about it,
+/// shared by all the quote methods
+fn complete_quote_response(
+    quote_request: &QuoteRequest,
+    quote_response: &mut QuoteResponse,
+    received_at: Instant,
+    request_ids: Vec<String>,
+) -> Result<(), ClientError> {
+    quote_response
+        .assert_mode(quote_request.swap_mode.unwrap_or_default())
+        .map_err(|e| {
+            ClientError::from(e)
+                .with_endpoint(Endpoint::Quote)
+                .with_request_ids(request_ids.clone())
+        })?;
+    quote_response.received_at = Some(received_at);
+    quote_response.request_ids = request_ids;
+    Ok(())
+}
+
+/// Query of a quote request, shared by sending and building it.
+/// The extra args are sent separately from the modeled parameters.
+fn quote_query(
+    quote_request: &QuoteRequest,
+    multi_value_style: MultiValueStyle,
+) -> Result<impl Fn(RequestBuilder) -> RequestBuilder, ClientError> {
+    quote_request.validate()?;
+    let extra_args = quote_request.quote_args.clone();
+    let mut internal_quote_request = InternalQuoteRequest::from(quote_request.clone());
+    let repeated_dexes = match multi_value_style {
+        MultiValueStyle::Comma => Vec::new(),
+        MultiValueStyle::Repeated => internal_quote_request.take_repeated_dexes(),
+    };
+    Ok(move |request: RequestBuilder| {
+        request
+            .query(&internal_quote_request)
+            .query(&repeated_dexes)
+            .query(&extra_args)
+    })
 }
 
 impl JupiterSwapApiClient {
     pub fn new(base_path: String, api_key: Option<String>) -> Self {
-        Self { base_path, api_key }
+        let host = if api_key.is_some() {
+            KEYED_API_HOST
+        } else {
+            LITE_API_HOST
+        };
+        Self {
+            base_path,
+            api_key,
+            ultra_base_path: format!("{host}/ultra/v1"),
+            price_base_path: format!("{host}/price/v3"),
+            trigger_base_path: format!("{host}/trigger/v1"),
+            recurring_base_path: format!("{host}/recurring/v1"),
+            tokens_base_path: format!("{host}/tokens/v2"),
+            http_client: Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("Client::new()"),
+            failover_base_paths: Vec::new(),
+            active_endpoint: Arc::default(),
+            metrics: MetricsHook::default(),
+            auth_scheme: AuthScheme::default(),
+            multi_value_style: MultiValueStyle::default(),
+            last_exchange: None,
+            token_cache: None,
+            etag_cache: Arc::default(),
+            #[cfg(feature = "quote-cache")]
+            quote_cache: None,
+            max_price_impact_pct: None,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            #[cfg(feature = "record-replay")]
+            recorder: None,
+            warn_on_field_drift: false,
+            retry_policy: RetryPolicy::none(),
+        }
     }
 
-    pub async fn quote(&self, quote_request: &QuoteRequest) -> Result<QuoteResponse, ClientError> {
-        let url = format!("{}/quote", self.base_path);
-        let extra_args = quote_request.quote_args.clone();
-        let internal_quote_request = InternalQuoteRequest::from(quote_request.clone());
+    pub fn builder(base_path: String) -> JupiterSwapApiClientBuilder {
+        JupiterSwapApiClientBuilder::new(base_path)
+    }
+
+    /// The api key of `options` takes precedence over the client api key
+    fn headers_with_options(&self, options: &RequestOptions) -> Result<HeaderMap, ClientError> {
         let mut headers = HeaderMap::new();
-        if let Some(api_key) = &self.api_key {
+        if let Some(api_key) = options
+            .api_key
+            .as_ref()
+            .or(self.api_key.as_ref())
+            .filter(|_| !options.without_api_key)
+        {
             headers.insert(
-                HeaderName::from_static("x-api-key"),
-                HeaderValue::from_str(api_key).map_err(ClientError::InvalidHeader)?,
+                self.auth_scheme.header_name(),
+                self.auth_scheme.header_value(api_key)?,
             );
         }
-        let response = Client::new()
-            .get(url)
-            .query(&internal_quote_request)
-            .query(&extra_args)
-            .headers(headers)
-            .send()
+        if let Some(idempotency_key) = &options.idempotency_key {
+            headers.insert(
+                HeaderName::from_static("x-idempotency-key"),
+                HeaderValue::from_str(idempotency_key)?,
+            );
+        }
+        headers.extend(options.headers.clone());
+        Ok(headers)
+    }
+
+    /// Use `endpoints` as the Swap API base paths in order of preference,
+    /// requests fail over to the next one on connection errors or 5xx responses.
+    /// The last endpoint that answered is tried first by the following requests.
+    pub fn with_endpoints(mut self, endpoints: Vec<String>) -> Self {
+        let mut endpoints = endpoints.into_iter();
+        if let Some(base_path) = endpoints.next() {
+            self.base_path = base_path;
+            self.failover_base_paths = endpoints.collect();
+            self.active_endpoint = Arc::default();
+        }
+        self
+    }
+
+    /// Last request and response sent by this client or its clones, api key redacted.
+    /// `None` unless debug capture was enabled on the builder.
+    pub fn last_exchange(&self) -> Option<HttpExchange> {
+        self.last_exchange
+            .as_ref()?
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Base path requests are currently sent to
+    pub fn active_base_path(&self) -> &str {
+        let index = self.active_endpoint.load(Ordering::Relaxed);
+        match index.checked_sub(1) {
+            Some(index) => self
+                .failover_base_paths
+                .get(index)
+                .unwrap_or(&self.base_path),
+            None => &self.base_path,
+        }
+    }
+
+    /// Send a request, `path` is relative to the base path unless it is an absolute url.
+    /// Requests to the base path fail over to the next endpoint on connection errors or 5xx responses,
+    /// requests failing on every endpoint are then retried according to the retry policy.
+    pub(crate) async fn send(
+        &self,
+        endpoint: Endpoint,
+        method: Method,
+        path: &str,
+        options: &RequestOptions,
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<Response, ClientError> {
+        let headers = self
+            .headers_with_options(options)
+            .map_err(|e| e.with_endpoint(endpoint))?;
+        let prepare =
+            |url: &str| self.prepare_request(method.clone(), url, headers.clone(), options, &build);
+        let mut attempt = 0;
+        let mut request_ids = Vec::new();
+        let mut retry = 0;
+        loop {
+            let result = self
+                .send_to_base_paths(endpoint, path, &prepare, &mut attempt, &mut request_ids)
+                .await;
+            let is_retryable = match &result {
+                Ok(response) => is_retryable_status(response.status()),
+                Err(e) => e.is_retryable(),
+            };
+            if !is_retryable || retry >= self.retry_policy.max_retries {
+                return match result {
+                    Ok(mut response) => {
+                        if let Some(context) = response.extensions_mut().get_mut::<RequestContext>()
+                        {
+                            request_ids.append(&mut context.request_ids);
+                            context.request_ids = request_ids;
+                        }
+                        Ok(response)
+                    }
+                    Err(e) => Err(e.with_endpoint(endpoint).with_request_ids(request_ids)),
+                };
+            }
+            if let Ok(response) = &result {
+                request_ids.extend(self::request_ids(response));
+            }
+            tokio::time::sleep(self.retry_policy.delay(retry)).await;
+            retry += 1;
+        }
+    }
+
+    /// Send the request prepared for a url to the active base path, failing over to the next ones.
+    /// `attempt` counts the requests sent, `request_ids` collects the ones of failed over responses.
+    async fn send_to_base_paths(
+        &self,
+        endpoint: Endpoint,
+        path: &str,
+        prepare: &impl Fn(&str) -> RequestBuilder,
+        attempt: &mut usize,
+        request_ids: &mut Vec<String>,
+    ) -> Result<Response, ClientError> {
+        if is_absolute_url(path) {
+            *attempt += 1;
+            return Ok(self.send_attempt(endpoint, *attempt, prepare(path)).await?);
+        }
+
+        let base_paths = std::iter::once(&self.base_path)
+            .chain(&self.failover_base_paths)
+            .collect::<Vec<_>>();
+        let active_endpoint = self.active_endpoint.load(Ordering::Relaxed) % base_paths.len();
+        for failover in 0..base_paths.len() {
+            let index = (active_endpoint + failover) % base_paths.len();
+            let is_last = failover + 1 == base_paths.len();
+            let request = prepare(&join_url(base_paths[index], path));
+            *attempt += 1;
+            match self.send_attempt(endpoint, *attempt, request).await {
+                Ok(response) if is_last || !response.status().is_server_error() => {
+                    if !response.status().is_server_error() {
+                        self.active_endpoint.store(index, Ordering::Relaxed);
+                    }
+                    return Ok(response);
+                }
+                Ok(response) => request_ids.extend(self::request_ids(&response)),
+                Err(e) if is_last || !(e.is_connect() || e.is_timeout()) => return Err(e.into()),
+                Err(_) => {}
+            }
+        }
+        unreachable!("there is always at least one base path")
+    }
+
+    /// Build the request `send` would send first, to the active base path, without sending it
+    pub(crate) fn build_request(
+        &self,
+        method: Method,
+        path: &str,
+        options: &RequestOptions,
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<Request, ClientError> {
+        let headers = self.headers_with_options(options)?;
+        let url = if is_absolute_url(path) {
+            path.to_string()
+        } else {
+            join_url(self.active_base_path(), path)
+        };
+        Ok(self
+            .prepare_request(method, &url, headers, options, &build)
+            .build()?)
+    }
+
+    fn prepare_request(
+        &self,
+        method: Method,
+        url: &str,
+        headers: HeaderMap,
+        options: &RequestOptions,
+        build: &impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> RequestBuilder {
+        let mut request = build(self.http_client.request(method, url).headers(headers));
+        if !options.query.is_empty() {
+            request = request.query(&options.query);
+        }
+        if let Some(timeout) = options.timeout {
+            request = request.timeout(timeout);
+        }
+        request
+    }
+
+    async fn send_attempt(
+        &self,
+        endpoint: Endpoint,
+        attempt: usize,
+        request: RequestBuilder,
+    ) -> Result<Response, reqwest::Error> {
+        let start = Instant::now();
+        let response = match &self.last_exchange {
+            Some(last_exchange) => {
+                capture::send_captured(
+                    &self.http_client,
+                    request,
+                    last_exchange,
+                    &self.auth_scheme.header_name(),
+                )
+                .await
+            }
+            None => request.send().await,
+        };
+        let (status, outcome) = match &response {
+            Ok(response)
+                if response.status().is_success()
+                    || response.status() == StatusCode::NOT_MODIFIED =>
+            {
+                (Some(response.status()), Outcome::Success)
+            }
+            Ok(response) => (Some(response.status()), Outcome::HttpError),
+            Err(e) => (e.status(), Outcome::TransportError),
+        };
+        self.metrics.record(
+            endpoint,
+            status,
+            start.elapsed(),
+            outcome,
+            u32::try_from(attempt).unwrap_or(u32::MAX),
+        );
+        response.map(|mut response| {
+            let request_ids = header_request_ids(response.headers());
+            response.extensions_mut().insert(RequestContext {
+                endpoint,
+                max_response_size: self.max_response_size,
+                request_ids,
+            });
+            response
+        })
+    }
+
+    pub(crate) async fn send_request<Q: Serialize + ?Sized, B: Serialize + ?Sized>(
+        &self,
+        endpoint: Endpoint,
+        method: Method,
+        path: &str,
+        query: Option<&Q>,
+        body: Option<&B>,
+        options: &RequestOptions,
+    ) -> Result<Response, ClientError> {
+        self.send(endpoint, method, path, options, |request| {
+            with_query_and_body(request, query, body)
+        })
+        .await
+    }
+
+    /// Call an endpoint the client does not model yet, `path` is relative to the base path unless it is an absolute url.
+    /// The api key is sent and errors are mapped as for the other endpoints.
+    ///
+    /// ```no_run
+    /// # async fn run(client: jupiter_swap_api_client::JupiterSwapApiClient) -> Result<(), jupiter_swap_api_client::ClientError> {
+    /// let labels: serde_json::Value = client
+    ///     .request(reqwest::Method::GET, "/program-id-to-label", None::<&()>, None::<&()>)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn request<Q, B, R>(
+        &self,
+        method: Method,
+        path: &str,
+        query: Option<&Q>,
+        body: Option<&B>,
+    ) -> Result<R, ClientError>
+    where
+        Q: Serialize + ?Sized,
+        B: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        self.request_with_options(method, path, query, body, &RequestOptions::default())
+            .await
+    }
+
+    pub async fn request_with_options<Q, B, R>(
+        &self,
+        method: Method,
+        path: &str,
+        query: Option<&Q>,
+        body: Option<&B>,
+        options: &RequestOptions,
+    ) -> Result<R, ClientError>
+    where
+        Q: Serialize + ?Sized,
+        B: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        let response = self
+            .send_request(Endpoint::Other, method, path, query, body, options)
+            .await?;
+        check_status_code_and_deserialize(response).await
+    }
+
+    pub(crate) async fn get<Q: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        endpoint: Endpoint,
+        path: &str,
+        query: &Q,
+    ) -> Result<R, ClientError> {
+        let response = self
+            .send_request(
+                endpoint,
+                Method::GET,
+                path,
+                Some(query),
+                None::<&()>,
+                &RequestOptions::default(),
+            )
             .await?;
         check_status_code_and_deserialize(response).await
     }
 
+    pub(crate) async fn post<B: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        endpoint: Endpoint,
+        path: &str,
+        body: &B,
+    ) -> Result<R, ClientError> {
+        let response = self
+            .send_request(
+                endpoint,
+                Method::POST,
+                path,
+                None::<&()>,
+                Some(body),
+                &RequestOptions::default(),
+            )
+            .await?;
+        check_status_code_and_deserialize(response).await
+    }
+
+    pub async fn quote(&self, quote_request: &QuoteRequest) -> Result<QuoteResponse, ClientError> {
+        self.quote_with_options(quote_request, &RequestOptions::default())
+            .await
+    }
+
+    pub async fn quote_with_options(
+        &self,
+        quote_request: &QuoteRequest,
+        options: &RequestOptions,
+    ) -> Result<QuoteResponse, ClientError> {
+        #[cfg(feature = "quote-cache")]
+        let cache_key = match &self.quote_cache {
+            Some(cache) if !options.bypass_cache => {
+                let cache_key = quote_cache::cache_key(quote_request, options);
+                if let Some(quote_response) = cache_key.as_ref().and_then(|key| cache.get(key)) {
+                    return Ok(quote_response);
+                }
+                cache_key
+            }
+            _ => None,
+        };
+        let (response, received_at) = self.send_quote(quote_request, options).await?;
+        let request_ids = request_ids(&response);
+        let (mut quote_response, warnings): (QuoteResponse, _) =
+            self.deserialize_response(response).await?;
+        complete_quote_response(quote_request, &mut quote_response, received_at, request_ids)?;
+        quote_response.warnings = warnings;
+        #[cfg(feature = "quote-cache")]
+        if let (Some(cache), Some(cache_key)) = (&self.quote_cache, cache_key) {
+            cache.insert(cache_key, quote_response.clone());
+        }
+        Ok(quote_response)
+    }
+
+    /// Quote every request with at most `concurrency` requests in flight.
+    /// Results are in the order of `quote_requests`, a failed quote does not fail the others.
+    pub async fn quote_many(
+        &self,
+        quote_requests: &[QuoteRequest],
+        concurrency: usize,
+    ) -> Vec<Result<QuoteResponse, ClientError>> {
+        self.quote_many_with_progress(quote_requests, concurrency, |_, _| {})
+            .await
+    }
+
+    /// Same as [`Self::quote_many`], calling `on_progress` with the number of results done and the total
+    /// as they are collected. Requests are only sent as slots free up, so at most `concurrency`
+    /// requests and connections are used at once whatever the size of the batch.
+    pub async fn quote_many_with_progress(
+        &self,
+        quote_requests: &[QuoteRequest],
+        concurrency: usize,
+        on_progress: impl Fn(usize, usize),
+    ) -> Vec<Result<QuoteResponse, ClientError>> {
+        let total = quote_requests.len();
+        stream::iter(quote_requests)
+            .map(|quote_request| self.quote(quote_request))
+            .buffered(concurrency.max(1))
+            .enumerate()
+            .map(|(index, result)| {
+                on_progress(index + 1, total);
+                result
+            })
+            .collect()
+            .await
+    }
+
+    /// Same as [`Self::quote`] but also returns the raw JSON response,
+    /// to log unexpected responses or access fields that are not modeled yet
+    pub async fn quote_raw(
+        &self,
+        quote_request: &QuoteRequest,
+    ) -> Result<(QuoteResponse, Value), ClientError> {
+        let (response, received_at) = self
+            .send_quote(quote_request, &RequestOptions::default())
+            .await?;
+        let request_ids = request_ids(&response);
+        let (mut quote_response, value): (QuoteResponse, _) =
+            check_status_code_and_deserialize_with_json(response).await?;
+        complete_quote_response(quote_request, &mut quote_response, received_at, request_ids)?;
+        Ok((quote_response, value))
+    }
+
+    /// Same as [`Self::quote`] but also returns the unmodified response body, e.g. to archive the exact quote
+    pub async fn quote_raw_bytes(
+        &self,
+        quote_request: &QuoteRequest,
+    ) -> Result<(QuoteResponse, Bytes), ClientError> {
+        let (response, received_at) = self
+            .send_quote(quote_request, &RequestOptions::default())
+            .await?;
+        let request_ids = request_ids(&response);
+        let (mut quote_response, bytes): (QuoteResponse, _) =
+            check_status_code_and_deserialize_with_bytes(response).await?;
+        complete_quote_response(quote_request, &mut quote_response, received_at, request_ids)?;
+        Ok((quote_response, bytes))
+    }
+
+    /// Same as [`Self::quote`] but fails with [`ErrorKind::UnknownFields`] when the response contains fields
+    /// that are not modeled, useful to detect API changes early, e.g. in CI against the live API
+    pub async fn quote_strict(
+        &self,
+        quote_request: &QuoteRequest,
+    ) -> Result<QuoteResponse, ClientError> {
+        let (response, received_at) = self
+            .send_quote(quote_request, &RequestOptions::default())
+            .await?;
+        let request_ids = request_ids(&response);
+        let mut quote_response: QuoteResponse =
+            check_status_code_and_deserialize_strict(response).await?;
+        complete_quote_response(quote_request, &mut quote_response, received_at, request_ids)?;
+        Ok(quote_response)
+    }
+
+    /// The response along with when it was received
+    async fn send_quote(
+        &self,
+        quote_request: &QuoteRequest,
+        options: &RequestOptions,
+    ) -> Result<(Response, Instant), ClientError> {
+        let response = self
+            .send(
+                Endpoint::Quote,
+                Method::GET,
+                QUOTE_PATH,
+                options,
+                quote_query(quote_request, self.multi_value_style)
+                    .map_err(|e| e.with_endpoint(Endpoint::Quote))?,
+            )
+            .await?;
+        let received_at = Instant::now();
+        let mut response = check_quote_is_success(response).await?;
+        self.record_response(Endpoint::Quote, &mut response, quote_request);
+        Ok((response, received_at))
+    }
+
     pub async fn swap(
         &self,
         swap_request: &SwapRequest,
         extra_args: Option<HashMap<String, String>>,
     ) -> Result<SwapResponse, ClientError> {
-        let mut headers = HeaderMap::new();
-        if let Some(api_key) = &self.api_key {
-            headers.insert(
-                HeaderName::from_static("x-api-key"),
-                HeaderValue::from_str(api_key).map_err(ClientError::InvalidHeader)?,
-            );
+        self.swap_with_options(swap_request, extra_args, &RequestOptions::default())
+            .await
+    }
+
+    pub async fn swap_with_options(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+        options: &RequestOptions,
+    ) -> Result<SwapResponse, ClientError> {
+        let response = self.send_swap(swap_request, extra_args, options).await?;
+        let request_ids = request_ids(&response);
+        let (mut swap_response, warnings): (SwapResponse, _) =
+            self.deserialize_response(response).await?;
+        swap_response.request_ids = request_ids;
+        swap_response.warnings = warnings;
+        Ok(swap_response)
+    }
+
+    /// Same as [`Self::swap`] but also returns the raw JSON response
+    pub async fn swap_raw(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+    ) -> Result<(SwapResponse, Value), ClientError> {
+        let response = self
+            .send_swap(swap_request, extra_args, &RequestOptions::default())
+            .await?;
+        let request_ids = request_ids(&response);
+        let (mut swap_response, value): (SwapResponse, _) =
+            check_status_code_and_deserialize_with_json(response).await?;
+        swap_response.request_ids = request_ids;
+        Ok((swap_response, value))
+    }
+
+    /// Same as [`Self::swap`] but also returns the unmodified response body
+    pub async fn swap_raw_bytes(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+    ) -> Result<(SwapResponse, Bytes), ClientError> {
+        let response = self
+            .send_swap(swap_request, extra_args, &RequestOptions::default())
+            .await?;
+        let request_ids = request_ids(&response);
+        let (mut swap_response, bytes): (SwapResponse, _) =
+            check_status_code_and_deserialize_with_bytes(response).await?;
+        swap_response.request_ids = request_ids;
+        Ok((swap_response, bytes))
+    }
+
+    /// Along with the field drift warnings of the response when enabled
+    async fn deserialize_response<T: DeserializeOwned + ExpectedFields>(
+        &self,
+        response: Response,
+    ) -> Result<(T, Vec<String>), ClientError> {
+        if self.warn_on_field_drift {
+            check_status_code_and_deserialize_with_warnings(response).await
+        } else {
+            Ok((
+                check_status_code_and_deserialize(response).await?,
+                Vec::new(),
+            ))
         }
-        let response = Client::new()
-            .post(format!("{}/swap", self.base_path))
-            .query(&extra_args)
-            .json(swap_request)
-            .headers(headers)
-            .send()
+    }
+
+    fn check_price_impact(
+        &self,
+        endpoint: Endpoint,
+        quote_response: &QuoteResponse,
+    ) -> Result<(), ClientError> {
+        match self.max_price_impact_pct {
+            Some(limit) if quote_response.price_impact_pct > limit => {
+                Err(ClientError::from(ErrorKind::PriceImpactTooHigh {
+                    actual: quote_response.price_impact_pct,
+                    limit,
+                })
+                .with_endpoint(endpoint))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn send_swap(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+        options: &RequestOptions,
+    ) -> Result<Response, ClientError> {
+        self.check_price_impact(Endpoint::Swap, &swap_request.quote_response)?;
+        let mut response = self
+            .send_request(
+                Endpoint::Swap,
+                Method::POST,
+                SWAP_PATH,
+                Some(&extra_args),
+                Some(swap_request),
+                options,
+            )
             .await?;
-        check_status_code_and_deserialize(response).await
+        self.record_response(Endpoint::Swap, &mut response, swap_request);
+        Ok(response)
     }
 
     pub async fn swap_instructions(
         &self,
         swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
     ) -> Result<SwapInstructionsResponse, ClientError> {
-        let mut headers = HeaderMap::new();
-        if let Some(api_key) = &self.api_key {
-            headers.insert(
+        self.swap_instructions_with_options(swap_request, extra_args, &RequestOptions::default())
+            .await
+    }
+
+    pub async fn swap_instructions_with_options(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+        options: &RequestOptions,
+    ) -> Result<SwapInstructionsResponse, ClientError> {
+        let response = self
+            .send_swap_instructions(swap_request, extra_args, options)
+            .await?;
+        let request_ids = request_ids(&response);
+        let (internal, warnings): (SwapInstructionsResponseInternal, _) =
+            self.deserialize_response(response).await?;
+        let mut swap_instructions_response: SwapInstructionsResponse = internal.into();
+        swap_instructions_response.request_ids = request_ids;
+        swap_instructions_response.warnings = warnings;
+        Ok(swap_instructions_response)
+    }
+
+    /// Same as [`Self::swap_instructions`] but also returns the raw JSON response
+    pub async fn swap_instructions_raw(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+    ) -> Result<(SwapInstructionsResponse, Value), ClientError> {
+        let response = self
+            .send_swap_instructions(swap_request, extra_args, &RequestOptions::default())
+            .await?;
+        let request_ids = request_ids(&response);
+        let (value, json) = check_status_code_and_deserialize_with_json::<
+            SwapInstructionsResponseInternal,
+        >(response)
+        .await?;
+        let mut swap_instructions_response: SwapInstructionsResponse = value.into();
+        swap_instructions_response.request_ids = request_ids;
+        Ok((swap_instructions_response, json))
+    }
+
+    /// Same as [`Self::swap_instructions`] but also returns the unmodified response body
+    pub async fn swap_instructions_raw_bytes(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+    ) -> Result<(SwapInstructionsResponse, Bytes), ClientError> {
+        let response = self
+            .send_swap_instructions(swap_request, extra_args, &RequestOptions::default())
+            .await?;
+        let request_ids = request_ids(&response);
+        let (value, body) = check_status_code_and_deserialize_with_bytes::<
+            SwapInstructionsResponseInternal,
+        >(response)
+        .await?;
+        let mut swap_instructions_response: SwapInstructionsResponse = value.into();
+        swap_instructions_response.request_ids = request_ids;
+        Ok((swap_instructions_response, body))
+    }
+
+    #[cfg(not(feature = "record-replay"))]
+    fn record_response<R: serde::Serialize + ?Sized>(&self, _: Endpoint, _: &mut Response, _: &R) {}
+
+    async fn send_swap_instructions(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+        options: &RequestOptions,
+    ) -> Result<Response, ClientError> {
+        self.check_price_impact(Endpoint::SwapInstructions, &swap_request.quote_response)?;
+        let mut response = self
+            .send_request(
+                Endpoint::SwapInstructions,
+                Method::POST,
+                SWAP_INSTRUCTIONS_PATH,
+                Some(&extra_args),
+                Some(swap_request),
+                options,
+            )
+            .await?;
+        self.record_response(Endpoint::SwapInstructions, &mut response, swap_request);
+        Ok(response)
+    }
+
+    /// GET /program-id-to-label, the label of the AMM of every program id the router uses,
+    /// revalidated with the `ETag` of the last response
+    pub async fn program_id_to_label(
+        &self,
+    ) -> Result<ConditionalResponse<HashMap<String, String>>, ClientError> {
+        self.get_conditional(Endpoint::ProgramIdToLabel, PROGRAM_ID_TO_LABEL_PATH, &())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_pubkey::Pubkey;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::quote::{QuoteExtraArgs, SwapMode};
+
+    // Shared across tasks and threads by callers
+    static_assertions::assert_impl_all!(JupiterSwapApiClient: Clone, Send, Sync);
+    static_assertions::assert_impl_all!(ClientError: std::fmt::Debug, Send, Sync);
+
+    // The lightweight types of the public signatures are the ones re-exported by solana-sdk
+    #[cfg(feature = "solana-sdk")]
+    static_assertions::assert_type_eq_all!(Pubkey, solana_sdk::pubkey::Pubkey);
+    #[cfg(feature = "solana-sdk")]
+    static_assertions::assert_type_eq_all!(solana_hash::Hash, solana_sdk::hash::Hash);
+    #[cfg(feature = "solana-sdk")]
+    static_assertions::assert_type_eq_all!(
+        solana_instruction::Instruction,
+        solana_sdk::instruction::Instruction
+    );
+
+    async fn mock_quote(server: &MockServer, quote_response: &QuoteResponse) {
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(quote_response))
+            .mount(server)
+            .await;
+    }
+
+    fn quote_request(swap_mode: Option<SwapMode>) -> QuoteRequest {
+        QuoteRequest {
+            amount: 1_000,
+            swap_mode,
+            ..QuoteRequest::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn quote_rejects_other_swap_mode() {
+        let server = MockServer::start().await;
+        mock_quote(
+            &server,
+            &QuoteResponse {
+                swap_mode: SwapMode::ExactIn,
+                ..QuoteResponse::default()
+            },
+        )
+        .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        let error = client
+            .quote(&quote_request(Some(SwapMode::ExactOut)))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::SwapModeMismatch(SwapModeMismatch {
+                expected: SwapMode::ExactOut,
+                actual: SwapMode::ExactIn
+            })
+        ));
+        assert_eq!(error.endpoint(), Some(Endpoint::Quote));
+
+        for swap_mode in [None, Some(SwapMode::ExactIn)] {
+            client.quote(&quote_request(swap_mode)).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn quote_rejects_zero_amount_before_sending() {
+        let server = MockServer::start().await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+        let error = client
+            .quote(&QuoteRequest {
+                amount: 0,
+                ..quote_request(None)
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::InvalidQuoteRequest(QuoteRequestError::ZeroAmount)
+        ));
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn empty_success_body_is_an_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(" \n", "application/json"))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        let error = client.quote(&quote_request(None)).await.unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::EmptyResponse {
+                endpoint: Some(Endpoint::Quote)
+            }
+        ));
+        assert_eq!(error.status(), Some(StatusCode::OK));
+    }
+
+    #[tokio::test]
+    async fn html_success_body_is_an_error() {
+        let server = MockServer::start().await;
+        let page = format!("<html>{}</html>", "challenge ".repeat(100));
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(page.clone(), "text/html; charset=utf-8"),
+            )
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        let error = client.quote(&quote_request(None)).await.unwrap_err();
+        let ErrorKind::UnexpectedContentType {
+            content_type,
+            body_snippet,
+        } = error.kind()
+        else {
+            panic!("unexpected error {error}");
+        };
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        assert_eq!(body_snippet, &page[..2 * SNIPPET_CONTEXT]);
+    }
+
+    #[tokio::test]
+    async fn body_without_content_type_is_read_as_json() {
+        let server = MockServer::start().await;
+        let body = serde_json::to_vec(&QuoteResponse::default()).unwrap();
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        client.quote(&quote_request(None)).await.unwrap();
+    }
+
+    #[test]
+    fn json_content_types() {
+        for content_type in [
+            "application/json",
+            "application/json; charset=utf-8",
+            "Application/JSON",
+            "text/json",
+            "application/problem+json",
+        ] {
+            assert!(is_json_content_type(content_type), "{content_type}");
+        }
+        for content_type in ["text/html", "text/plain", "application/octet-stream", ""] {
+            assert!(!is_json_content_type(content_type), "{content_type}");
+        }
+    }
+
+    /// Quote response as a newer API version could send it: `timeTaken` is gone
+    /// and a top level and a nested field were added
+    fn drifted_quote_response() -> Value {
+        let mut quote_response = serde_json::to_value(QuoteResponse {
+            context_slot: Some(1),
+            route_plan: vec![Default::default()],
+            ..QuoteResponse::default()
+        })
+        .unwrap();
+        quote_response["feeStructure"] = serde_json::json!({ "bps": 5 });
+        quote_response["routePlan"][0]["swapInfo"]["feeVault"] = "vault".into();
+        quote_response
+    }
+
+    async fn mock_drifted_quote() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(drifted_quote_response()))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn field_drift_produces_warnings() {
+        let server = mock_drifted_quote().await;
+        let client = JupiterSwapApiClient::builder(server.uri())
+            .warn_on_field_drift(true)
+            .build()
+            .unwrap();
+
+        let quote_response = client.quote(&quote_request(None)).await.unwrap();
+        assert_eq!(quote_response.context_slot, Some(1));
+        assert_eq!(
+            quote_response.warnings,
+            [
+                "missing field `timeTaken`",
+                "unknown field `feeStructure`",
+                "unknown field `routePlan.0.swapInfo.feeVault`",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn field_drift_is_ignored_by_default() {
+        let server = mock_drifted_quote().await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        let quote_response = client.quote(&quote_request(None)).await.unwrap();
+        assert!(quote_response.warnings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn strict_quote_fails_on_unknown_fields() {
+        let server = mock_drifted_quote().await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        let error = client.quote_strict(&quote_request(None)).await.unwrap_err();
+        let ErrorKind::UnknownFields(unknown_fields) = error.kind() else {
+            panic!("unexpected error {error}");
+        };
+        assert_eq!(
+            unknown_fields,
+            &["feeStructure", "routePlan.0.swapInfo.feeVault"]
+        );
+    }
+
+    #[tokio::test]
+    async fn raw_quote_keeps_the_fields_which_are_not_modeled() {
+        let server = mock_drifted_quote().await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        let (quote_response, value) = client.quote_raw(&quote_request(None)).await.unwrap();
+        assert_eq!(value, drifted_quote_response());
+        assert_eq!(value["feeStructure"]["bps"], 5);
+        assert_eq!(quote_response.context_slot, Some(1));
+        assert!(quote_response.received_at.is_some());
+        assert_eq!(
+            client.quote(&quote_request(None)).await.unwrap().route_plan,
+            quote_response.route_plan
+        );
+    }
+
+    #[tokio::test]
+    async fn raw_quote_bytes_are_the_body_as_received() {
+        let server = MockServer::start().await;
+        // Formatting, key order and a number representation a reserialization would not keep
+        let mut body = serde_json::to_string_pretty(&drifted_quote_response()).unwrap();
+        body.insert_str(1, "\n  \"zFirst\": 1.10,");
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body.clone(), "application/json"))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        let (quote_response, bytes) = client.quote_raw_bytes(&quote_request(None)).await.unwrap();
+        assert_eq!(bytes, body.as_bytes());
+        assert_eq!(quote_response.context_slot, Some(1));
+    }
+
+    fn header<'a>(request: &'a wiremock::Request, name: &str) -> Option<&'a str> {
+        request
+            .headers
+            .get(name)
+            .map(|value| value.to_str().unwrap())
+    }
+
+    async fn mock_swap_endpoints(server: &MockServer) {
+        mock_quote(
+            server,
+            &QuoteResponse {
+                swap_mode: SwapMode::ExactIn,
+                ..QuoteResponse::default()
+            },
+        )
+        .await;
+        Mock::given(method("POST"))
+            .and(path("/swap"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(SwapResponse::default()))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/swap-instructions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "computeBudgetInstructions": [],
+                "setupInstructions": [],
+                "swapInstruction": {
+                    "programId": "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4",
+                    "accounts": [],
+                    "data": ""
+                },
+                "otherInstructions": [],
+                "addressLookupTableAddresses": [],
+                "prioritizationFeeLamports": 0,
+                "computeUnitLimit": 0
+            })))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn request_options_merge_over_the_client_headers() {
+        let server = MockServer::start().await;
+        mock_swap_endpoints(&server).await;
+        let client = JupiterSwapApiClient::new(server.uri(), Some("client-key".to_string()));
+        let options = RequestOptions::default()
+            .header(
+                HeaderName::from_static("x-client-id"),
+                HeaderValue::from_static("b"),
+            )
+            .query("experimental".to_string(), "1".to_string());
+        let swap_request = SwapRequest {
+            user_public_key: Pubkey::new_unique(),
+            ..SwapRequest::default()
+        };
+
+        client
+            .quote_with_options(&quote_request(None), &options)
+            .await
+            .unwrap();
+        client
+            .swap_with_options(&swap_request, None, &options)
+            .await
+            .unwrap();
+        client
+            .swap_instructions_with_options(&swap_request, None, &options)
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 3);
+        for request in &requests {
+            // The api key is kept next to the per call header
+            assert_eq!(header(request, "x-api-key"), Some("client-key"));
+            assert_eq!(header(request, "x-client-id"), Some("b"));
+            assert!(request
+                .url
+                .query_pairs()
+                .any(|(key, value)| key == "experimental" && value == "1"));
+        }
+    }
+
+    #[tokio::test]
+    async fn api_key_is_only_replaced_explicitly() {
+        let server = MockServer::start().await;
+        mock_swap_endpoints(&server).await;
+        let client = JupiterSwapApiClient::new(server.uri(), Some("client-key".to_string()));
+        let quote_request = quote_request(None);
+
+        for options in [
+            RequestOptions::default().api_key("call-key".to_string()),
+            RequestOptions::default().header(
                 HeaderName::from_static("x-api-key"),
-                HeaderValue::from_str(api_key).map_err(ClientError::InvalidHeader)?,
+                HeaderValue::from_static("header-key"),
+            ),
+            RequestOptions::default().without_api_key(),
+            RequestOptions::default().idempotency_key("swap-1".to_string()),
+        ] {
+            client
+                .quote_with_options(&quote_request, &options)
+                .await
+                .unwrap();
+        }
+
+        let requests = server.received_requests().await.unwrap();
+        let api_keys: Vec<_> = requests
+            .iter()
+            .map(|request| header(request, "x-api-key"))
+            .collect();
+        assert_eq!(
+            api_keys,
+            [
+                Some("call-key"),
+                Some("header-key"),
+                None,
+                Some("client-key")
+            ]
+        );
+        assert_eq!(header(&requests[3], "x-idempotency-key"), Some("swap-1"));
+    }
+
+    fn swap_request_with_impact(price_impact_pct: Decimal) -> SwapRequest {
+        SwapRequest {
+            user_public_key: Pubkey::new_unique(),
+            quote_response: QuoteResponse {
+                price_impact_pct,
+                ..QuoteResponse::default()
+            },
+            ..SwapRequest::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn swaps_above_the_max_price_impact_are_not_sent() {
+        let server = MockServer::start().await;
+        mock_swap_endpoints(&server).await;
+        let client = JupiterSwapApiClient::builder(server.uri())
+            .max_price_impact_pct(Decimal::new(1, 2))
+            .build()
+            .unwrap();
+        let swap_request = swap_request_with_impact(Decimal::new(2, 2));
+
+        let error = client.swap(&swap_request, None).await.unwrap_err();
+        assert!(
+            matches!(
+                error.kind(),
+                ErrorKind::PriceImpactTooHigh { actual, limit }
+                    if *actual == Decimal::new(2, 2) && *limit == Decimal::new(1, 2)
+            ),
+            "{error:?}"
+        );
+        assert_eq!(error.endpoint(), Some(Endpoint::Swap));
+        let error = client
+            .swap_instructions(&swap_request, None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error.kind(), ErrorKind::PriceImpactTooHigh { .. }),
+            "{error:?}"
+        );
+        assert_eq!(request_count(&server).await, 0);
+    }
+
+    #[tokio::test]
+    async fn swaps_up_to_the_max_price_impact_are_sent() {
+        let server = MockServer::start().await;
+        mock_swap_endpoints(&server).await;
+        let client = JupiterSwapApiClient::builder(server.uri())
+            .max_price_impact_pct(Decimal::new(1, 2))
+            .build()
+            .unwrap();
+        let swap_request = swap_request_with_impact(Decimal::new(1, 2));
+
+        client.swap(&swap_request, None).await.unwrap();
+        client.swap_instructions(&swap_request, None).await.unwrap();
+        assert_eq!(request_count(&server).await, 2);
+
+        // Without a limit any price impact is sent
+        JupiterSwapApiClient::new(server.uri(), None)
+            .swap(&swap_request_with_impact(Decimal::ONE), None)
+            .await
+            .unwrap();
+        assert_eq!(request_count(&server).await, 3);
+    }
+
+    #[tokio::test]
+    async fn auth_scheme_sets_the_api_key_header() {
+        let server = MockServer::start().await;
+        mock_swap_endpoints(&server).await;
+
+        for auth_scheme in [
+            AuthScheme::Bearer,
+            AuthScheme::Header {
+                name: HeaderName::from_static("x-gateway-key"),
+                prefix: Some("Key ".to_string()),
+            },
+        ] {
+            let client = JupiterSwapApiClient::builder(server.uri())
+                .api_key("client-key".to_string())
+                .auth_scheme(auth_scheme)
+                .build()
+                .unwrap();
+            client.quote(&quote_request(None)).await.unwrap();
+        }
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(
+            header(&requests[0], "authorization"),
+            Some("Bearer client-key")
+        );
+        assert_eq!(
+            header(&requests[1], "x-gateway-key"),
+            Some("Key client-key")
+        );
+        for request in &requests {
+            assert_eq!(header(request, "x-api-key"), None);
+        }
+        assert_eq!(header(&requests[1], "authorization"), None);
+    }
+
+    #[tokio::test]
+    async fn repeated_style_sends_one_pair_per_dex() {
+        let server = MockServer::start().await;
+        mock_swap_endpoints(&server).await;
+        let quote_request = QuoteRequest {
+            dexes: Some("Raydium,Orca".to_string()),
+            excluded_dexes: Some("Meteora".to_string()),
+            ..quote_request(None)
+        };
+
+        for multi_value_style in [MultiValueStyle::Comma, MultiValueStyle::Repeated] {
+            let client = JupiterSwapApiClient::builder(server.uri())
+                .multi_value_style(multi_value_style)
+                .build()
+                .unwrap();
+            client.quote(&quote_request).await.unwrap();
+        }
+
+        let requests = server.received_requests().await.unwrap();
+        let dexes: Vec<Vec<_>> = requests
+            .iter()
+            .map(|request| {
+                request
+                    .url
+                    .query_pairs()
+                    .filter(|(key, _)| key == "dexes" || key == "excludedDexes")
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            dexes,
+            [
+                vec!["dexes=Orca,Raydium", "excludedDexes=Meteora"],
+                vec!["dexes=Orca", "dexes=Raydium", "excludedDexes=Meteora"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn type_mismatch_is_reported_with_its_path() {
+        let server = MockServer::start().await;
+        let mut body = crate::quote::tests::quote_response_fixture();
+        body["routePlan"][1]["swapInfo"]["feeAmount"] = serde_json::json!(["12"]);
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&server)
+            .await;
+
+        let error = JupiterSwapApiClient::new(server.uri(), None)
+            .quote(&quote_request(None))
+            .await
+            .unwrap_err();
+        let ErrorKind::DeserializationDetailed {
+            path,
+            message,
+            snippet,
+        } = error.kind()
+        else {
+            panic!("{error:?}");
+        };
+        assert_eq!(path, "routePlan[1].swapInfo.feeAmount");
+        assert!(message.contains("invalid type"), "{message}");
+        assert!(snippet.contains(r#""feeAmount":["12"]"#), "{snippet}");
+        assert_eq!(error.endpoint(), Some(Endpoint::Quote));
+        assert_eq!(error.status(), Some(StatusCode::OK));
+    }
+
+    async fn quote_error(body: serde_json::Value) -> ClientError {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(body))
+            .mount(&server)
+            .await;
+        JupiterSwapApiClient::new(server.uri(), None)
+            .quote(&quote_request(None))
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn known_quote_error_codes_are_mapped() {
+        let error = quote_error(serde_json::json!({
+            "error": "Could not find any route",
+            "errorCode": "COULD_NOT_FIND_ANY_ROUTE"
+        }))
+        .await;
+        let ErrorKind::QuoteFailed {
+            error: kind,
+            message,
+        } = error.kind()
+        else {
+            panic!("{error:?}");
+        };
+        assert_eq!(*kind, QuoteError::NoRoute);
+        assert_eq!(message, "Could not find any route");
+        assert_eq!(error.status(), Some(StatusCode::BAD_REQUEST));
+        assert!(!error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn unknown_quote_error_codes_keep_the_body() {
+        let body = serde_json::json!({
+            "error": "Amount is too small",
+            "errorCode": "AMOUNT_TOO_SMALL"
+        });
+        let error = quote_error(body.clone()).await;
+        let ErrorKind::RequestFailed {
+            status,
+            body: error_body,
+        } = error.kind()
+        else {
+            panic!("{error:?}");
+        };
+        assert_eq!(*status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(error_body).unwrap(),
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn request_timeout_overrides_the_client_one() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(QuoteResponse::default())
+                    .set_delay(std::time::Duration::from_millis(500)),
+            )
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+        let options = RequestOptions::default().timeout(std::time::Duration::from_millis(20));
+
+        let error = client
+            .quote_with_options(&quote_request(None), &options)
+            .await
+            .unwrap_err();
+        assert!(error.is_retryable(), "{error}");
+    }
+
+    #[tokio::test]
+    async fn quote_many_keeps_the_order_of_the_requests() {
+        let server = MockServer::start().await;
+        // The first quote is the last to be answered
+        for (amount, delay) in [(1_000, 200), (3_000, 0)] {
+            Mock::given(method("GET"))
+                .and(path("/quote"))
+                .and(query_param("amount", amount.to_string()))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(QuoteResponse {
+                            in_amount: amount,
+                            ..QuoteResponse::default()
+                        })
+                        .set_delay(std::time::Duration::from_millis(delay)),
+                )
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .and(query_param("amount", "2000"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": "Could not find any route",
+                "errorCode": "COULD_NOT_FIND_ANY_ROUTE"
+            })))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+
+        let quote_requests: Vec<_> = [1_000, 2_000, 3_000]
+            .into_iter()
+            .map(|amount| QuoteRequest {
+                amount,
+                ..quote_request(None)
+            })
+            .collect();
+        let progress = std::sync::Mutex::new(Vec::new());
+        let results = client
+            .quote_many_with_progress(&quote_requests, 3, |done, total| {
+                progress.lock().unwrap().push((done, total))
+            })
+            .await;
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().in_amount, 1_000);
+        assert_eq!(
+            results[1].as_ref().unwrap_err().status(),
+            Some(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(results[2].as_ref().unwrap().in_amount, 3_000);
+        assert_eq!(progress.into_inner().unwrap(), [(1, 3), (2, 3), (3, 3)]);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn quote_args_are_merged_into_the_query() {
+        let server = MockServer::start().await;
+        mock_quote(&server, &QuoteResponse::default()).await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+        let market = Pubkey::new_unique();
+        let quote_args = QuoteExtraArgs {
+            markets: Some(vec![market]),
+            custom: HashMap::from([
+                ("markets".to_string(), "overridden".to_string()),
+                ("maxHops".to_string(), "2".to_string()),
+            ]),
+            ..QuoteExtraArgs::default()
+        };
+
+        client
+            .quote(&QuoteRequest {
+                quote_args: Some(quote_args),
+                ..quote_request(None)
+            })
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let query: Vec<_> = requests[0].url.query_pairs().into_owned().collect();
+        let values = |key: &str| {
+            query
+                .iter()
+                .filter(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values("markets"), [market.to_string()]);
+        assert_eq!(values("maxHops"), ["2"]);
+        assert_eq!(values("amount"), ["1000"]);
+    }
+
+    fn retrying_client(server: &MockServer, max_retries: u32) -> JupiterSwapApiClient {
+        JupiterSwapApiClientBuilder::new(server.uri())
+            .retry_policy(
+                retry::RetryPolicy::none()
+                    .max_retries(max_retries)
+                    .base_delay(std::time::Duration::from_millis(1))
+                    .jitter(std::sync::Arc::new(retry::NoJitter)),
+            )
+            .build()
+            .unwrap()
+    }
+
+    async fn mock_unavailable_quotes(server: &MockServer, times: u64) {
+        for attempt in 1..=times {
+            Mock::given(method("GET"))
+                .and(path("/quote"))
+                .respond_with(
+                    ResponseTemplate::new(503)
+                        .insert_header("x-request-id", format!("failed-{attempt}")),
+                )
+                .up_to_n_times(1)
+                .with_priority(1)
+                .mount(server)
+                .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn request_ids_of_retried_attempts_are_kept() {
+        let server = MockServer::start().await;
+        mock_unavailable_quotes(&server, 2).await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-request-id", "succeeded")
+                    .set_body_json(QuoteResponse::default()),
+            )
+            .mount(&server)
+            .await;
+
+        let quote_response = retrying_client(&server, 2)
+            .quote(&quote_request(None))
+            .await
+            .unwrap();
+        assert_eq!(
+            quote_response.request_ids,
+            ["failed-1", "failed-2", "succeeded"]
+        );
+    }
+
+    #[tokio::test]
+    async fn request_ids_of_all_attempts_are_in_the_error() {
+        let server = MockServer::start().await;
+        mock_unavailable_quotes(&server, 3).await;
+
+        let error = retrying_client(&server, 2)
+            .quote(&quote_request(None))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(error.request_ids(), ["failed-1", "failed-2", "failed-3"]);
+        assert!(
+            error
+                .to_string()
+                .ends_with("(request ids: failed-1, failed-2, failed-3)"),
+            "{error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn all_quote_methods_reject_other_swap_mode() {
+        let server = MockServer::start().await;
+        mock_quote(&server, &QuoteResponse::default()).await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+        let quote_request = quote_request(Some(SwapMode::ExactOut));
+
+        let errors = [
+            client
+                .quote_raw(&quote_request)
+                .await
+                .map(drop)
+                .unwrap_err(),
+            client
+                .quote_raw_bytes(&quote_request)
+                .await
+                .map(drop)
+                .unwrap_err(),
+            client
+                .quote_strict(&quote_request)
+                .await
+                .map(drop)
+                .unwrap_err(),
+        ];
+        for error in errors {
+            assert!(
+                matches!(
+                    error.kind(),
+                    ErrorKind::SwapModeMismatch(SwapModeMismatch {
+                        expected: SwapMode::ExactOut,
+                        actual: SwapMode::ExactIn,
+                    })
+                ),
+                "{error:?}"
             );
+            assert_eq!(error.endpoint(), Some(Endpoint::Quote));
         }
-        let response = Client::new()
-            .post(format!("{}/swap-instructions", self.base_path))
-            .json(swap_request)
-            .headers(headers)
-            .send()
-            .await?;
-        check_status_code_and_deserialize::<SwapInstructionsResponseInternal>(response)
+    }
+
+    async fn mock_status(server: &MockServer, status: u16) {
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(status))
+            .mount(server)
+            .await;
+    }
+
+    async fn request_count(server: &MockServer) -> usize {
+        server.received_requests().await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn server_error_fails_over_and_sticks_to_the_endpoint_that_worked() {
+        let primary = MockServer::start().await;
+        mock_status(&primary, 503).await;
+        let failover = MockServer::start().await;
+        mock_quote(&failover, &QuoteResponse::default()).await;
+        let client = JupiterSwapApiClient::new(String::new(), None)
+            .with_endpoints(vec![primary.uri(), failover.uri()]);
+        assert_eq!(client.active_base_path(), primary.uri());
+
+        client.quote(&quote_request(None)).await.unwrap();
+        assert_eq!(client.active_base_path(), failover.uri());
+        // Shared by the clones, the primary is no longer tried first
+        client.clone().quote(&quote_request(None)).await.unwrap();
+        assert_eq!(request_count(&primary).await, 1);
+        assert_eq!(request_count(&failover).await, 2);
+    }
+
+    #[tokio::test]
+    async fn client_error_does_not_fail_over() {
+        let primary = MockServer::start().await;
+        mock_status(&primary, 400).await;
+        let failover = MockServer::start().await;
+        mock_quote(&failover, &QuoteResponse::default()).await;
+        let client = JupiterSwapApiClient::new(String::new(), None)
+            .with_endpoints(vec![primary.uri(), failover.uri()]);
+
+        let error = client.quote(&quote_request(None)).await.unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::BAD_REQUEST));
+        assert_eq!(client.active_base_path(), primary.uri());
+        assert_eq!(request_count(&failover).await, 0);
+    }
+
+    #[tokio::test]
+    async fn connect_error_and_timeout_fail_over() {
+        let failover = MockServer::start().await;
+        mock_quote(&failover, &QuoteResponse::default()).await;
+        // Nothing listens on the discard port
+        let unreachable = "http://127.0.0.1:9".to_string();
+        let client = JupiterSwapApiClient::new(String::new(), None)
+            .with_endpoints(vec![unreachable, failover.uri()]);
+        client.quote(&quote_request(None)).await.unwrap();
+        assert_eq!(client.active_base_path(), failover.uri());
+
+        let slow = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(QuoteResponse::default())
+                    .set_delay(std::time::Duration::from_millis(500)),
+            )
+            .mount(&slow)
+            .await;
+        let client = JupiterSwapApiClient::new(String::new(), None)
+            .with_endpoints(vec![slow.uri(), failover.uri()]);
+        let options = RequestOptions::default().timeout(std::time::Duration::from_millis(50));
+        client
+            .quote_with_options(&quote_request(None), &options)
             .await
-            .map(Into::into)
+            .unwrap();
+        assert_eq!(client.active_base_path(), failover.uri());
+    }
+
+    #[tokio::test]
+    async fn last_endpoint_server_error_is_returned() {
+        let primary = MockServer::start().await;
+        mock_status(&primary, 503).await;
+        let failover = MockServer::start().await;
+        mock_status(&failover, 502).await;
+        let client = JupiterSwapApiClient::new(String::new(), None)
+            .with_endpoints(vec![primary.uri(), failover.uri()]);
+
+        let error = client.quote(&quote_request(None)).await.unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::BAD_GATEWAY));
+        // Neither answered, the primary is still tried first
+        assert_eq!(client.active_base_path(), primary.uri());
     }
 }
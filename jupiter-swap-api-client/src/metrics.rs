@@ -0,0 +1,417 @@
+//! Request metrics hook, see [`JupiterSwapApiClientBuilder::metrics`](crate::builder::JupiterSwapApiClientBuilder::metrics)
+//!
+//! With the `metrics` feature, clients default to `MetricsFacade` which emits `jupiter_requests_total`
+//! and `jupiter_request_duration_seconds` through the `metrics` crate. These names, without the `client`
+//! of the metrics first requested, are kept for the dashboards built on them; set
+//! `MetricsFacade::with_names(MetricNames::JUPITER_CLIENT)` on the builder for `jupiter_client_requests_total`
+//! and `jupiter_client_request_duration_seconds`.
+
+use std::{fmt, panic::AssertUnwindSafe, sync::Arc, time::Duration};
+
+use reqwest::StatusCode;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum Endpoint {
+    Quote,
+    Swap,
+    SwapInstructions,
+    Health,
+    UltraOrder,
+    UltraExecute,
+    UltraBalances,
+    UltraShield,
+    Price,
+    TriggerCreateOrder,
+    TriggerCancelOrder,
+    TriggerCancelOrders,
+    TriggerExecute,
+    TriggerGetOrders,
+    RecurringCreateOrder,
+    RecurringCancelOrder,
+    RecurringPriceDeposit,
+    RecurringPriceWithdraw,
+    RecurringExecute,
+    RecurringGetOrders,
+    TokenSearch,
+    TokenTag,
+    ProgramIdToLabel,
+    /// Sent through [`JupiterSwapApiClient::request`](crate::JupiterSwapApiClient::request)
+    Other,
+}
+
+impl Endpoint {
+    /// Label of the endpoint in metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Quote => "quote",
+            Self::Swap => "swap",
+            Self::SwapInstructions => "swap_instructions",
+            Self::Health => "health",
+            Self::UltraOrder => "ultra_order",
+            Self::UltraExecute => "ultra_execute",
+            Self::UltraBalances => "ultra_balances",
+            Self::UltraShield => "ultra_shield",
+            Self::Price => "price",
+            Self::TriggerCreateOrder => "trigger_create_order",
+            Self::TriggerCancelOrder => "trigger_cancel_order",
+            Self::TriggerCancelOrders => "trigger_cancel_orders",
+            Self::TriggerExecute => "trigger_execute",
+            Self::TriggerGetOrders => "trigger_get_orders",
+            Self::RecurringCreateOrder => "recurring_create_order",
+            Self::RecurringCancelOrder => "recurring_cancel_order",
+            Self::RecurringPriceDeposit => "recurring_price_deposit",
+            Self::RecurringPriceWithdraw => "recurring_price_withdraw",
+            Self::RecurringExecute => "recurring_execute",
+            Self::RecurringGetOrders => "recurring_get_orders",
+            Self::TokenSearch => "token_search",
+            Self::TokenTag => "token_tag",
+            Self::ProgramIdToLabel => "program_id_to_label",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum Outcome {
+    /// 2xx response, or 304 to a conditional request
+    Success,
+    /// Any other response status
+    HttpError,
+    /// No response, e.g. connection error or timeout
+    TransportError,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::HttpError => "http_error",
+            Self::TransportError => "transport_error",
+        }
+    }
+}
+
+pub trait ClientMetrics: Send + Sync {
+    /// Called once per attempt of every request, `attempt` starting at 1, `latency` being the time to the response headers
+    fn record(
+        &self,
+        endpoint: Endpoint,
+        status: Option<StatusCode>,
+        latency: Duration,
+        outcome: Outcome,
+        attempt: u32,
+    );
+}
+
+/// Default hook, records nothing
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopMetrics;
+
+impl ClientMetrics for NoopMetrics {
+    fn record(&self, _: Endpoint, _: Option<StatusCode>, _: Duration, _: Outcome, _: u32) {}
+}
+
+/// Shared metrics hook of a client
+#[derive(Clone)]
+pub(crate) struct MetricsHook(pub(crate) Arc<dyn ClientMetrics>);
+
+impl Default for MetricsHook {
+    #[cfg(not(feature = "metrics"))]
+    fn default() -> Self {
+        Self(Arc::new(NoopMetrics))
+    }
+
+    #[cfg(feature = "metrics")]
+    fn default() -> Self {
+        Self(Arc::new(MetricsFacade::default()))
+    }
+}
+
+impl fmt::Debug for MetricsHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MetricsHook")
+    }
+}
+
+impl MetricsHook {
+    /// A panicking hook must not fail the request, the panic is swallowed
+    pub(crate) fn record(
+        &self,
+        endpoint: Endpoint,
+        status: Option<StatusCode>,
+        latency: Duration,
+        outcome: Outcome,
+        attempt: u32,
+    ) {
+        let recorded = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            self.0.record(endpoint, status, latency, outcome, attempt)
+        }));
+        if recorded.is_err() {
+            log::debug!("Metrics hook panicked while recording a {endpoint} request");
+        }
+    }
+}
+
+/// Names of the metrics emitted by [`MetricsFacade`]
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricNames {
+    /// Counter labelled by endpoint, outcome and status
+    pub requests_total: &'static str,
+    /// Histogram labelled by endpoint and outcome
+    pub request_duration_seconds: &'static str,
+}
+
+#[cfg(feature = "metrics")]
+impl MetricNames {
+    /// `jupiter_requests_total` and `jupiter_request_duration_seconds`, the default
+    pub const JUPITER: Self = Self {
+        requests_total: "jupiter_requests_total",
+        request_duration_seconds: "jupiter_request_duration_seconds",
+    };
+    /// `jupiter_client_requests_total` and `jupiter_client_request_duration_seconds`
+    pub const JUPITER_CLIENT: Self = Self {
+        requests_total: "jupiter_client_requests_total",
+        request_duration_seconds: "jupiter_client_request_duration_seconds",
+    };
+}
+
+#[cfg(feature = "metrics")]
+impl Default for MetricNames {
+    fn default() -> Self {
+        Self::JUPITER
+    }
+}
+
+/// Emits the request metrics through the `metrics` crate facade, the default hook with the `metrics` feature
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MetricsFacade {
+    names: MetricNames,
+}
+
+#[cfg(feature = "metrics")]
+impl MetricsFacade {
+    pub fn with_names(names: MetricNames) -> Self {
+        Self { names }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl ClientMetrics for MetricsFacade {
+    fn record(
+        &self,
+        endpoint: Endpoint,
+        status: Option<StatusCode>,
+        latency: Duration,
+        outcome: Outcome,
+        _attempt: u32,
+    ) {
+        let status = status.map_or_else(String::new, |status| status.as_u16().to_string());
+        ::metrics::counter!(
+            self.names.requests_total,
+            "endpoint" => endpoint.as_str(),
+            "outcome" => outcome.as_str(),
+            "status" => status,
+        )
+        .increment(1);
+        ::metrics::histogram!(
+            self.names.request_duration_seconds,
+            "endpoint" => endpoint.as_str(),
+            "outcome" => outcome.as_str(),
+        )
+        .record(latency.as_secs_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{
+        retry::{NoJitter, RetryPolicy},
+        JupiterSwapApiClient,
+    };
+
+    type Recorded = (Endpoint, Option<StatusCode>, Outcome, u32);
+
+    #[derive(Default)]
+    struct RecordingMetrics(Mutex<Vec<Recorded>>);
+
+    impl ClientMetrics for RecordingMetrics {
+        fn record(
+            &self,
+            endpoint: Endpoint,
+            status: Option<StatusCode>,
+            _latency: Duration,
+            outcome: Outcome,
+            attempt: u32,
+        ) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((endpoint, status, outcome, attempt));
+        }
+    }
+
+    struct PanickingMetrics;
+
+    impl ClientMetrics for PanickingMetrics {
+        fn record(&self, _: Endpoint, _: Option<StatusCode>, _: Duration, _: Outcome, _: u32) {
+            panic!("metrics backend is down");
+        }
+    }
+
+    async fn mock_health(server: &MockServer, status: u16, times: u64) {
+        Mock::given(method("GET"))
+            .and(path("/health"))
+            .respond_with(ResponseTemplate::new(status).set_body_json(serde_json::json!({})))
+            .up_to_n_times(times)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn every_attempt_is_recorded() {
+        let server = MockServer::start().await;
+        mock_health(&server, 503, 2).await;
+        mock_health(&server, 200, 1).await;
+        let metrics = Arc::new(RecordingMetrics::default());
+        let client = JupiterSwapApiClient::builder(server.uri())
+            .metrics(metrics.clone())
+            .retry_policy(
+                RetryPolicy::default()
+                    .base_delay(Duration::from_millis(1))
+                    .jitter(Arc::new(NoJitter)),
+            )
+            .build()
+            .unwrap();
+
+        client.health().await.unwrap();
+        assert_eq!(
+            *metrics.0.lock().unwrap(),
+            [
+                (
+                    Endpoint::Health,
+                    Some(StatusCode::SERVICE_UNAVAILABLE),
+                    Outcome::HttpError,
+                    1
+                ),
+                (
+                    Endpoint::Health,
+                    Some(StatusCode::SERVICE_UNAVAILABLE),
+                    Outcome::HttpError,
+                    2
+                ),
+                (Endpoint::Health, Some(StatusCode::OK), Outcome::Success, 3),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn transport_errors_are_recorded() {
+        let metrics = Arc::new(RecordingMetrics::default());
+        // Nothing listens on the discard port
+        let client = JupiterSwapApiClient::builder("http://127.0.0.1:9".to_string())
+            .metrics(metrics.clone())
+            .build()
+            .unwrap();
+
+        assert!(!client.health().await.unwrap().up);
+        assert_eq!(
+            *metrics.0.lock().unwrap(),
+            [(Endpoint::Health, None, Outcome::TransportError, 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn panicking_hook_does_not_fail_requests() {
+        let server = MockServer::start().await;
+        mock_health(&server, 200, 1).await;
+        let client = JupiterSwapApiClient::builder(server.uri())
+            .metrics(Arc::new(PanickingMetrics))
+            .build()
+            .unwrap();
+
+        client.health().await.unwrap();
+    }
+
+    #[cfg(feature = "metrics")]
+    mod facade {
+        use ::metrics::{
+            Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+        };
+
+        use super::*;
+
+        /// Names of the metrics registered
+        #[derive(Default)]
+        struct NameRecorder(Mutex<Vec<String>>);
+
+        impl Recorder for NameRecorder {
+            fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+            fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+                self.0.lock().unwrap().push(key.name().to_string());
+                Counter::noop()
+            }
+
+            fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+                self.0.lock().unwrap().push(key.name().to_string());
+                Gauge::noop()
+            }
+
+            fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+                self.0.lock().unwrap().push(key.name().to_string());
+                Histogram::noop()
+            }
+        }
+
+        fn recorded_names(facade: MetricsFacade) -> Vec<String> {
+            let recorder = NameRecorder::default();
+            ::metrics::with_local_recorder(&recorder, || {
+                facade.record(
+                    Endpoint::Quote,
+                    Some(StatusCode::OK),
+                    Duration::from_millis(10),
+                    Outcome::Success,
+                    1,
+                );
+            });
+            recorder.0.into_inner().unwrap()
+        }
+
+        #[test]
+        fn default_names() {
+            assert_eq!(
+                recorded_names(MetricsFacade::default()),
+                ["jupiter_requests_total", "jupiter_request_duration_seconds"]
+            );
+        }
+
+        #[test]
+        fn client_prefixed_names() {
+            assert_eq!(
+                recorded_names(MetricsFacade::with_names(MetricNames::JUPITER_CLIENT)),
+                [
+                    "jupiter_client_requests_total",
+                    "jupiter_client_request_duration_seconds"
+                ]
+            );
+        }
+    }
+}
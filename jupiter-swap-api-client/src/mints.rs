@@ -0,0 +1,8 @@
+//! Well-known mints
+
+use solana_pubkey::{pubkey, Pubkey};
+
+/// Wrapped SOL, the mint the API uses for native SOL
+pub const NATIVE_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
+pub const USDC_MINT: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+pub const USDT_MINT: Pubkey = pubkey!("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB");
@@ -0,0 +1,276 @@
+//! Building requests without sending them and parsing responses received elsewhere,
+//! for setups where the HTTP exchange happens outside of this client, e.g. in an egress service
+//!
+//! The send methods of the client are built on the same request construction and error mapping.
+
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use reqwest::{header::HeaderMap, Method, Request, Response, StatusCode};
+
+use crate::{
+    check_quote_is_success, check_status_code_and_deserialize,
+    metrics::Endpoint,
+    quote::{QuoteRequest, QuoteResponse},
+    quote_query,
+    swap::{SwapInstructionsResponse, SwapInstructionsResponseInternal, SwapRequest, SwapResponse},
+    with_query_and_body, ClientError, JupiterSwapApiClient, QUOTE_PATH, SWAP_INSTRUCTIONS_PATH,
+    SWAP_PATH,
+};
+
+fn response(status: StatusCode, headers: HeaderMap, body: Bytes) -> Response {
+    let mut response = http::Response::new(body);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    Response::from(response)
+}
+
+impl JupiterSwapApiClient {
+    /// GET /quote request as [`Self::quote`] would send it to the active base path
+    pub fn build_quote_request(
+        &self,
+        quote_request: &QuoteRequest,
+    ) -> Result<Request, ClientError> {
+        self.build_request(
+            Method::GET,
+            QUOTE_PATH,
+            &Default::default(),
+            quote_query(quote_request, self.multi_value_style)
+                .map_err(|e| e.with_endpoint(Endpoint::Quote))?,
+        )
+    }
+
+    /// POST /swap request as [`Self::swap`] would send it to the active base path
+    pub fn build_swap_request(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+    ) -> Result<Request, ClientError> {
+        self.check_price_impact(Endpoint::Swap, &swap_request.quote_response)?;
+        self.build_request(Method::POST, SWAP_PATH, &Default::default(), |request| {
+            with_query_and_body(request, Some(&extra_args), Some(swap_request))
+        })
+    }
+
+    /// POST /swap-instructions request as [`Self::swap_instructions`] would send it to the active base path
+    pub fn build_swap_instructions_request(
+        &self,
+        swap_request: &SwapRequest,
+        extra_args: Option<HashMap<String, String>>,
+    ) -> Result<Request, ClientError> {
+        self.check_price_impact(Endpoint::SwapInstructions, &swap_request.quote_response)?;
+        self.build_request(
+            Method::POST,
+            SWAP_INSTRUCTIONS_PATH,
+            &Default::default(),
+            |request| with_query_and_body(request, Some(&extra_args), Some(swap_request)),
+        )
+    }
+
+    /// Parse a /quote response with the error mapping of [`Self::quote`]
+    pub async fn parse_quote_response(
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<QuoteResponse, ClientError> {
+        let response = check_quote_is_success(response(status, headers, body)).await?;
+        check_status_code_and_deserialize(response).await
+    }
+
+    /// Parse a /swap response with the error mapping of [`Self::swap`]
+    pub async fn parse_swap_response(
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<SwapResponse, ClientError> {
+        check_status_code_and_deserialize(response(status, headers, body)).await
+    }
+
+    /// Parse a /swap-instructions response with the error mapping of [`Self::swap_instructions`]
+    pub async fn parse_swap_instructions_response(
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<SwapInstructionsResponse, ClientError> {
+        check_status_code_and_deserialize::<SwapInstructionsResponseInternal>(response(
+            status, headers, body,
+        ))
+        .await
+        .map(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+    use serde_json::json;
+    use solana_pubkey::Pubkey;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{
+        mints::{NATIVE_MINT, USDC_MINT},
+        quote::{tests::quote_response_fixture, QuoteError},
+        ErrorKind,
+    };
+
+    fn quote_request() -> QuoteRequest {
+        QuoteRequest {
+            input_mint: NATIVE_MINT,
+            output_mint: USDC_MINT,
+            amount: 1_000_000_000,
+            slippage_bps: 50,
+            ..QuoteRequest::default()
+        }
+    }
+
+    /// Send `request` elsewhere than through the client, as an egress service would
+    async fn exchange(request: Request) -> (StatusCode, HeaderMap, Bytes) {
+        let response = Client::new().execute(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        (status, headers, response.bytes().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn quote_request_round_trips() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(quote_response_fixture()))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), Some("key".to_string()));
+
+        let request = client.build_quote_request(&quote_request()).unwrap();
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(request.url().path(), "/quote");
+        let query: HashMap<_, _> = request.url().query_pairs().into_owned().collect();
+        assert_eq!(query["inputMint"], NATIVE_MINT.to_string());
+        assert_eq!(query["outputMint"], USDC_MINT.to_string());
+        assert_eq!(query["amount"], "1000000000");
+        assert_eq!(query["slippageBps"], "50");
+        assert_eq!(request.headers()["x-api-key"], "key");
+        assert!(request.body().is_none());
+
+        let (status, headers, body) = exchange(request).await;
+        let quote_response = JupiterSwapApiClient::parse_quote_response(status, headers, body)
+            .await
+            .unwrap();
+        assert_eq!(
+            quote_response,
+            serde_json::from_value(quote_response_fixture()).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn swap_requests_round_trip() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/swap"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "swapTransaction": "AQID",
+                "lastValidBlockHeight": 301_000_150,
+                "prioritizationFeeLamports": 5_000,
+                "computeUnitLimit": 200_000
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/swap-instructions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "computeBudgetInstructions": [],
+                "setupInstructions": [],
+                "swapInstruction": {
+                    "programId": "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4",
+                    "accounts": [],
+                    "data": "AQ=="
+                },
+                "otherInstructions": [],
+                "addressLookupTableAddresses": [],
+                "prioritizationFeeLamports": 5_000,
+                "computeUnitLimit": 200_000
+            })))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+        let swap_request = SwapRequest {
+            user_public_key: Pubkey::new_unique(),
+            quote_response: serde_json::from_value(quote_response_fixture()).unwrap(),
+            ..SwapRequest::default()
+        };
+        let extra_args = Some(HashMap::from([("flag".to_string(), "1".to_string())]));
+
+        let request = client
+            .build_swap_request(&swap_request, extra_args.clone())
+            .unwrap();
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.url().path(), "/swap");
+        assert_eq!(request.url().query(), Some("flag=1"));
+        let body = request.body().and_then(|body| body.as_bytes()).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(body).unwrap(),
+            serde_json::to_value(&swap_request).unwrap()
+        );
+        let (status, headers, body) = exchange(request).await;
+        let swap_response = JupiterSwapApiClient::parse_swap_response(status, headers, body)
+            .await
+            .unwrap();
+        assert_eq!(swap_response.swap_transaction, [1, 2, 3]);
+        assert_eq!(swap_response.last_valid_block_height, 301_000_150);
+
+        let request = client
+            .build_swap_instructions_request(&swap_request, extra_args)
+            .unwrap();
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.url().path(), "/swap-instructions");
+        let (status, headers, body) = exchange(request).await;
+        let swap_instructions =
+            JupiterSwapApiClient::parse_swap_instructions_response(status, headers, body)
+                .await
+                .unwrap();
+        assert_eq!(swap_instructions.swap_instruction.data, [1]);
+        assert_eq!(swap_instructions.compute_unit_limit, 200_000);
+    }
+
+    #[tokio::test]
+    async fn failed_responses_are_mapped_as_when_sent_by_the_client() {
+        let body = json!({
+            "error": "Could not find any route",
+            "errorCode": "COULD_NOT_FIND_ANY_ROUTE"
+        });
+        let error = JupiterSwapApiClient::parse_quote_response(
+            StatusCode::BAD_REQUEST,
+            HeaderMap::new(),
+            Bytes::from(body.to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(
+                error.kind(),
+                ErrorKind::QuoteFailed {
+                    error: QuoteError::NoRoute,
+                    ..
+                }
+            ),
+            "{error:?}"
+        );
+
+        let error = JupiterSwapApiClient::parse_swap_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            HeaderMap::new(),
+            Bytes::from_static(b"upstream failed"),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(error.kind(), ErrorKind::RequestFailed { body, .. } if body == "upstream failed"),
+            "{error:?}"
+        );
+        assert_eq!(error.status(), Some(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+}
@@ -0,0 +1,74 @@
+//! Lazy iteration over the pages of the order history endpoints
+//!
+
+use std::{collections::VecDeque, future::Future};
+
+use futures::{stream, Stream};
+
+use crate::ClientError;
+
+/// Pages fetched at most by a stream, in case a server keeps announcing a next page
+pub const MAX_PAGES: u32 = 1_000;
+
+/// A page of items
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    /// `None` on the last page
+    pub next_page: Option<u32>,
+    pub total_pages: Option<u32>,
+}
+
+impl<T> Paginated<T> {
+    pub(crate) fn new(items: Vec<T>, page: u32, total_pages: u32) -> Self {
+        Self {
+            items,
+            next_page: (page < total_pages).then(|| page + 1),
+            total_pages: Some(total_pages),
+        }
+    }
+}
+
+struct PaginationState<T, F> {
+    fetch_page: F,
+    next_page: Option<u32>,
+    fetched_pages: u32,
+    items: VecDeque<T>,
+}
+
+/// Yield the items of every page starting at `first_page`, fetching a page once the previous one is consumed.
+/// Stops after an error, an empty page or [`MAX_PAGES`] pages.
+pub(crate) fn paginate<T, F, Fut>(
+    first_page: u32,
+    fetch_page: F,
+) -> impl Stream<Item = Result<T, ClientError>>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<Paginated<T>, ClientError>>,
+{
+    let state = PaginationState {
+        fetch_page,
+        next_page: Some(first_page),
+        fetched_pages: 0,
+        items: VecDeque::new(),
+    };
+    stream::unfold(state, |mut state| async move {
+        if let Some(item) = state.items.pop_front() {
+            return Some((Ok(item), state));
+        }
+        let page = state.next_page.take()?;
+        if state.fetched_pages == MAX_PAGES {
+            return None;
+        }
+        state.fetched_pages += 1;
+        match (state.fetch_page)(page).await {
+            Ok(paginated) => {
+                state.items = paginated.items.into();
+                let item = state.items.pop_front()?;
+                state.next_page = paginated.next_page;
+                Some((Ok(item), state))
+            }
+            Err(e) => Some((Err(e), state)),
+        }
+    })
+}
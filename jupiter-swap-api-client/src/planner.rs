@@ -0,0 +1,363 @@
+//! Balance check, quote and swap of many wallets at once, requires the `rpc` feature
+//!
+//! Every plan goes through the same steps: resolve the amount from the balance of the wallet when given
+//! as a percentage, quote, then request the swap transaction, ready to sign.
+//! A failed plan does not fail the others.
+
+use futures::{stream, StreamExt};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use solana_client::{client_error::ClientErrorKind, nonblocking::rpc_client::RpcClient};
+use solana_pubkey::Pubkey;
+use thiserror::Error;
+
+use crate::{
+    ata::associated_token_address,
+    mints::NATIVE_MINT,
+    quote::{QuoteRequest, QuoteResponse},
+    swap::{SwapRequest, SwapResponse},
+    transaction_config::TransactionConfig,
+    ClientError, ErrorKind, JupiterSwapApiClient,
+};
+
+/// Lamports left in a wallet swapping a percentage of its SOL, covering fees and the rent of the token accounts
+pub const DEFAULT_SOL_BUFFER_LAMPORTS: u64 = 10_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanAmount {
+    /// Raw amount of the input mint
+    Exact(u64),
+    /// Percentage, above 0 and up to 100, of the balance of the wallet in the input mint,
+    /// rounded down. For native SOL the percentage is of the balance less the SOL buffer.
+    BalancePercentage(Decimal),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapPlan {
+    pub wallet: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount_or_percentage: PlanAmount,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlanOptions {
+    /// Plans processed at once, each sending at most one request at a time.
+    /// Requests still go through the retry policy of the client, e.g. on 429.
+    pub concurrency: usize,
+    /// See [`DEFAULT_SOL_BUFFER_LAMPORTS`]
+    pub sol_buffer_lamports: u64,
+    /// Template of the quote requests, its mints and amount are replaced by the ones of the plan
+    pub quote_request: QuoteRequest,
+    pub transaction_config: TransactionConfig,
+}
+
+impl Default for PlanOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            sol_buffer_lamports: DEFAULT_SOL_BUFFER_LAMPORTS,
+            quote_request: QuoteRequest::default(),
+            transaction_config: TransactionConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedSwap {
+    pub plan: SwapPlan,
+    /// Raw amount quoted
+    pub amount: u64,
+    pub quote_response: QuoteResponse,
+    /// `swap_transaction` is to be signed by `plan.wallet`
+    pub swap_response: SwapResponse,
+}
+
+#[derive(Debug, Error)]
+pub enum PlanError {
+    #[error("Percentage {0} is not above 0 and up to 100")]
+    InvalidPercentage(Decimal),
+    #[error("Failed to fetch the balance: {0}")]
+    Balance(ClientError),
+    #[error("Balance of {balance} leaves nothing to swap")]
+    InsufficientBalance { balance: u64 },
+    #[error("Quote failed: {0}")]
+    Quote(ClientError),
+    #[error("Swap failed: {0}")]
+    Swap(ClientError),
+}
+
+/// Plan the swaps of `plans`, returning their results in the same order
+pub async fn plan_swaps(
+    client: &JupiterSwapApiClient,
+    rpc: &RpcClient,
+    plans: Vec<SwapPlan>,
+    options: &PlanOptions,
+) -> Vec<Result<PlannedSwap, PlanError>> {
+    stream::iter(plans)
+        .map(|plan| plan_swap(client, rpc, plan, options))
+        .buffered(options.concurrency.max(1))
+        .collect()
+        .await
+}
+
+async fn plan_swap(
+    client: &JupiterSwapApiClient,
+    rpc: &RpcClient,
+    plan: SwapPlan,
+    options: &PlanOptions,
+) -> Result<PlannedSwap, PlanError> {
+    let amount = match plan.amount_or_percentage {
+        PlanAmount::Exact(amount) => amount,
+        PlanAmount::BalancePercentage(percentage) => {
+            if percentage <= Decimal::ZERO || percentage > Decimal::ONE_HUNDRED {
+                return Err(PlanError::InvalidPercentage(percentage));
+            }
+            let balance = balance(rpc, &plan.wallet, &plan.input_mint)
+                .await
+                .map_err(PlanError::Balance)?;
+            let available = if plan.input_mint == NATIVE_MINT {
+                balance.saturating_sub(options.sol_buffer_lamports)
+            } else {
+                balance
+            };
+            percentage_of(available, percentage)
+                .filter(|amount| *amount > 0)
+                .ok_or(PlanError::InsufficientBalance { balance })?
+        }
+    };
+    let quote_request = QuoteRequest {
+        input_mint: plan.input_mint,
+        output_mint: plan.output_mint,
+        amount,
+        ..options.quote_request.clone()
+    };
+    let quote_response = client
+        .quote(&quote_request)
+        .await
+        .map_err(PlanError::Quote)?;
+    let swap_response = client
+        .swap(
+            &SwapRequest {
+                user_public_key: plan.wallet,
+                quote_response: quote_response.clone(),
+                config: options.transaction_config.clone(),
+            },
+            None,
+        )
+        .await
+        .map_err(PlanError::Swap)?;
+    Ok(PlannedSwap {
+        plan,
+        amount,
+        quote_response,
+        swap_response,
+    })
+}
+
+/// `percentage` of `amount` rounded down
+fn percentage_of(amount: u64, percentage: Decimal) -> Option<u64> {
+    (Decimal::from(amount) * percentage / Decimal::ONE_HUNDRED)
+        .floor()
+        .to_u64()
+}
+
+/// Lamports of `wallet` for native SOL, otherwise the balance of its associated token account of `mint`
+async fn balance(rpc: &RpcClient, wallet: &Pubkey, mint: &Pubkey) -> Result<u64, ClientError> {
+    let rpc_error = |e| ClientError::from(ErrorKind::RpcError(Box::new(e)));
+    if *mint == NATIVE_MINT {
+        return rpc.get_balance(wallet).await.map_err(rpc_error);
+    }
+    let token_program = rpc.get_account(mint).await.map_err(rpc_error)?.owner;
+    let token_account = associated_token_address(wallet, mint, &token_program);
+    let balance = rpc
+        .get_token_account_balance(&token_account)
+        .await
+        .map_err(rpc_error)?;
+    balance.amount.parse().map_err(|_| {
+        ErrorKind::RpcError(Box::new(
+            ClientErrorKind::Custom(format!("Invalid token amount {}", balance.amount)).into(),
+        ))
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+    use solana_client::rpc_request::RpcRequest;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::mints::USDC_MINT;
+
+    /// RPC answering `get_balance` once with `lamports`, then with the default of the mock of 50
+    fn rpc_with_balance(lamports: Option<u64>) -> RpcClient {
+        let mocks = lamports
+            .map(|lamports| {
+                HashMap::from([(
+                    RpcRequest::GetBalance,
+                    json!({ "context": { "slot": 1 }, "value": lamports }),
+                )])
+            })
+            .unwrap_or_default();
+        RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks)
+    }
+
+    async fn mock_jupiter() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .and(query_param("outputMint", Pubkey::default().to_string()))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "error": "Could not find any route",
+                "errorCode": "COULD_NOT_FIND_ANY_ROUTE",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(QuoteResponse::default()))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/swap"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(SwapResponse::default()))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn plan(output_mint: Pubkey, amount_or_percentage: PlanAmount) -> SwapPlan {
+        SwapPlan {
+            wallet: Pubkey::new_unique(),
+            input_mint: NATIVE_MINT,
+            output_mint,
+            amount_or_percentage,
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_plans_do_not_fail_the_others() {
+        let server = mock_jupiter().await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+        let plans = vec![
+            plan(USDC_MINT, PlanAmount::Exact(1_000)),
+            plan(Pubkey::default(), PlanAmount::Exact(1_000)),
+            plan(USDC_MINT, PlanAmount::BalancePercentage(Decimal::ZERO)),
+            plan(USDC_MINT, PlanAmount::Exact(2_000)),
+        ];
+
+        let results = plan_swaps(
+            &client,
+            &rpc_with_balance(None),
+            plans.clone(),
+            &PlanOptions::default(),
+        )
+        .await;
+        assert_eq!(results.len(), 4);
+        let planned = results[0].as_ref().unwrap();
+        assert_eq!(planned.plan, plans[0]);
+        assert_eq!(planned.amount, 1_000);
+        assert!(
+            matches!(&results[1], Err(PlanError::Quote(e)) if matches!(e.kind(), ErrorKind::QuoteFailed { .. }))
+        );
+        assert!(matches!(
+            results[2],
+            Err(PlanError::InvalidPercentage(percentage)) if percentage.is_zero()
+        ));
+        assert_eq!(results[3].as_ref().unwrap().amount, 2_000);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_percentages() {
+        let server = MockServer::start().await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+        for percentage in [Decimal::ZERO, Decimal::NEGATIVE_ONE, Decimal::new(1001, 1)] {
+            let results = plan_swaps(
+                &client,
+                &rpc_with_balance(Some(1_000_000_000)),
+                vec![plan(USDC_MINT, PlanAmount::BalancePercentage(percentage))],
+                &PlanOptions::default(),
+            )
+            .await;
+            assert!(matches!(
+                results[0],
+                Err(PlanError::InvalidPercentage(invalid)) if invalid == percentage
+            ));
+        }
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn sol_percentage_leaves_the_buffer() {
+        let server = mock_jupiter().await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+        let results = plan_swaps(
+            &client,
+            &rpc_with_balance(Some(DEFAULT_SOL_BUFFER_LAMPORTS + 1_000_000_001)),
+            vec![plan(
+                USDC_MINT,
+                PlanAmount::BalancePercentage(Decimal::ONE_HUNDRED),
+            )],
+            &PlanOptions::default(),
+        )
+        .await;
+        assert_eq!(results[0].as_ref().unwrap().amount, 1_000_000_001);
+
+        // Half of the balance past the buffer, rounded down
+        let results = plan_swaps(
+            &client,
+            &rpc_with_balance(Some(DEFAULT_SOL_BUFFER_LAMPORTS + 1_000_000_001)),
+            vec![plan(
+                USDC_MINT,
+                PlanAmount::BalancePercentage(Decimal::new(50, 0)),
+            )],
+            &PlanOptions::default(),
+        )
+        .await;
+        assert_eq!(results[0].as_ref().unwrap().amount, 500_000_000);
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests.iter().any(|request| request
+            .url
+            .query()
+            .is_some_and(|query| query.contains("amount=500000000"))));
+    }
+
+    #[tokio::test]
+    async fn balance_within_the_buffer_is_insufficient() {
+        let server = MockServer::start().await;
+        let client = JupiterSwapApiClient::new(server.uri(), None);
+        let results = plan_swaps(
+            &client,
+            &rpc_with_balance(Some(DEFAULT_SOL_BUFFER_LAMPORTS)),
+            vec![plan(
+                USDC_MINT,
+                PlanAmount::BalancePercentage(Decimal::ONE_HUNDRED),
+            )],
+            &PlanOptions::default(),
+        )
+        .await;
+        assert!(matches!(
+            results[0],
+            Err(PlanError::InsufficientBalance {
+                balance: DEFAULT_SOL_BUFFER_LAMPORTS
+            })
+        ));
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn percentage_rounds_down() {
+        assert_eq!(percentage_of(999, Decimal::new(50, 0)), Some(499));
+        assert_eq!(
+            percentage_of(u64::MAX, Decimal::ONE_HUNDRED),
+            Some(u64::MAX)
+        );
+        assert_eq!(percentage_of(1, Decimal::new(1, 1)), Some(0));
+    }
+}
@@ -0,0 +1,156 @@
+//! Price API, USD prices of tokens
+//!
+//! Jupiter does not expose a streaming price endpoint,
+//! [`JupiterSwapApiClient::price_stream`] polls the price endpoint and only yields changed prices.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use futures::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use solana_pubkey::Pubkey;
+
+use crate::{metrics::Endpoint, ClientError, JupiterSwapApiClient};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenPrice {
+    pub usd_price: f64,
+    /// Block the price was computed at
+    pub block_id: Option<u64>,
+    pub decimals: u8,
+    pub price_change_24h: Option<f64>,
+}
+
+/// A changed price yielded by [`JupiterSwapApiClient::price_stream`]
+#[derive(Debug, PartialEq, Clone)]
+pub struct PriceUpdate {
+    pub mint: Pubkey,
+    pub price: TokenPrice,
+}
+
+struct PriceStreamState {
+    client: JupiterSwapApiClient,
+    mints: Vec<Pubkey>,
+    interval: Duration,
+    last_prices: HashMap<Pubkey, f64>,
+    pending_updates: VecDeque<PriceUpdate>,
+    polled: bool,
+}
+
+impl JupiterSwapApiClient {
+    /// GET /price, keyed by mint, mints without a reliable price are omitted
+    pub async fn price(
+        &self,
+        mints: &[Pubkey],
+    ) -> Result<HashMap<String, TokenPrice>, ClientError> {
+        let ids = mints
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        self.get(Endpoint::Price, &self.price_base_path, &[("ids", ids)])
+            .await
+    }
+
+    /// Poll the prices of `mints` every `interval`, yielding a [`PriceUpdate`] whenever the price of a mint changes.
+    /// The first poll yields every priced mint, a failed poll yields the error and polling carries on.
+    pub fn price_stream(
+        &self,
+        mints: Vec<Pubkey>,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<PriceUpdate, ClientError>> {
+        let state = PriceStreamState {
+            client: self.clone(),
+            mints,
+            interval,
+            last_prices: HashMap::new(),
+            pending_updates: VecDeque::new(),
+            polled: false,
+        };
+        stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(update) = state.pending_updates.pop_front() {
+                    return Some((Ok(update), state));
+                }
+                if state.polled {
+                    tokio::time::sleep(state.interval).await;
+                }
+                state.polled = true;
+
+                let mut prices = match state.client.price(&state.mints).await {
+                    Ok(prices) => prices,
+                    Err(e) => return Some((Err(e), state)),
+                };
+                for mint in &state.mints {
+                    let Some(price) = prices.remove(&mint.to_string()) else {
+                        continue;
+                    };
+                    if state.last_prices.insert(*mint, price.usd_price) != Some(price.usd_price) {
+                        state
+                            .pending_updates
+                            .push_back(PriceUpdate { mint: *mint, price });
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::mints::{NATIVE_MINT, USDC_MINT};
+
+    fn prices(sol: f64, usdc: f64) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({
+            NATIVE_MINT.to_string(): { "usdPrice": sol, "blockId": 1, "decimals": 9, "priceChange24h": null },
+            USDC_MINT.to_string(): { "usdPrice": usdc, "blockId": 1, "decimals": 6, "priceChange24h": null }
+        }))
+    }
+
+    #[tokio::test]
+    async fn price_stream_only_yields_changed_prices() {
+        let server = MockServer::start().await;
+        let ids = format!("{NATIVE_MINT},{USDC_MINT}");
+        for response in [prices(150.0, 1.0), ResponseTemplate::new(500)] {
+            Mock::given(method("GET"))
+                .and(query_param("ids", ids.as_str()))
+                .respond_with(response)
+                .up_to_n_times(1)
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(query_param("ids", ids.as_str()))
+            .respond_with(prices(150.0, 0.9999))
+            .mount(&server)
+            .await;
+        let mut client = JupiterSwapApiClient::new(server.uri(), None);
+        client.price_base_path = server.uri();
+
+        let updates: Vec<_> = client
+            .price_stream(vec![NATIVE_MINT, USDC_MINT], Duration::from_millis(1))
+            .take(4)
+            .map(|update| update.map(|update| (update.mint, update.price.usd_price)))
+            .collect()
+            .await;
+        assert_eq!(updates[0].as_ref().unwrap(), &(NATIVE_MINT, 150.0));
+        assert_eq!(updates[1].as_ref().unwrap(), &(USDC_MINT, 1.0));
+        assert_eq!(
+            updates[2].as_ref().unwrap_err().status(),
+            Some(StatusCode::INTERNAL_SERVER_ERROR)
+        );
+        assert_eq!(updates[3].as_ref().unwrap(), &(USDC_MINT, 0.9999));
+    }
+}
@@ -0,0 +1,145 @@
+//! Compute unit price estimation from recent prioritization fees, requires the `rpc` feature
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_pubkey::Pubkey;
+
+use crate::{
+    transaction_config::{ComputeUnitPriceMicroLamports, TransactionConfig},
+    ClientError, ErrorKind,
+};
+
+/// Maximum number of accounts accepted by `getRecentPrioritizationFees`
+pub const MAX_PRIORITIZATION_FEE_ACCOUNTS: usize = 128;
+
+/// Compute unit price in micro lamports paid by `percentile` (0 to 100) of the recent slots writing to `accounts`,
+/// e.g. [`SwapInstructionsResponse::writable_accounts`](crate::swap::SwapInstructionsResponse::writable_accounts).
+/// Never below `floor_micro_lamports`, which is also returned when there is no fee history.
+pub async fn estimate_priority_fee(
+    rpc: &RpcClient,
+    accounts: &[Pubkey],
+    percentile: u8,
+    floor_micro_lamports: u64,
+) -> Result<u64, ClientError> {
+    let accounts = &accounts[..accounts.len().min(MAX_PRIORITIZATION_FEE_ACCOUNTS)];
+    let fees = rpc
+        .get_recent_prioritization_fees(accounts)
+        .await
+        .map_err(|e| ErrorKind::RpcError(Box::new(e)))?
+        .into_iter()
+        .map(|fee| fee.prioritization_fee)
+        .collect::<Vec<_>>();
+    Ok(fee_percentile(fees, percentile)
+        .unwrap_or_default()
+        .max(floor_micro_lamports))
+}
+
+/// Nearest rank percentile, `None` when there are no fees
+fn fee_percentile(mut fees: Vec<u64>, percentile: u8) -> Option<u64> {
+    if fees.is_empty() {
+        return None;
+    }
+    fees.sort_unstable();
+    let percentile = usize::from(percentile.min(100));
+    let rank = (percentile * fees.len()).div_ceil(100).max(1);
+    fees.get(rank - 1).copied()
+}
+
+impl TransactionConfig {
+    /// Set the compute unit price from [`estimate_priority_fee`], clearing any prioritization fee
+    pub async fn with_estimated_priority_fee(
+        mut self,
+        rpc: &RpcClient,
+        accounts: &[Pubkey],
+        percentile: u8,
+        floor_micro_lamports: u64,
+    ) -> Result<Self, ClientError> {
+        let micro_lamports =
+            estimate_priority_fee(rpc, accounts, percentile, floor_micro_lamports).await?;
+        self.compute_unit_price_micro_lamports =
+            Some(ComputeUnitPriceMicroLamports::MicroLamports(micro_lamports));
+        self.prioritization_fee_lamports = None;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::{json, Value};
+    use solana_client::rpc_request::RpcRequest;
+
+    use super::*;
+
+    fn rpc_with_fees(fees: &[u64]) -> RpcClient {
+        let fees: Vec<Value> = fees
+            .iter()
+            .enumerate()
+            .map(|(slot, fee)| json!({ "slot": slot, "prioritizationFee": fee }))
+            .collect();
+        let mocks = HashMap::from([(RpcRequest::GetRecentPrioritizationFees, json!(fees))]);
+        RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks)
+    }
+
+    #[test]
+    fn percentile_uses_the_nearest_rank() {
+        let fees = vec![50, 10, 40, 20, 30];
+        assert_eq!(fee_percentile(fees.clone(), 0), Some(10));
+        assert_eq!(fee_percentile(fees.clone(), 20), Some(10));
+        assert_eq!(fee_percentile(fees.clone(), 21), Some(20));
+        assert_eq!(fee_percentile(fees.clone(), 50), Some(30));
+        assert_eq!(fee_percentile(fees.clone(), 100), Some(50));
+        // Capped to the maximum
+        assert_eq!(fee_percentile(fees, u8::MAX), Some(50));
+        assert_eq!(fee_percentile(vec![7], 75), Some(7));
+        assert_eq!(fee_percentile(Vec::new(), 50), None);
+    }
+
+    #[tokio::test]
+    async fn estimate_uses_the_recent_fees() {
+        let rpc = rpc_with_fees(&[0, 1_000, 5_000, 20_000]);
+        let accounts = [Pubkey::new_unique()];
+        assert_eq!(
+            estimate_priority_fee(&rpc, &accounts, 75, 0).await.unwrap(),
+            5_000
+        );
+        assert_eq!(
+            estimate_priority_fee(&rpc, &accounts, 75, 10_000)
+                .await
+                .unwrap(),
+            10_000
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_fee_history_falls_back_to_the_floor() {
+        let rpc = rpc_with_fees(&[]);
+        assert_eq!(
+            estimate_priority_fee(&rpc, &[Pubkey::new_unique()], 90, 1_234)
+                .await
+                .unwrap(),
+            1_234
+        );
+    }
+
+    #[tokio::test]
+    async fn estimated_fee_replaces_the_prioritization_fee() {
+        let rpc = rpc_with_fees(&[3_000]);
+        let config = TransactionConfig::for_jito(10_000)
+            .with_estimated_priority_fee(&rpc, &[Pubkey::new_unique()], 50, 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            config.compute_unit_price_micro_lamports,
+            Some(ComputeUnitPriceMicroLamports::MicroLamports(3_000))
+        );
+        assert_eq!(config.prioritization_fee_lamports, None);
+    }
+
+    #[tokio::test]
+    async fn rpc_failures_are_reported() {
+        let rpc = RpcClient::new_mock("fails".to_string());
+        let error = estimate_priority_fee(&rpc, &[], 50, 0).await.unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::RpcError(_)));
+    }
+}
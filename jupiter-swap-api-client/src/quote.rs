@@ -1,121 +1,352 @@
 //! Quote data structure for quoting and quote response
-//!
 
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
+use crate::amount::{self, AmountError};
+use crate::mints::NATIVE_MINT;
 use crate::route_plan_with_metadata::RoutePlanWithMetadata;
-use crate::serde_helpers::field_as_string;
-use anyhow::{anyhow, Error};
+/// Swap information of each swap of the route plan, see [`RoutePlanStep`](crate::route_plan_with_metadata::RoutePlanStep)
+pub use crate::route_plan_with_metadata::SwapInfo;
+use crate::serde_helpers::{field_as_string, option_field_as_string};
+use crate::ExpectedFields;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use solana_sdk::pubkey::Pubkey;
+use solana_pubkey::Pubkey;
+use thiserror::Error;
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
-#[serde(rename_all = "camelCase")]
-/// Swap information of each Swap occurred in the route paths
-pub struct SwapInfo {
-    #[serde(with = "field_as_string")]
-    pub amm_key: Pubkey,
-    pub label: String,
-    #[serde(with = "field_as_string")]
-    pub input_mint: Pubkey,
-    #[serde(with = "field_as_string")]
-    pub output_mint: Pubkey,
-    /// An estimation of the input amount into the AMM
-    #[serde(with = "field_as_string")]
-    pub in_amount: u64,
-    /// An estimation of the output amount into the AMM
-    #[serde(with = "field_as_string")]
-    pub out_amount: u64,
-    #[serde(with = "field_as_string")]
-    pub fee_amount: u64,
-    #[serde(with = "field_as_string")]
-    pub fee_mint: Pubkey,
-}
-
-#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Debug)]
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Copy, Clone, Debug)]
 pub enum SwapMode {
     #[default]
     ExactIn,
     ExactOut,
 }
 
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+#[error("{0} is not a valid SwapMode, expected ExactIn or ExactOut")]
+pub struct ParseSwapModeError(pub String);
+
+/// Case-insensitive
 impl FromStr for SwapMode {
-    type Err = Error;
+    type Err = ParseSwapModeError;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        match s {
-            "ExactIn" => Ok(Self::ExactIn),
-            "ExactOut" => Ok(Self::ExactOut),
-            _ => Err(anyhow!("{} is not a valid SwapMode", s)),
+        if s.eq_ignore_ascii_case("ExactIn") {
+            Ok(Self::ExactIn)
+        } else if s.eq_ignore_ascii_case("ExactOut") {
+            Ok(Self::ExactOut)
+        } else {
+            Err(ParseSwapModeError(s.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for SwapMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ExactIn => f.write_str("ExactIn"),
+            Self::ExactOut => f.write_str("ExactOut"),
         }
     }
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Debug, Error, PartialEq, Eq, Copy, Clone)]
+#[error("Quote was computed as {actual} but {expected} was requested")]
+pub struct SwapModeMismatch {
+    pub expected: SwapMode,
+    pub actual: SwapMode,
+}
+
+/// Reasons for two quotes not to be comparable
+#[derive(Debug, Error, PartialEq, Eq, Copy, Clone)]
+pub enum QuoteCompareError {
+    #[error("Quotes were computed as {0} and {1}")]
+    DifferentSwapMode(SwapMode, SwapMode),
+    #[error("Quotes swap different mints")]
+    DifferentMints,
+    #[error("Quotes are for different amounts, {0} and {1}")]
+    DifferentAmount(u64, u64),
+}
+
+/// Best of `quotes` according to [`QuoteResponse::compare`],
+/// quotes which are not comparable with the first one are skipped
+pub fn best_quote<'a>(
+    quotes: impl IntoIterator<Item = &'a QuoteResponse>,
+) -> Option<&'a QuoteResponse> {
+    let mut quotes = quotes.into_iter();
+    let mut best = quotes.next()?;
+    for quote in quotes {
+        if quote.better_than(best) == Ok(true) {
+            best = quote;
+        }
+    }
+    Some(best)
+}
+
+/// Well known reasons for Jupiter to reject a quote request
+#[derive(Debug, Error, PartialEq, Eq, Copy, Clone)]
+pub enum QuoteError {
+    #[error("No route found")]
+    NoRoute,
+    #[error("Token not tradable")]
+    TokenNotTradable,
+    #[error("Route plan does not consume all the amount")]
+    RoutePlanDoesNotConsumeAllTheAmount,
+    #[error("Circular arbitrage is disabled")]
+    CircularArbitrageIsDisabled,
+    #[error("Cannot compute other amount threshold")]
+    CannotComputeOtherAmountThreshold,
+}
+
+impl QuoteError {
+    /// Map a Jupiter `errorCode`, `None` for codes without a dedicated variant
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "COULD_NOT_FIND_ANY_ROUTE" | "NO_ROUTES_FOUND" => Some(Self::NoRoute),
+            "TOKEN_NOT_TRADABLE" => Some(Self::TokenNotTradable),
+            "ROUTE_PLAN_DOES_NOT_CONSUME_ALL_THE_AMOUNT" => {
+                Some(Self::RoutePlanDoesNotConsumeAllTheAmount)
+            }
+            "CIRCULAR_ARBITRAGE_IS_DISABLED" => Some(Self::CircularArbitrageIsDisabled),
+            "CANNOT_COMPUTE_OTHER_AMOUNT_THRESHOLD" => {
+                Some(Self::CannotComputeOtherAmountThreshold)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuoteErrorResponse {
+    #[serde(default)]
+    error: String,
+    error_code: Option<String>,
+}
+
+/// Parse the body of a failed quote into a well known error and the server message
+pub(crate) fn parse_quote_error(body: &str) -> Option<(QuoteError, String)> {
+    let response = serde_json::from_str::<QuoteErrorResponse>(body).ok()?;
+    let error = QuoteError::from_code(response.error_code.as_deref()?)?;
+    Some((error, response.error))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct ComputeUnitScore {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub max_penalty_bps: Option<f64>,
 }
 
-#[derive(Serialize, Debug, Default, Clone)]
-#[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
 pub struct QuoteRequest {
     #[serde(with = "field_as_string")]
     pub input_mint: Pubkey,
     #[serde(with = "field_as_string")]
     pub output_mint: Pubkey,
     /// The amount to swap, have to factor in the token decimals.
+    /// Sent as a decimal string, a zero amount is rejected before sending.
     #[serde(with = "field_as_string")]
     pub amount: u64,
     /// (ExactIn or ExactOut) Defaults to ExactIn.
     /// ExactOut is for supporting use cases where you need an exact token amount, like payments.
-    /// In this case the slippage is on the input token.
+    /// In this case the slippage is on the input token, `amount` is the output amount
+    /// and the platform fee is taken from the input mint.
+    /// Not all DEXes support ExactOut, [`JupiterSwapApiClient::quote`](crate::JupiterSwapApiClient::quote)
+    /// fails with [`ErrorKind::SwapModeMismatch`](crate::ErrorKind::SwapModeMismatch) when the quote
+    /// comes back with another swap mode, see also [`QuoteResponse::assert_mode`].
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub swap_mode: Option<SwapMode>,
-    /// Allowed slippage in basis points
+    /// Allowed slippage in basis points, leave to 0 when enabling `auto_slippage`.
+    /// 0 is not sent, the API then applies its default of 50.
     pub slippage_bps: u16,
     /// Default is false.
     /// By setting this to true, our API will suggest smart slippage info that you can use.
     /// slippageBps is what we suggest you to use. Additionally, you should check out max_auto_slippage_bps and auto_slippage_collision_usd_value.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub auto_slippage: Option<bool>,
     /// The max amount of slippage in basis points that you are willing to accept for auto slippage.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub max_auto_slippage_bps: Option<u16>,
     pub compute_auto_slippage: bool,
     /// The max amount of USD value that you are willing to accept for auto slippage.
-    pub auto_slippage_collision_usd_value: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_slippage_collision_usd_value: Option<u64>,
     /// Quote with a greater amount to find the route to minimize slippage
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub minimize_slippage: Option<bool>,
     /// Platform fee in basis points
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub platform_fee_bps: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dexes: Option<Dexes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub excluded_dexes: Option<Dexes>,
     /// Quote only direct routes
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub only_direct_routes: Option<bool>,
     /// Quote fit into legacy transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub as_legacy_transaction: Option<bool>,
     /// Restrict intermediate tokens to a top token set that has stable liquidity.
     /// This will help to ease potential high slippage error rate when swapping with minimal impact on pricing.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub restrict_intermediate_tokens: Option<bool>,
     /// Find a route given a maximum number of accounts involved,
     /// this might dangerously limit routing ending up giving a bad price.
     /// The max is an estimation and not the exact count
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub max_accounts: Option<usize>,
     /// Quote type to be used for routing, switches the algorithm
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub quote_type: Option<String>,
-    /// Extra args which are quote type specific to allow controlling settings from the top level
-    pub quote_args: Option<HashMap<String, String>>,
+    /// Extra args which are quote type specific to allow controlling settings from the top level,
+    /// see [`QuoteExtraArgs`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote_args: Option<QuoteExtraArgs>,
     /// enable only full liquid markets as intermediate tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub prefer_liquid_dexes: Option<bool>,
     /// Use the compute unit score to pick a route
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub compute_unit_score: Option<ComputeUnitScore>,
     /// Routing constraints
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub routing_constraints: Option<String>,
     /// Token category based intermediates token
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub token_category_based_intermediate_tokens: Option<bool>,
 }
 
+/// Extra /quote query parameters, mostly understood by the self-hosted API
+///
+/// Known flags are typed, anything else goes in `custom`.
+/// When a `custom` key is also set by a typed field, the typed field wins.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct QuoteExtraArgs {
+    /// Only route through these markets
+    #[serde(deserialize_with = "deserialize_pubkey_list")]
+    pub markets: Option<Vec<Pubkey>>,
+    /// Never route through these markets
+    #[serde(deserialize_with = "deserialize_pubkey_list")]
+    pub excluded_markets: Option<Vec<Pubkey>>,
+    #[serde(flatten)]
+    pub custom: HashMap<String, String>,
+}
+
+impl QuoteExtraArgs {
+    /// Query parameters in the order they are sent, typed ones first
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        let typed = [
+            ("markets", &self.markets),
+            ("excludedMarkets", &self.excluded_markets),
+        ];
+        let mut pairs: Vec<(String, String)> = typed
+            .into_iter()
+            .filter_map(|(key, markets)| {
+                let markets = markets.as_ref()?;
+                let value = markets
+                    .iter()
+                    .map(Pubkey::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                Some((key.to_string(), value))
+            })
+            .collect();
+
+        let mut custom: Vec<_> = self
+            .custom
+            .iter()
+            .filter(|(key, _)| !pairs.iter().any(|(typed_key, _)| typed_key == *key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        custom.sort();
+        pairs.extend(custom);
+        pairs
+    }
+}
+
+impl From<HashMap<String, String>> for QuoteExtraArgs {
+    fn from(custom: HashMap<String, String>) -> Self {
+        Self {
+            custom,
+            ..Default::default()
+        }
+    }
+}
+
+impl Serialize for QuoteExtraArgs {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let pairs = self.query_pairs();
+        let mut map = serializer.serialize_map(Some(pairs.len()))?;
+        for (key, value) in &pairs {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+fn deserialize_pubkey_list<'de, D>(deserializer: D) -> Result<Option<Vec<Pubkey>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let Some(list) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    list.split(',')
+        .filter(|pubkey| !pubkey.is_empty())
+        .map(|pubkey| Pubkey::from_str(pubkey).map_err(serde::de::Error::custom))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// Requests rejected before being sent
+#[derive(Debug, Error, PartialEq, Eq, Copy, Clone)]
+pub enum QuoteRequestError {
+    #[error("amount is 0, there is nothing to quote")]
+    ZeroAmount,
+    #[error("slippage_bps is {0} while auto_slippage is enabled, cap the auto slippage with max_auto_slippage_bps instead")]
+    SlippageWithAutoSlippage(u16),
+}
+
+impl QuoteRequest {
+    /// Native SOL is swapped from or to, see [`QuoteResponse::involves_native_sol`]
+    pub fn involves_native_sol(&self) -> bool {
+        self.input_mint == NATIVE_MINT || self.output_mint == NATIVE_MINT
+    }
+
+    /// Check the request for conflicting parameters, also done before sending it
+    pub fn validate(&self) -> Result<(), QuoteRequestError> {
+        if self.amount == 0 {
+            return Err(QuoteRequestError::ZeroAmount);
+        }
+        if self.auto_slippage == Some(true) && self.slippage_bps != 0 {
+            return Err(QuoteRequestError::SlippageWithAutoSlippage(
+                self.slippage_bps,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Set `amount` from a UI amount, rounding down to the token decimals
+    pub fn amount_ui(mut self, ui_amount: Decimal, decimals: u8) -> Result<Self, AmountError> {
+        self.amount = amount::ui_to_raw(ui_amount, decimals)?;
+        Ok(self)
+    }
+}
+
 // Essentially the same as QuoteRequest, but without the extra args
-// as we pass the extra args separately
-#[derive(Serialize, Debug, Default, Clone)]
+// as we pass the extra args separately.
+// Unset and default fields are omitted as the API treats the presence of some flags as intent
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct InternalQuoteRequest {
     #[serde(with = "field_as_string")]
@@ -128,38 +359,54 @@ pub struct InternalQuoteRequest {
     /// (ExactIn or ExactOut) Defaults to ExactIn.
     /// ExactOut is for supporting use cases where you need an exact token amount, like payments.
     /// In this case the slippage is on the input token.
+    #[serde(skip_serializing_if = "is_none_or_exact_in")]
     pub swap_mode: Option<SwapMode>,
-    /// Allowed slippage in basis points
+    /// Allowed slippage in basis points, omitted when 0 for the API to apply its default
+    #[serde(skip_serializing_if = "is_zero")]
     pub slippage_bps: u16,
     /// Default is false.
     /// By setting this to true, our API will suggest smart slippage info that you can use.
     /// slippageBps is what we suggest you to use. Additionally, you should check out max_auto_slippage_bps and auto_slippage_collision_usd_value.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub auto_slippage: Option<bool>,
     /// The max amount of slippage in basis points that you are willing to accept for auto slippage.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub max_auto_slippage_bps: Option<u16>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
     pub compute_auto_slippage: bool,
     /// The max amount of USD value that you are willing to accept for auto slippage.
-    pub auto_slippage_collision_usd_value: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_slippage_collision_usd_value: Option<u64>,
     /// Quote with a greater amount to find the route to minimize slippage
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub minimize_slippage: Option<bool>,
     /// Platform fee in basis points
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub platform_fee_bps: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dexes: Option<Dexes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub excluded_dexes: Option<Dexes>,
     /// Quote only direct routes
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub only_direct_routes: Option<bool>,
     /// Quote fit into legacy transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub as_legacy_transaction: Option<bool>,
     /// Restrict intermediate tokens to a top token set that has stable liquidity.
     /// This will help to ease potential high slippage error rate when swapping with minimal impact on pricing.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub restrict_intermediate_tokens: Option<bool>,
     /// Find a route given a maximum number of accounts involved,
     /// this might dangerously limit routing ending up giving a bad price.
     /// The max is an estimation and not the exact count
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub max_accounts: Option<usize>,
     // Quote type to be used for routing, switches the algorithm
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub quote_type: Option<String>,
     // enable only full liquid markets as intermediate tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub prefer_liquid_dexes: Option<bool>,
 }
 
@@ -177,8 +424,8 @@ impl From<QuoteRequest> for InternalQuoteRequest {
             auto_slippage_collision_usd_value: request.auto_slippage_collision_usd_value,
             minimize_slippage: request.minimize_slippage,
             platform_fee_bps: request.platform_fee_bps,
-            dexes: request.dexes,
-            excluded_dexes: request.excluded_dexes,
+            dexes: request.dexes.as_deref().map(normalize_dexes),
+            excluded_dexes: request.excluded_dexes.as_deref().map(normalize_dexes),
             only_direct_routes: request.only_direct_routes,
             as_legacy_transaction: request.as_legacy_transaction,
             restrict_intermediate_tokens: request.restrict_intermediate_tokens,
@@ -189,18 +436,77 @@ impl From<QuoteRequest> for InternalQuoteRequest {
     }
 }
 
+/// ExactIn is the API default so it is only sent for ExactOut
+fn is_none_or_exact_in(swap_mode: &Option<SwapMode>) -> bool {
+    matches!(swap_mode, None | Some(SwapMode::ExactIn))
+}
+
+fn is_zero(slippage_bps: &u16) -> bool {
+    *slippage_bps == 0
+}
+
 /// Comma delimited list of dex labels
 type Dexes = String;
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+/// How lists such as `dexes` are sent in the query string,
+/// see [`JupiterSwapApiClientBuilder::multi_value_style`](crate::builder::JupiterSwapApiClientBuilder::multi_value_style)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MultiValueStyle {
+    /// `dexes=A,B`, expected by the public API
+    #[default]
+    Comma,
+    /// `dexes=A&dexes=B`, for self-hosted versions only parsing this form
+    Repeated,
+}
+
+impl InternalQuoteRequest {
+    /// Take out `dexes` and `excluded_dexes` as one query pair per label
+    pub(crate) fn take_repeated_dexes(&mut self) -> Vec<(&'static str, String)> {
+        [
+            ("dexes", self.dexes.take()),
+            ("excludedDexes", self.excluded_dexes.take()),
+        ]
+        .into_iter()
+        .flat_map(|(key, dexes)| {
+            dexes
+                .unwrap_or_default()
+                .split(',')
+                .filter(|dex| !dex.is_empty())
+                .map(|dex| (key, dex.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+    }
+}
+
+/// Sort and dedup the labels so equal requests produce identical query strings
+fn normalize_dexes(dexes: &str) -> Dexes {
+    dexes
+        .split(',')
+        .map(str::trim)
+        .filter(|dex| !dex.is_empty())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PlatformFee {
     #[serde(with = "field_as_string")]
     pub amount: u64,
     pub fee_bps: u8,
+    /// Mint the fee is taken in, when reported by the server
+    #[serde(
+        default,
+        with = "option_field_as_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub fee_mint: Option<Pubkey>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct QuoteResponse {
     #[serde(with = "field_as_string")]
@@ -211,6 +517,8 @@ pub struct QuoteResponse {
     pub output_mint: Pubkey,
     #[serde(with = "field_as_string")]
     pub out_amount: u64,
+    /// Minimum output amount for ExactIn or maximum input amount for ExactOut once `slippage_bps` is applied,
+    /// the guarantee enforced by the swap, see [`Self::min_output_amount`] and [`Self::max_input_amount`].
     /// Not used by build transaction
     #[serde(with = "field_as_string")]
     pub other_amount_threshold: u64,
@@ -220,11 +528,1140 @@ pub struct QuoteResponse {
     pub computed_auto_slippage: Option<u16>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub uses_quote_minimizing_slippage: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub platform_fee: Option<PlatformFee>,
     pub price_impact_pct: Decimal,
     pub route_plan: RoutePlanWithMetadata,
-    #[serde(default)]
-    pub context_slot: u64,
-    #[serde(default)]
-    pub time_taken: f64,
+    /// Slot the market state was read at, `None` when not reported by the server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_slot: Option<u64>,
+    /// Seconds taken to compute the quote
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_taken: Option<f64>,
+    /// When the client received the response, `None` for quotes which were not fetched by the client
+    #[serde(skip)]
+    pub received_at: Option<Instant>,
+    /// Request ids of the response, see [`ClientError::request_ids`](crate::ClientError::request_ids)
+    #[serde(skip)]
+    pub request_ids: Vec<String>,
+    /// Fields the response is missing or does not model, only collected with
+    /// [`JupiterSwapApiClientBuilder::warn_on_field_drift`](crate::builder::JupiterSwapApiClientBuilder::warn_on_field_drift)
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+impl ExpectedFields for QuoteResponse {
+    const EXPECTED_FIELDS: &'static [&'static str] = &["contextSlot", "timeTaken"];
+}
+
+/// All the costs of a quote
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FeeSummary {
+    /// Sum of the LP fees of every hop, `None` when the fees are taken in different mints
+    pub total_lp_fee: Option<u64>,
+    /// LP fees summed per fee mint
+    pub total_lp_fee_mint_breakdown: BTreeMap<Pubkey, u64>,
+    pub platform_fee: Option<PlatformFee>,
+    pub price_impact_pct: Decimal,
+}
+
+/// How involved the route of a quote is, simpler routes are more likely to land
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RouteComplexity {
+    /// Distinct input and output mint pairs swapped, the steps splitting a pair across AMMs counting once
+    pub hop_count: usize,
+    /// Distinct AMM labels of the route plan
+    pub distinct_amms: usize,
+    /// Single hop from the input mint to the output mint, possibly split across AMMs
+    pub uses_direct_route: bool,
+}
+
+/// `other_amount_threshold` qualified by the swap mode it applies to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Threshold {
+    /// ExactIn, the swap fails if the user would receive less
+    MinimumOut(u64),
+    /// ExactOut, the swap fails if the user would spend more
+    MaximumIn(u64),
+}
+
+impl Threshold {
+    pub fn amount(&self) -> u64 {
+        match self {
+            Self::MinimumOut(amount) | Self::MaximumIn(amount) => *amount,
+        }
+    }
+}
+
+const BPS_DENOMINATOR: u128 = 10_000;
+
+impl QuoteResponse {
+    /// Native SOL is swapped from or to, so the swap may wrap or unwrap SOL depending on `wrap_and_unwrap_sol`
+    pub fn involves_native_sol(&self) -> bool {
+        self.input_mint == NATIVE_MINT || self.output_mint == NATIVE_MINT
+    }
+
+    /// Aggregate the LP fees of every hop in the route plan, the platform fee and the price impact
+    pub fn fee_summary(&self) -> FeeSummary {
+        let mut total_lp_fee_mint_breakdown = BTreeMap::new();
+        for step in &self.route_plan {
+            let fee = total_lp_fee_mint_breakdown
+                .entry(step.swap_info.fee_mint.pubkey())
+                .or_insert(0u64);
+            *fee = fee.saturating_add(step.swap_info.fee_amount);
+        }
+        let total_lp_fee = match total_lp_fee_mint_breakdown.len() {
+            0 => Some(0),
+            1 => total_lp_fee_mint_breakdown.values().next().copied(),
+            _ => None,
+        };
+        FeeSummary {
+            total_lp_fee,
+            total_lp_fee_mint_breakdown,
+            platform_fee: self.platform_fee.clone(),
+            price_impact_pct: self.price_impact_pct,
+        }
+    }
+
+    /// Hop and AMM counts of the route plan, see [`RouteComplexity`]
+    pub fn route_complexity(&self) -> RouteComplexity {
+        let hops: BTreeSet<_> = self
+            .route_plan
+            .iter()
+            .map(|step| (step.swap_info.input_mint, step.swap_info.output_mint))
+            .collect();
+        let amms: BTreeSet<_> = self
+            .route_plan
+            .iter()
+            .map(|step| step.swap_info.label.as_str())
+            .collect();
+        RouteComplexity {
+            hop_count: hops.len(),
+            distinct_amms: amms.len(),
+            uses_direct_route: hops.len() == 1
+                && hops.contains(&(self.input_mint, self.output_mint)),
+        }
+    }
+
+    /// `in_amount` as a UI amount given the input mint decimals
+    pub fn in_amount_ui(&self, decimals: u8) -> Decimal {
+        amount::raw_to_ui(self.in_amount, decimals)
+    }
+
+    /// `out_amount` as a UI amount given the output mint decimals
+    pub fn out_amount_ui(&self, decimals: u8) -> Decimal {
+        amount::raw_to_ui(self.out_amount, decimals)
+    }
+
+    /// Time elapsed since the client received the quote
+    pub fn age(&self) -> Option<Duration> {
+        self.received_at.map(|received_at| received_at.elapsed())
+    }
+
+    /// Whether the quote was computed against a later slot than `other`.
+    /// A quote reporting its context slot is fresher than one which does not,
+    /// when neither does the one received last is fresher.
+    pub fn is_fresher_than(&self, other: &QuoteResponse) -> bool {
+        match (self.context_slot, other.context_slot) {
+            (Some(slot), Some(other_slot)) => slot > other_slot,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => matches!(
+                (self.received_at, other.received_at),
+                (Some(received_at), Some(other_received_at)) if received_at > other_received_at
+            ),
+        }
+    }
+
+    /// `out_amount` minus the platform fee when it is taken from the output mint,
+    /// which is the case for ExactIn unless the server reports another fee mint
+    pub fn net_out_amount(&self) -> u64 {
+        match &self.platform_fee {
+            Some(platform_fee) if self.is_platform_fee_on_output(platform_fee) => {
+                self.out_amount.saturating_sub(platform_fee.amount)
+            }
+            _ => self.out_amount,
+        }
+    }
+
+    fn is_platform_fee_on_output(&self, platform_fee: &PlatformFee) -> bool {
+        match platform_fee.fee_mint {
+            Some(fee_mint) => fee_mint == self.output_mint,
+            None => self.swap_mode == SwapMode::ExactIn,
+        }
+    }
+
+    /// Most the user spends: `other_amount_threshold` for ExactOut, `in_amount` for ExactIn
+    pub fn max_input_amount(&self) -> u64 {
+        match self.swap_mode {
+            SwapMode::ExactIn => self.in_amount,
+            SwapMode::ExactOut => self.other_amount_threshold,
+        }
+    }
+
+    /// Least the user receives: `other_amount_threshold` for ExactIn, `out_amount` for ExactOut
+    pub fn min_output_amount(&self) -> u64 {
+        match self.swap_mode {
+            SwapMode::ExactIn => self.other_amount_threshold,
+            SwapMode::ExactOut => self.out_amount,
+        }
+    }
+
+    /// The "you will receive at least" amount to display, same as [`Self::min_output_amount`]
+    pub fn minimum_received(&self) -> u64 {
+        self.min_output_amount()
+    }
+
+    /// The "you will spend at most" amount to display, same as [`Self::max_input_amount`]
+    pub fn maximum_spent(&self) -> u64 {
+        self.max_input_amount()
+    }
+
+    /// Maximum input amount of an ExactOut quote, `None` for ExactIn where the input amount is exact
+    pub fn max_in_amount(&self) -> Option<u64> {
+        match self.swap_mode {
+            SwapMode::ExactIn => None,
+            SwapMode::ExactOut => Some(self.other_amount_threshold),
+        }
+    }
+
+    /// Whether a `balance` of input mint covers the most the swap may spend
+    pub fn fits_balance(&self, balance: u64) -> bool {
+        self.max_input_amount() <= balance
+    }
+
+    /// `other_amount_threshold` as a minimum output for ExactIn or a maximum input for ExactOut
+    pub fn threshold(&self) -> Threshold {
+        match self.swap_mode {
+            SwapMode::ExactIn => Threshold::MinimumOut(self.other_amount_threshold),
+            SwapMode::ExactOut => Threshold::MaximumIn(self.other_amount_threshold),
+        }
+    }
+
+    /// Threshold the server computes for `slippage_bps`, the minimum output is rounded down
+    /// and the maximum input rounded up so the slippage is never exceeded
+    pub fn threshold_with_slippage(&self, slippage_bps: u16) -> Threshold {
+        let slippage_bps = u128::from(slippage_bps);
+        match self.swap_mode {
+            SwapMode::ExactIn => {
+                let min_out = u128::from(self.out_amount)
+                    * BPS_DENOMINATOR.saturating_sub(slippage_bps)
+                    / BPS_DENOMINATOR;
+                Threshold::MinimumOut(min_out as u64)
+            }
+            SwapMode::ExactOut => {
+                let max_in = (u128::from(self.in_amount) * (BPS_DENOMINATOR + slippage_bps))
+                    .div_ceil(BPS_DENOMINATOR);
+                Threshold::MaximumIn(u64::try_from(max_in).unwrap_or(u64::MAX))
+            }
+        }
+    }
+
+    /// Order two quotes of the same swap, greater is better: a higher output for ExactIn or a lower input for ExactOut,
+    /// then fewer route plan steps, then a lower price impact
+    pub fn compare(&self, other: &QuoteResponse) -> Result<Ordering, QuoteCompareError> {
+        if self.swap_mode != other.swap_mode {
+            return Err(QuoteCompareError::DifferentSwapMode(
+                self.swap_mode,
+                other.swap_mode,
+            ));
+        }
+        if self.input_mint != other.input_mint || self.output_mint != other.output_mint {
+            return Err(QuoteCompareError::DifferentMints);
+        }
+        let amounts = match self.swap_mode {
+            SwapMode::ExactIn => (self.in_amount, other.in_amount),
+            SwapMode::ExactOut => (self.out_amount, other.out_amount),
+        };
+        if amounts.0 != amounts.1 {
+            return Err(QuoteCompareError::DifferentAmount(amounts.0, amounts.1));
+        }
+        let by_amount = match self.swap_mode {
+            SwapMode::ExactIn => self.out_amount.cmp(&other.out_amount),
+            SwapMode::ExactOut => other.in_amount.cmp(&self.in_amount),
+        };
+        Ok(by_amount
+            .then_with(|| other.route_plan.len().cmp(&self.route_plan.len()))
+            .then_with(|| other.price_impact_pct.cmp(&self.price_impact_pct)))
+    }
+
+    /// Whether the quote is strictly better than `other`, see [`Self::compare`]
+    pub fn better_than(&self, other: &QuoteResponse) -> Result<bool, QuoteCompareError> {
+        Ok(self.compare(other)? == Ordering::Greater)
+    }
+
+    /// Check that the quote was computed with the requested swap mode,
+    /// some DEXes do not support ExactOut
+    pub fn assert_mode(&self, expected: SwapMode) -> Result<(), SwapModeMismatch> {
+        if self.swap_mode != expected {
+            return Err(SwapModeMismatch {
+                expected,
+                actual: self.swap_mode,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        mints::{USDC_MINT, USDT_MINT},
+        route_plan_with_metadata::{AmmLabel, FeeMint, RoutePlanStep},
+    };
+
+    // Pinned for downstream caches and comparisons
+    static_assertions::assert_impl_all!(SwapInfo: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(SwapMode: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(ComputeUnitScore: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(QuoteRequest: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(QuoteExtraArgs: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(InternalQuoteRequest: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(PlatformFee: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(QuoteResponse: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(FeeSummary: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(RouteComplexity: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(Threshold: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+    static_assertions::assert_impl_all!(QuoteRequestError: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+    static_assertions::assert_impl_all!(QuoteError: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+
+    fn quote_request() -> QuoteRequest {
+        QuoteRequest {
+            input_mint: NATIVE_MINT,
+            output_mint: USDC_MINT,
+            amount: 1_000_000,
+            ..QuoteRequest::default()
+        }
+    }
+
+    /// Query string of `quote_request` as sent by the client
+    fn query(quote_request: QuoteRequest) -> String {
+        let request = reqwest::Client::new()
+            .get("http://localhost/quote")
+            .query(&InternalQuoteRequest::from(quote_request))
+            .build()
+            .unwrap();
+        request.url().query().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn swap_mode_round_trips() {
+        for swap_mode in [SwapMode::ExactIn, SwapMode::ExactOut] {
+            assert_eq!(swap_mode.to_string().parse(), Ok(swap_mode));
+            let json = serde_json::to_string(&swap_mode).unwrap();
+            assert_eq!(json, format!("\"{swap_mode}\""));
+            assert_eq!(serde_json::from_str::<SwapMode>(&json).unwrap(), swap_mode);
+        }
+        assert_eq!("exactout".parse(), Ok(SwapMode::ExactOut));
+        assert_eq!("EXACTIN".parse(), Ok(SwapMode::ExactIn));
+    }
+
+    #[test]
+    fn swap_mode_parse_failures() {
+        for invalid in ["", "Exact", "ExactInn", " ExactIn", "exact_in"] {
+            assert_eq!(
+                invalid.parse::<SwapMode>(),
+                Err(ParseSwapModeError(invalid.to_string()))
+            );
+        }
+        // Serde only accepts the exact strings of the API
+        assert!(serde_json::from_str::<SwapMode>("\"exactIn\"").is_err());
+    }
+
+    #[test]
+    fn assert_mode() {
+        let quote_response = QuoteResponse {
+            swap_mode: SwapMode::ExactIn,
+            ..QuoteResponse::default()
+        };
+        assert_eq!(quote_response.assert_mode(SwapMode::ExactIn), Ok(()));
+        assert_eq!(
+            quote_response.assert_mode(SwapMode::ExactOut),
+            Err(SwapModeMismatch {
+                expected: SwapMode::ExactOut,
+                actual: SwapMode::ExactIn
+            })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_zero_amount() {
+        assert_eq!(quote_request().validate(), Ok(()));
+        let zero_amount = QuoteRequest {
+            amount: 0,
+            ..quote_request()
+        };
+        assert_eq!(zero_amount.validate(), Err(QuoteRequestError::ZeroAmount));
+    }
+
+    #[test]
+    fn validate_rejects_slippage_with_auto_slippage() {
+        let quote_request = QuoteRequest {
+            slippage_bps: 50,
+            auto_slippage: Some(true),
+            ..quote_request()
+        };
+        assert_eq!(
+            quote_request.validate(),
+            Err(QuoteRequestError::SlippageWithAutoSlippage(50))
+        );
+    }
+
+    #[test]
+    fn minimal_request_only_sends_mints_and_amount() {
+        assert_eq!(
+            query(quote_request()),
+            format!("inputMint={NATIVE_MINT}&outputMint={USDC_MINT}&amount=1000000")
+        );
+    }
+
+    #[test]
+    fn swap_mode_is_only_sent_for_exact_out() {
+        let exact_in = QuoteRequest {
+            swap_mode: Some(SwapMode::ExactIn),
+            ..quote_request()
+        };
+        assert!(!query(exact_in).contains("swapMode"));
+        let exact_out = QuoteRequest {
+            swap_mode: Some(SwapMode::ExactOut),
+            ..quote_request()
+        };
+        assert!(query(exact_out).ends_with("&amount=1000000&swapMode=ExactOut"));
+    }
+
+    #[test]
+    fn set_fields_are_sent() {
+        let quote_request = QuoteRequest {
+            slippage_bps: 50,
+            only_direct_routes: Some(false),
+            compute_auto_slippage: true,
+            ..quote_request()
+        };
+        assert!(query(quote_request)
+            .ends_with("&slippageBps=50&computeAutoSlippage=true&onlyDirectRoutes=false"));
+    }
+
+    #[test]
+    fn auto_slippage_collision_usd_value_takes_u64() {
+        let quote_request = QuoteRequest {
+            auto_slippage_collision_usd_value: Some(u64::from(u32::MAX) + 1),
+            ..quote_request()
+        };
+        assert!(query(quote_request).ends_with("&autoSlippageCollisionUsdValue=4294967296"));
+    }
+
+    #[test]
+    fn ui_amounts_use_the_mint_decimals() {
+        let quote_request = quote_request()
+            .amount_ui(Decimal::new(1_234_567, 6), 2)
+            .unwrap();
+        assert_eq!(quote_request.amount, 123);
+        assert!(matches!(
+            quote_request.amount_ui(Decimal::MAX, 6),
+            Err(AmountError::Overflow { .. })
+        ));
+
+        let quote_response = QuoteResponse {
+            in_amount: 1_500_000_000,
+            out_amount: 250_000,
+            ..QuoteResponse::default()
+        };
+        assert_eq!(quote_response.in_amount_ui(9), Decimal::new(15, 1));
+        assert_eq!(quote_response.out_amount_ui(6), Decimal::new(25, 2));
+    }
+
+    /// `/quote` response as sent by the API
+    pub(crate) fn quote_response_fixture() -> serde_json::Value {
+        serde_json::json!({
+            "inputMint": "So11111111111111111111111111111111111111112",
+            "inAmount": "1000000000",
+            "outputMint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+            "outAmount": "146829450",
+            "otherAmountThreshold": "146095303",
+            "swapMode": "ExactIn",
+            "slippageBps": 50,
+            "platformFee": { "amount": "14683", "feeBps": 1 },
+            "priceImpactPct": "0.0001",
+            "routePlan": [
+                {
+                    "swapInfo": {
+                        "ammKey": "5BKxfWMbmYBAEWvyPZS9esPducUba9GqyMjtLCfbaqyF",
+                        "label": "Meteora DLMM",
+                        "inputMint": "So11111111111111111111111111111111111111112",
+                        "outputMint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+                        "inAmount": "700000000",
+                        "outAmount": "102780615",
+                        "feeAmount": "70000",
+                        "feeMint": "So11111111111111111111111111111111111111112"
+                    },
+                    "percent": 70
+                },
+                {
+                    "swapInfo": {
+                        "ammKey": "Czfq3xZZDmsdGdUyrNLtRhGc47cXcZtLG4crryfu44zE",
+                        "label": "Some New Amm",
+                        "inputMint": "So11111111111111111111111111111111111111112",
+                        "outputMint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+                        "inAmount": "300000000",
+                        "outAmount": "44048835",
+                        "feeAmount": "1200",
+                        "feeMint": "9BEcn9aPEmhSPbPQeFGjidRiEKki46fVQDyPpSQXPA2D"
+                    },
+                    "percent": 30
+                }
+            ],
+            "contextSlot": 325_679_512,
+            "timeTaken": 0.012
+        })
+    }
+
+    pub(crate) fn assert_no_nulls(value: &serde_json::Value) {
+        match value {
+            serde_json::Value::Null => panic!("null values are rejected by the API"),
+            serde_json::Value::Array(values) => values.iter().for_each(assert_no_nulls),
+            serde_json::Value::Object(fields) => fields.values().for_each(assert_no_nulls),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn quote_response_round_trips_as_stable_json() {
+        let fixture = quote_response_fixture();
+        let quote_response: QuoteResponse = serde_json::from_value(fixture.clone()).unwrap();
+        assert_eq!(quote_response.route_plan.len(), 2);
+        assert_eq!(quote_response.price_impact_pct, Decimal::new(1, 4));
+
+        let serialized = serde_json::to_value(&quote_response).unwrap();
+        assert_eq!(serialized, fixture);
+        assert_eq!(
+            serde_json::from_value::<QuoteResponse>(serialized).unwrap(),
+            quote_response
+        );
+    }
+
+    #[test]
+    fn unset_quote_response_fields_are_omitted() {
+        let serialized = serde_json::to_value(QuoteResponse::default()).unwrap();
+        assert_no_nulls(&serialized);
+        for field in [
+            "computedAutoSlippage",
+            "usesQuoteMinimizingSlippage",
+            "platformFee",
+            "contextSlot",
+            "timeTaken",
+        ] {
+            assert!(serialized.get(field).is_none(), "{field}");
+        }
+        assert_eq!(
+            serde_json::from_value::<QuoteResponse>(serialized).unwrap(),
+            QuoteResponse::default()
+        );
+    }
+
+    #[test]
+    fn context_slot_is_parsed_when_present() {
+        let quote_response: QuoteResponse =
+            serde_json::from_value(quote_response_fixture()).unwrap();
+        assert_eq!(quote_response.context_slot, Some(325_679_512));
+        assert_eq!(quote_response.time_taken, Some(0.012));
+        assert_eq!(quote_response.other_amount_threshold, 146_095_303);
+    }
+
+    #[test]
+    fn context_slot_is_none_when_absent() {
+        let mut fixture = quote_response_fixture();
+        let fields = fixture.as_object_mut().unwrap();
+        fields.remove("contextSlot");
+        fields.remove("timeTaken");
+        let quote_response: QuoteResponse = serde_json::from_value(fixture).unwrap();
+        assert_eq!(quote_response.context_slot, None);
+        assert_eq!(quote_response.time_taken, None);
+
+        let mut fixture = quote_response_fixture();
+        fixture["contextSlot"] = serde_json::Value::Null;
+        let quote_response: QuoteResponse = serde_json::from_value(fixture).unwrap();
+        assert_eq!(quote_response.context_slot, None);
+    }
+
+    fn quote_in_mode(swap_mode: SwapMode) -> QuoteResponse {
+        QuoteResponse {
+            in_amount: 10_000,
+            out_amount: 20_000,
+            other_amount_threshold: match swap_mode {
+                SwapMode::ExactIn => 19_900,
+                SwapMode::ExactOut => 10_050,
+            },
+            swap_mode,
+            slippage_bps: 50,
+            ..QuoteResponse::default()
+        }
+    }
+
+    #[test]
+    fn max_in_amount_is_only_set_for_exact_out() {
+        assert_eq!(quote_in_mode(SwapMode::ExactIn).max_in_amount(), None);
+        assert_eq!(
+            quote_in_mode(SwapMode::ExactOut).max_in_amount(),
+            Some(10_050)
+        );
+    }
+
+    #[test]
+    fn fits_balance_at_the_boundary() {
+        let exact_in = quote_in_mode(SwapMode::ExactIn);
+        assert!(exact_in.fits_balance(10_000));
+        assert!(!exact_in.fits_balance(9_999));
+
+        // The slippage on the input has to be covered as well
+        let exact_out = quote_in_mode(SwapMode::ExactOut);
+        assert!(exact_out.fits_balance(10_050));
+        assert!(!exact_out.fits_balance(10_049));
+        assert!(!exact_out.fits_balance(10_000));
+    }
+
+    #[test]
+    fn threshold_depends_on_the_swap_mode() {
+        assert_eq!(
+            quote_in_mode(SwapMode::ExactIn).threshold(),
+            Threshold::MinimumOut(19_900)
+        );
+        assert_eq!(
+            quote_in_mode(SwapMode::ExactOut).threshold(),
+            Threshold::MaximumIn(10_050)
+        );
+        assert_eq!(Threshold::MaximumIn(10_050).amount(), 10_050);
+    }
+
+    #[test]
+    fn threshold_with_slippage_rounds_against_the_user() {
+        let mut exact_in = quote_in_mode(SwapMode::ExactIn);
+        assert_eq!(
+            exact_in.threshold_with_slippage(50),
+            Threshold::MinimumOut(19_900)
+        );
+        // 9950.995 is rounded down
+        exact_in.out_amount = 10_001;
+        assert_eq!(
+            exact_in.threshold_with_slippage(50),
+            Threshold::MinimumOut(9_950)
+        );
+        assert_eq!(
+            exact_in.threshold_with_slippage(0),
+            Threshold::MinimumOut(10_001)
+        );
+        assert_eq!(
+            exact_in.threshold_with_slippage(10_000),
+            Threshold::MinimumOut(0)
+        );
+        assert_eq!(
+            exact_in.threshold_with_slippage(u16::MAX),
+            Threshold::MinimumOut(0)
+        );
+
+        let mut exact_out = quote_in_mode(SwapMode::ExactOut);
+        assert_eq!(
+            exact_out.threshold_with_slippage(50),
+            Threshold::MaximumIn(10_050)
+        );
+        // 10051.005 is rounded up
+        exact_out.in_amount = 10_001;
+        assert_eq!(
+            exact_out.threshold_with_slippage(50),
+            Threshold::MaximumIn(10_052)
+        );
+        assert_eq!(
+            exact_out.threshold_with_slippage(0),
+            Threshold::MaximumIn(10_001)
+        );
+    }
+
+    #[test]
+    fn minimum_received_prefers_the_server_threshold() {
+        let mut exact_in = quote_in_mode(SwapMode::ExactIn);
+        // Not recomputed from slippage_bps, the server threshold is what the swap enforces
+        exact_in.other_amount_threshold = 19_899;
+        assert_eq!(exact_in.minimum_received(), 19_899);
+        assert_eq!(exact_in.maximum_spent(), 10_000);
+
+        let exact_out = quote_in_mode(SwapMode::ExactOut);
+        assert_eq!(exact_out.minimum_received(), 20_000);
+        assert_eq!(exact_out.maximum_spent(), 10_050);
+    }
+
+    #[test]
+    fn large_amounts_do_not_overflow() {
+        let exact_in = QuoteResponse {
+            in_amount: u64::MAX,
+            out_amount: u64::MAX,
+            other_amount_threshold: u64::MAX - 1,
+            ..quote_in_mode(SwapMode::ExactIn)
+        };
+        assert_eq!(exact_in.minimum_received(), u64::MAX - 1);
+        assert_eq!(exact_in.maximum_spent(), u64::MAX);
+        assert_eq!(
+            exact_in.threshold_with_slippage(1),
+            Threshold::MinimumOut(u64::MAX / 10_000 * 9_999 + u64::MAX % 10_000 * 9_999 / 10_000)
+        );
+
+        let exact_out = QuoteResponse {
+            swap_mode: SwapMode::ExactOut,
+            other_amount_threshold: u64::MAX,
+            ..exact_in
+        };
+        assert_eq!(exact_out.maximum_spent(), u64::MAX);
+        assert_eq!(exact_out.minimum_received(), u64::MAX);
+        // Capped rather than wrapping
+        assert_eq!(
+            exact_out.threshold_with_slippage(u16::MAX),
+            Threshold::MaximumIn(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn equal_dex_lists_produce_identical_queries() {
+        let with_dexes = |dexes: &str| {
+            query(QuoteRequest {
+                dexes: Some(dexes.to_string()),
+                excluded_dexes: Some(dexes.to_string()),
+                ..quote_request()
+            })
+        };
+        let expected = with_dexes("Raydium,Orca");
+        assert_eq!(with_dexes("Orca,Raydium,Orca"), expected);
+        assert_eq!(with_dexes(" Raydium, Orca,,"), expected);
+        assert!(expected.contains("dexes=Orca%2CRaydium"), "{expected}");
+        assert!(
+            expected.contains("excludedDexes=Orca%2CRaydium"),
+            "{expected}"
+        );
+    }
+
+    #[test]
+    fn repeated_dexes_are_normalized_too() {
+        let mut request = InternalQuoteRequest::from(QuoteRequest {
+            dexes: Some("Orca,Raydium,Orca".to_string()),
+            ..quote_request()
+        });
+        assert_eq!(
+            request.take_repeated_dexes(),
+            [
+                ("dexes", "Orca".to_string()),
+                ("dexes", "Raydium".to_string())
+            ]
+        );
+        assert_eq!(request.dexes, None);
+    }
+
+    fn exact_in_quote(out_amount: u64, steps: usize, price_impact_pct: &str) -> QuoteResponse {
+        QuoteResponse {
+            out_amount,
+            route_plan: vec![RoutePlanStep::default(); steps],
+            price_impact_pct: Decimal::from_str(price_impact_pct).unwrap(),
+            ..quote_in_mode(SwapMode::ExactIn)
+        }
+    }
+
+    #[test]
+    fn better_than_prefers_the_amount_then_fewer_steps_then_lower_impact() {
+        let quote = exact_in_quote(20_000, 2, "0.01");
+        assert!(exact_in_quote(20_001, 3, "0.5")
+            .better_than(&quote)
+            .unwrap());
+        assert!(exact_in_quote(20_000, 1, "0.5")
+            .better_than(&quote)
+            .unwrap());
+        assert!(exact_in_quote(20_000, 2, "0.001")
+            .better_than(&quote)
+            .unwrap());
+        assert!(!exact_in_quote(19_999, 1, "0").better_than(&quote).unwrap());
+        // A tie is not better
+        assert!(!quote.better_than(&quote.clone()).unwrap());
+        assert_eq!(quote.compare(&quote.clone()), Ok(Ordering::Equal));
+
+        // Spending less is better for ExactOut
+        let exact_out = quote_in_mode(SwapMode::ExactOut);
+        let cheaper = QuoteResponse {
+            in_amount: 9_999,
+            ..exact_out.clone()
+        };
+        assert!(cheaper.better_than(&exact_out).unwrap());
+        assert!(!exact_out.better_than(&cheaper).unwrap());
+    }
+
+    #[test]
+    fn quotes_of_different_swaps_are_not_comparable() {
+        let quote = quote_in_mode(SwapMode::ExactIn);
+        assert_eq!(
+            quote.compare(&quote_in_mode(SwapMode::ExactOut)),
+            Err(QuoteCompareError::DifferentSwapMode(
+                SwapMode::ExactIn,
+                SwapMode::ExactOut
+            ))
+        );
+        let other_mint = QuoteResponse {
+            output_mint: Pubkey::new_unique(),
+            ..quote.clone()
+        };
+        assert_eq!(
+            quote.compare(&other_mint),
+            Err(QuoteCompareError::DifferentMints)
+        );
+        let other_amount = QuoteResponse {
+            in_amount: 5_000,
+            ..quote.clone()
+        };
+        assert_eq!(
+            quote.better_than(&other_amount),
+            Err(QuoteCompareError::DifferentAmount(10_000, 5_000))
+        );
+    }
+
+    #[test]
+    fn best_quote_keeps_the_first_of_ties_and_skips_incomparable_quotes() {
+        assert_eq!(best_quote([]), None);
+
+        let first = exact_in_quote(20_000, 2, "0.01");
+        let tie = exact_in_quote(20_000, 2, "0.01");
+        let better = exact_in_quote(20_500, 2, "0.01");
+        let incomparable = QuoteResponse {
+            in_amount: 1_000_000,
+            out_amount: 2_000_000,
+            ..first.clone()
+        };
+        let quotes = [first, tie, incomparable, better];
+        assert!(std::ptr::eq(best_quote(&quotes).unwrap(), &quotes[3]));
+        assert!(std::ptr::eq(best_quote(&quotes[..3]).unwrap(), &quotes[0]));
+    }
+
+    #[test]
+    fn typed_quote_args_win_over_custom_ones() {
+        let market = Pubkey::new_unique();
+        let quote_args = QuoteExtraArgs {
+            markets: Some(vec![market]),
+            excluded_markets: None,
+            custom: HashMap::from([
+                ("markets".to_string(), "overridden".to_string()),
+                ("excludedMarkets".to_string(), "kept".to_string()),
+                ("b".to_string(), "2".to_string()),
+                ("a".to_string(), "1".to_string()),
+            ]),
+        };
+        assert_eq!(
+            quote_args.query_pairs(),
+            [
+                ("markets".to_string(), market.to_string()),
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "2".to_string()),
+                ("excludedMarkets".to_string(), "kept".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn quote_args_deserialize_typed_and_custom_keys() {
+        let markets = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let quote_args: QuoteExtraArgs = serde_json::from_value(serde_json::json!({
+            "markets": format!("{},{}", markets[0], markets[1]),
+            "maxHops": "2",
+        }))
+        .unwrap();
+        assert_eq!(
+            quote_args,
+            QuoteExtraArgs {
+                markets: Some(markets.to_vec()),
+                excluded_markets: None,
+                custom: HashMap::from([("maxHops".to_string(), "2".to_string())]),
+            }
+        );
+        assert!(
+            serde_json::from_value::<QuoteExtraArgs>(serde_json::json!({"markets": "nope"}))
+                .is_err()
+        );
+    }
+
+    fn with_platform_fee(
+        quote: QuoteResponse,
+        amount: u64,
+        fee_mint: Option<Pubkey>,
+    ) -> QuoteResponse {
+        QuoteResponse {
+            platform_fee: Some(PlatformFee {
+                amount,
+                fee_bps: 20,
+                fee_mint,
+            }),
+            ..quote
+        }
+    }
+
+    #[test]
+    fn net_out_amount_of_exact_in_deducts_the_fee_on_output() {
+        let quote = QuoteResponse {
+            output_mint: USDC_MINT,
+            ..quote_in_mode(SwapMode::ExactIn)
+        };
+        assert_eq!(quote.net_out_amount(), 20_000);
+        assert_eq!(
+            with_platform_fee(quote.clone(), 40, None).net_out_amount(),
+            19_960
+        );
+        assert_eq!(
+            with_platform_fee(quote.clone(), 40, Some(USDC_MINT)).net_out_amount(),
+            19_960
+        );
+        // Reported in the input mint, the output is untouched
+        assert_eq!(
+            with_platform_fee(quote.clone(), 40, Some(NATIVE_MINT)).net_out_amount(),
+            20_000
+        );
+        // A fee larger than the output saturates
+        assert_eq!(with_platform_fee(quote, 20_001, None).net_out_amount(), 0);
+    }
+
+    #[test]
+    fn net_out_amount_of_exact_out_ignores_the_fee_on_input() {
+        let quote = QuoteResponse {
+            input_mint: NATIVE_MINT,
+            output_mint: USDC_MINT,
+            ..quote_in_mode(SwapMode::ExactOut)
+        };
+        assert_eq!(
+            with_platform_fee(quote.clone(), 40, None).net_out_amount(),
+            20_000
+        );
+        assert_eq!(
+            with_platform_fee(quote.clone(), 40, Some(NATIVE_MINT)).net_out_amount(),
+            20_000
+        );
+        // Unless the server reports it in the output mint
+        assert_eq!(
+            with_platform_fee(quote.clone(), 40, Some(USDC_MINT)).net_out_amount(),
+            19_960
+        );
+        assert_eq!(
+            with_platform_fee(quote, u64::MAX, Some(USDC_MINT)).net_out_amount(),
+            0
+        );
+    }
+
+    #[test]
+    fn native_sol_is_involved_on_either_side() {
+        let other_mint = Pubkey::new_unique();
+        for (input_mint, output_mint, involves_native_sol) in [
+            (NATIVE_MINT, USDC_MINT, true),
+            (USDC_MINT, NATIVE_MINT, true),
+            (USDC_MINT, other_mint, false),
+        ] {
+            let quote_request = QuoteRequest {
+                input_mint,
+                output_mint,
+                ..quote_request()
+            };
+            assert_eq!(quote_request.involves_native_sol(), involves_native_sol);
+            let quote_response = QuoteResponse {
+                input_mint,
+                output_mint,
+                ..QuoteResponse::default()
+            };
+            assert_eq!(quote_response.involves_native_sol(), involves_native_sol);
+        }
+    }
+
+    fn route_step(
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+        label: AmmLabel,
+        fee_amount: u64,
+        fee_mint: Pubkey,
+    ) -> RoutePlanStep {
+        RoutePlanStep {
+            swap_info: SwapInfo {
+                amm_key: Pubkey::new_unique(),
+                label,
+                input_mint,
+                output_mint,
+                fee_amount,
+                fee_mint: FeeMint::from(fee_mint),
+                ..Default::default()
+            },
+            percent: 100,
+        }
+    }
+
+    #[test]
+    fn fee_summary_breaks_down_the_fees_of_a_multi_hop_route_per_mint() {
+        let middle_mint = Pubkey::new_unique();
+        let quote = QuoteResponse {
+            input_mint: NATIVE_MINT,
+            output_mint: USDT_MINT,
+            price_impact_pct: Decimal::from_str("0.012").unwrap(),
+            route_plan: vec![
+                route_step(
+                    NATIVE_MINT,
+                    middle_mint,
+                    AmmLabel::Whirlpool,
+                    25,
+                    NATIVE_MINT,
+                ),
+                route_step(middle_mint, USDC_MINT, AmmLabel::Raydium, 7, middle_mint),
+                route_step(USDC_MINT, USDT_MINT, AmmLabel::Raydium, 3, NATIVE_MINT),
+            ],
+            ..quote_in_mode(SwapMode::ExactIn)
+        };
+        let quote = with_platform_fee(quote, 40, Some(USDT_MINT));
+
+        let fee_summary = quote.fee_summary();
+        // Fees in different mints can not be added up
+        assert_eq!(fee_summary.total_lp_fee, None);
+        assert_eq!(
+            fee_summary.total_lp_fee_mint_breakdown,
+            BTreeMap::from([(NATIVE_MINT, 28), (middle_mint, 7)])
+        );
+        assert_eq!(fee_summary.platform_fee, quote.platform_fee);
+        assert_eq!(fee_summary.price_impact_pct, quote.price_impact_pct);
+    }
+
+    #[test]
+    fn fee_summary_totals_the_fees_taken_in_a_single_mint() {
+        let middle_mint = Pubkey::new_unique();
+        let quote = QuoteResponse {
+            route_plan: vec![
+                route_step(NATIVE_MINT, middle_mint, AmmLabel::Whirlpool, 25, USDC_MINT),
+                route_step(
+                    middle_mint,
+                    USDC_MINT,
+                    AmmLabel::Raydium,
+                    u64::MAX,
+                    USDC_MINT,
+                ),
+            ],
+            ..quote_in_mode(SwapMode::ExactIn)
+        };
+        let fee_summary = quote.fee_summary();
+        assert_eq!(fee_summary.total_lp_fee, Some(u64::MAX));
+        assert_eq!(
+            fee_summary.total_lp_fee_mint_breakdown,
+            BTreeMap::from([(USDC_MINT, u64::MAX)])
+        );
+        assert_eq!(fee_summary.platform_fee, None);
+
+        // No hop, no fee
+        assert_eq!(
+            QuoteResponse::default().fee_summary(),
+            FeeSummary {
+                total_lp_fee: Some(0),
+                ..FeeSummary::default()
+            }
+        );
+    }
+
+    #[test]
+    fn route_complexity_counts_hops_and_amms() {
+        let middle_mint = Pubkey::new_unique();
+        // Split across two AMMs on the direct pair
+        let split = QuoteResponse {
+            input_mint: NATIVE_MINT,
+            output_mint: USDC_MINT,
+            route_plan: vec![
+                route_step(NATIVE_MINT, USDC_MINT, AmmLabel::Whirlpool, 0, NATIVE_MINT),
+                route_step(NATIVE_MINT, USDC_MINT, AmmLabel::Raydium, 0, NATIVE_MINT),
+            ],
+            ..QuoteResponse::default()
+        };
+        assert_eq!(
+            split.route_complexity(),
+            RouteComplexity {
+                hop_count: 1,
+                distinct_amms: 2,
+                uses_direct_route: true,
+            }
+        );
+
+        // Two hops through the same AMM, one of them split
+        let multi_hop = QuoteResponse {
+            route_plan: vec![
+                route_step(NATIVE_MINT, middle_mint, AmmLabel::Raydium, 0, NATIVE_MINT),
+                route_step(middle_mint, USDC_MINT, AmmLabel::Raydium, 0, middle_mint),
+                route_step(middle_mint, USDC_MINT, AmmLabel::Raydium, 0, middle_mint),
+            ],
+            ..split.clone()
+        };
+        assert_eq!(
+            multi_hop.route_complexity(),
+            RouteComplexity {
+                hop_count: 2,
+                distinct_amms: 1,
+                uses_direct_route: false,
+            }
+        );
+
+        // A single hop which does not swap the quoted pair is not a direct route
+        let indirect = QuoteResponse {
+            route_plan: vec![route_step(
+                NATIVE_MINT,
+                middle_mint,
+                AmmLabel::Other("Unknown AMM".to_string()),
+                0,
+                NATIVE_MINT,
+            )],
+            ..split
+        };
+        assert_eq!(
+            indirect.route_complexity(),
+            RouteComplexity {
+                hop_count: 1,
+                distinct_amms: 1,
+                uses_direct_route: false,
+            }
+        );
+        assert_eq!(
+            QuoteResponse::default().route_complexity(),
+            RouteComplexity::default()
+        );
+    }
+
+    #[test]
+    fn age_is_measured_from_when_the_quote_was_received() {
+        assert_eq!(QuoteResponse::default().age(), None);
+        let quote = QuoteResponse {
+            received_at: Instant::now().checked_sub(Duration::from_secs(5)),
+            ..QuoteResponse::default()
+        };
+        assert!(quote.age().unwrap() >= Duration::from_secs(5));
+    }
+
+    #[test]
+    fn fresher_quotes_have_a_later_context_slot_or_were_received_last() {
+        let at_slot = |context_slot| QuoteResponse {
+            context_slot,
+            ..QuoteResponse::default()
+        };
+        assert!(at_slot(Some(11)).is_fresher_than(&at_slot(Some(10))));
+        assert!(!at_slot(Some(10)).is_fresher_than(&at_slot(Some(11))));
+        assert!(!at_slot(Some(10)).is_fresher_than(&at_slot(Some(10))));
+        assert!(at_slot(Some(10)).is_fresher_than(&at_slot(None)));
+        assert!(!at_slot(None).is_fresher_than(&at_slot(Some(10))));
+
+        let now = Instant::now();
+        let received = |received_at| QuoteResponse {
+            received_at,
+            ..QuoteResponse::default()
+        };
+        let earlier = received(now.checked_sub(Duration::from_secs(1)));
+        assert!(received(Some(now)).is_fresher_than(&earlier));
+        assert!(!earlier.is_fresher_than(&received(Some(now))));
+        assert!(!received(Some(now)).is_fresher_than(&received(None)));
+        assert!(!received(None).is_fresher_than(&received(None)));
+        // The slot wins over the time the quote was received
+        let stale_slot = QuoteResponse {
+            context_slot: Some(10),
+            ..received(Some(now))
+        };
+        let fresh_slot = QuoteResponse {
+            context_slot: Some(11),
+            ..earlier
+        };
+        assert!(fresh_slot.is_fresher_than(&stale_slot));
+    }
 }
@@ -0,0 +1,127 @@
+//! Memoization of quotes for UIs requesting the same quote on every keystroke, requires the `quote-cache` feature,
+//! see [`JupiterSwapApiClientBuilder::quote_cache`](crate::builder::JupiterSwapApiClientBuilder::quote_cache)
+//!
+//! Cached quotes keep the `context_slot` and `received_at` of the response they come from,
+//! so [`QuoteResponse::age`] tells how stale they are.
+//! Use [`RequestOptions::bypass_cache`] for the quote about to be swapped.
+
+use std::time::Duration;
+
+use crate::{
+    cache::TtlCache,
+    quote::{InternalQuoteRequest, QuoteRequest, QuoteResponse},
+    request_options::RequestOptions,
+};
+
+/// Short enough for a quote to still be executable
+pub const DEFAULT_QUOTE_CACHE_TTL: Duration = Duration::from_millis(250);
+
+/// Quotes by [`cache_key`]
+pub(crate) type QuoteCache = TtlCache<String, QuoteResponse>;
+
+/// The query string parameters the quote is requested with, `None` when they cannot be serialized
+pub(crate) fn cache_key(quote_request: &QuoteRequest, options: &RequestOptions) -> Option<String> {
+    let internal_quote_request = InternalQuoteRequest::from(quote_request.clone());
+    let extra_args = quote_request
+        .quote_args
+        .as_ref()
+        .map(|quote_args| quote_args.query_pairs())
+        .unwrap_or_default();
+    serde_json::to_string(&(&internal_quote_request, extra_args, &options.query)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{builder::JupiterSwapApiClientBuilder, JupiterSwapApiClient};
+
+    fn quote_request() -> QuoteRequest {
+        QuoteRequest {
+            amount: 1_000,
+            ..QuoteRequest::default()
+        }
+    }
+
+    async fn cached_client() -> (MockServer, JupiterSwapApiClient) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(QuoteResponse {
+                context_slot: Some(325_679_512),
+                ..QuoteResponse::default()
+            }))
+            .mount(&server)
+            .await;
+        let client = JupiterSwapApiClientBuilder::new(server.uri())
+            .quote_cache(DEFAULT_QUOTE_CACHE_TTL, 16)
+            .build()
+            .unwrap();
+        (server, client)
+    }
+
+    async fn request_count(server: &MockServer) -> usize {
+        server.received_requests().await.unwrap().len()
+    }
+
+    #[test]
+    fn cache_key_covers_the_request_and_the_extra_query() {
+        let options = RequestOptions::default();
+        let key = cache_key(&quote_request(), &options).unwrap();
+        assert_eq!(cache_key(&quote_request(), &options).unwrap(), key);
+
+        let other_amount = QuoteRequest {
+            amount: 2_000,
+            ..quote_request()
+        };
+        assert_ne!(cache_key(&other_amount, &options).unwrap(), key);
+        let with_query = RequestOptions::default().query("maxHops".to_string(), "2".to_string());
+        assert_ne!(cache_key(&quote_request(), &with_query).unwrap(), key);
+        // Options which do not change the query share the entry
+        let with_timeout = RequestOptions::default().timeout(Duration::from_secs(1));
+        assert_eq!(cache_key(&quote_request(), &with_timeout).unwrap(), key);
+    }
+
+    #[tokio::test]
+    async fn identical_requests_hit_the_cache() {
+        let (server, client) = cached_client().await;
+
+        let first = client.quote(&quote_request()).await.unwrap();
+        // Shared by the clones of the client
+        let cached = client.clone().quote(&quote_request()).await.unwrap();
+        assert_eq!(request_count(&server).await, 1);
+        assert_eq!(cached.context_slot, first.context_slot);
+        assert_eq!(cached.received_at, first.received_at);
+
+        let with_query = RequestOptions::default().query("maxHops".to_string(), "2".to_string());
+        client
+            .quote_with_options(&quote_request(), &with_query)
+            .await
+            .unwrap();
+        assert_eq!(request_count(&server).await, 2);
+        client
+            .quote_with_options(&quote_request(), &RequestOptions::default().bypass_cache())
+            .await
+            .unwrap();
+        assert_eq!(request_count(&server).await, 3);
+    }
+
+    #[tokio::test]
+    async fn entries_expire_after_the_ttl() {
+        let (server, client) = cached_client().await;
+        tokio::time::pause();
+
+        client.quote(&quote_request()).await.unwrap();
+        tokio::time::advance(DEFAULT_QUOTE_CACHE_TTL - Duration::from_millis(1)).await;
+        client.quote(&quote_request()).await.unwrap();
+        assert_eq!(request_count(&server).await, 1);
+
+        tokio::time::advance(Duration::from_millis(1)).await;
+        client.quote(&quote_request()).await.unwrap();
+        assert_eq!(request_count(&server).await, 2);
+    }
+}
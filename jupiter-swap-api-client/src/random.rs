@@ -0,0 +1,50 @@
+//! Randomness spreading load, such as retry jitter, not suitable for anything security related
+
+use std::{
+    cell::Cell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+thread_local! {
+    /// xorshift64* state, never 0
+    static STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+pub(crate) fn random_u64() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    })
+}
+
+/// Uniform in `[0, 1]`
+pub(crate) fn random_fraction() -> f64 {
+    (random_u64() >> 11) as f64 / ((1u64 << 53) - 1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractions_are_within_bounds() {
+        let fractions: Vec<f64> = (0..10_000).map(|_| random_fraction()).collect();
+        assert!(fractions
+            .iter()
+            .all(|fraction| (0.0..=1.0).contains(fraction)));
+        // Spread over the whole range
+        assert!(fractions.iter().any(|fraction| *fraction < 0.1));
+        assert!(fractions.iter().any(|fraction| *fraction > 0.9));
+    }
+
+    #[test]
+    fn values_differ() {
+        let values: std::collections::HashSet<u64> = (0..1_000).map(|_| random_u64()).collect();
+        assert_eq!(values.len(), 1_000);
+    }
+}
@@ -0,0 +1,392 @@
+//! Recording of the quote and swap responses seen by a client and their replay, requires the `record-replay` feature
+//!
+//! Responses are recorded as received, byte for byte, along with the request which produced them.
+//! [`ReplayClient`] serves them back through [`JupiterApi`] for backtests.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    api::JupiterApi,
+    deserialize_body,
+    metrics::Endpoint,
+    quote::{QuoteRequest, QuoteResponse},
+    swap::{
+        base64_serialize_deserialize, SwapInstructionsResponse, SwapInstructionsResponseInternal,
+        SwapRequest, SwapResponse,
+    },
+    ClientError, ErrorKind, JupiterSwapApiClient,
+};
+
+/// Fields which differ between otherwise identical requests, ignored when matching recordings
+const VOLATILE_FIELDS: [&str; 2] = ["contextSlot", "timeTaken"];
+
+/// Receives every successful quote, swap and swap instructions response of a client,
+/// see [`JupiterSwapApiClientBuilder::recorder`](crate::builder::JupiterSwapApiClientBuilder::recorder)
+pub trait Recorder: Send + Sync {
+    /// `response` is the body as received
+    fn record(
+        &self,
+        endpoint: Endpoint,
+        request: &Value,
+        response: &[u8],
+        timestamp: DateTime<Utc>,
+    );
+}
+
+impl fmt::Debug for dyn Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Recorder")
+    }
+}
+
+/// Line of a recording file
+#[derive(Serialize, Deserialize)]
+struct Recording {
+    endpoint: String,
+    request: Value,
+    /// Base64 of the body, which may not be valid UTF-8
+    #[serde(with = "base64_serialize_deserialize")]
+    response: Vec<u8>,
+    timestamp: String,
+}
+
+/// Appends recordings to a file, one JSON object per line
+pub struct JsonlRecorder {
+    file: Mutex<File>,
+}
+
+impl JsonlRecorder {
+    /// Append to `path`, creating it if needed
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl Recorder for JsonlRecorder {
+    fn record(
+        &self,
+        endpoint: Endpoint,
+        request: &Value,
+        response: &[u8],
+        timestamp: DateTime<Utc>,
+    ) {
+        let recording = Recording {
+            endpoint: endpoint.to_string(),
+            request: request.clone(),
+            response: response.to_vec(),
+            timestamp: timestamp.to_rfc3339(),
+        };
+        let mut line = match serde_json::to_vec(&recording) {
+            Ok(line) => line,
+            Err(e) => {
+                log::warn!("Failed to serialize the {endpoint} recording: {e}");
+                return;
+            }
+        };
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(e) = file.write_all(&line) {
+            log::warn!("Failed to write the {endpoint} recording: {e}");
+        }
+    }
+}
+
+/// Request recorded along with the response once its body is read
+#[derive(Clone)]
+pub(crate) struct PendingRecording {
+    recorder: Arc<dyn Recorder>,
+    endpoint: Endpoint,
+    request: Value,
+}
+
+impl PendingRecording {
+    pub(crate) fn take(mut response: Response) -> (Response, Option<Self>) {
+        let pending = response.extensions_mut().remove::<Self>();
+        (response, pending)
+    }
+
+    pub(crate) fn record(self, body: &[u8]) {
+        self.recorder
+            .record(self.endpoint, &self.request, body, SystemTime::now().into());
+    }
+}
+
+impl JupiterSwapApiClient {
+    /// Record the body of `response` once read if it is successful and the client has a recorder
+    pub(crate) fn record_response<R: Serialize + ?Sized>(
+        &self,
+        endpoint: Endpoint,
+        response: &mut Response,
+        request: &R,
+    ) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+        if !response.status().is_success() {
+            return;
+        }
+        match serde_json::to_value(request) {
+            Ok(request) => {
+                response.extensions_mut().insert(PendingRecording {
+                    recorder: recorder.clone(),
+                    endpoint,
+                    request,
+                });
+            }
+            Err(e) => log::warn!("Failed to serialize the {endpoint} request to record: {e}"),
+        }
+    }
+}
+
+/// `value` without its volatile fields, with the keys of objects sorted whatever the `serde_json` features
+fn canonical(value: &Value) -> String {
+    match value {
+        Value::Object(object) => {
+            let fields: BTreeMap<_, _> = object
+                .iter()
+                .filter(|(key, _)| !VOLATILE_FIELDS.contains(&key.as_str()))
+                .collect();
+            let fields: Vec<_> = fields
+                .into_iter()
+                .map(|(key, value)| format!("{}:{}", Value::from(key.as_str()), canonical(value)))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(values) => {
+            let values: Vec<_> = values.iter().map(canonical).collect();
+            format!("[{}]", values.join(","))
+        }
+        value => value.to_string(),
+    }
+}
+
+/// Serves recorded responses, matched by endpoint and request ignoring volatile fields such as the context slot.
+/// Responses recorded for the same request are served in order, the last one being repeated.
+pub struct ReplayClient {
+    /// By endpoint and canonical request
+    recordings: Mutex<HashMap<(String, String), Responses>>,
+}
+
+#[derive(Default)]
+struct Responses {
+    bodies: Vec<Vec<u8>>,
+    next: usize,
+}
+
+impl ReplayClient {
+    /// Load the recordings written by a [`JsonlRecorder`]
+    pub fn from_jsonl(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Load recordings in the format of [`JsonlRecorder`], one per line
+    pub fn from_reader(reader: impl BufRead) -> io::Result<Self> {
+        let mut recordings: HashMap<_, Responses> = HashMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let recording: Recording = serde_json::from_str(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            recordings
+                .entry((recording.endpoint, canonical(&recording.request)))
+                .or_default()
+                .bodies
+                .push(recording.response);
+        }
+        Ok(Self {
+            recordings: Mutex::new(recordings),
+        })
+    }
+
+    fn replay<R: Serialize + ?Sized>(
+        &self,
+        endpoint: Endpoint,
+        request: &R,
+    ) -> Result<Vec<u8>, ClientError> {
+        let request = serde_json::to_value(request)
+            .map_err(|e| ClientError::from(e).with_endpoint(endpoint))?;
+        let mut recordings = self
+            .recordings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let responses = recordings
+            .get_mut(&(endpoint.to_string(), canonical(&request)))
+            .ok_or_else(|| {
+                ClientError::from(ErrorKind::NotRecorded { endpoint }).with_endpoint(endpoint)
+            })?;
+        let body = responses.bodies[responses.next.min(responses.bodies.len() - 1)].clone();
+        responses.next += 1;
+        Ok(body)
+    }
+}
+
+#[async_trait]
+impl JupiterApi for ReplayClient {
+    async fn quote(&self, quote_request: &QuoteRequest) -> Result<QuoteResponse, ClientError> {
+        let response = self.replay(Endpoint::Quote, quote_request)?;
+        deserialize_body(&response).map_err(|e| e.with_endpoint(Endpoint::Quote))
+    }
+
+    async fn swap(
+        &self,
+        swap_request: &SwapRequest,
+        _extra_args: Option<HashMap<String, String>>,
+    ) -> Result<SwapResponse, ClientError> {
+        let response = self.replay(Endpoint::Swap, swap_request)?;
+        deserialize_body(&response).map_err(|e| e.with_endpoint(Endpoint::Swap))
+    }
+
+    async fn swap_instructions(
+        &self,
+        swap_request: &SwapRequest,
+        _extra_args: Option<HashMap<String, String>>,
+    ) -> Result<SwapInstructionsResponse, ClientError> {
+        let response = self.replay(Endpoint::SwapInstructions, swap_request)?;
+        deserialize_body::<SwapInstructionsResponseInternal>(&response)
+            .map(Into::into)
+            .map_err(|e| e.with_endpoint(Endpoint::SwapInstructions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{builder::JupiterSwapApiClientBuilder, random::random_u64};
+
+    /// Writes to a fresh file of the temporary directory, removed on drop
+    struct TempPath(std::path::PathBuf);
+
+    impl TempPath {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!("recording-{:x}.jsonl", random_u64())))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn swap_request(context_slot: u64, time_taken: f64) -> SwapRequest {
+        SwapRequest {
+            quote_response: QuoteResponse {
+                in_amount: 1_000,
+                context_slot: Some(context_slot),
+                time_taken: Some(time_taken),
+                ..QuoteResponse::default()
+            },
+            ..SwapRequest::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn recorded_swap_is_replayed_whatever_the_volatile_fields() {
+        let server = MockServer::start().await;
+        let swap_response = SwapResponse {
+            swap_transaction: vec![1, 2, 3],
+            last_valid_block_height: 301_000_150,
+            ..SwapResponse::default()
+        };
+        Mock::given(method("POST"))
+            .and(path("/swap"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&swap_response))
+            .mount(&server)
+            .await;
+        let recording = TempPath::new();
+        let client = JupiterSwapApiClientBuilder::new(server.uri())
+            .recorder(Arc::new(JsonlRecorder::create(&recording.0).unwrap()))
+            .build()
+            .unwrap();
+        client
+            .swap(&swap_request(325_679_512, 0.012), None)
+            .await
+            .unwrap();
+
+        let replay = ReplayClient::from_jsonl(&recording.0).unwrap();
+        // Requoted at another slot, the request is the same otherwise
+        let replayed = replay
+            .swap(&swap_request(325_679_600, 0.5), None)
+            .await
+            .unwrap();
+        assert_eq!(replayed, swap_response);
+        // Any other change is not recorded
+        let mut other = swap_request(325_679_512, 0.012);
+        other.quote_response.in_amount = 2_000;
+        let error = replay.swap(&other, None).await.unwrap_err();
+        assert!(
+            matches!(
+                error.kind(),
+                ErrorKind::NotRecorded {
+                    endpoint: Endpoint::Swap
+                }
+            ),
+            "{error:?}"
+        );
+    }
+
+    #[test]
+    fn bodies_are_replayed_byte_for_byte_in_order() {
+        let mut lines = Vec::new();
+        let bodies: [&[u8]; 2] = [b"{\"a\":1}", &[0xff, 0xfe, b'{', 0x80]];
+        for body in bodies {
+            let path = TempPath::new();
+            JsonlRecorder::create(&path.0).unwrap().record(
+                Endpoint::Quote,
+                &serde_json::json!({ "amount": "1", "contextSlot": 1 }),
+                body,
+                SystemTime::now().into(),
+            );
+            lines.push(std::fs::read_to_string(&path.0).unwrap());
+        }
+        let replay = ReplayClient::from_reader(Cursor::new(lines.concat())).unwrap();
+
+        let request = serde_json::json!({ "contextSlot": 2, "amount": "1" });
+        assert_eq!(replay.replay(Endpoint::Quote, &request).unwrap(), bodies[0]);
+        assert_eq!(replay.replay(Endpoint::Quote, &request).unwrap(), bodies[1]);
+        // The last one is repeated
+        assert_eq!(replay.replay(Endpoint::Quote, &request).unwrap(), bodies[1]);
+    }
+
+    #[test]
+    fn canonical_ignores_key_order_and_volatile_fields() {
+        let recorded = serde_json::json!({
+            "b": [{ "timeTaken": 0.1, "y": 1, "x": 2 }],
+            "a": "1",
+            "contextSlot": 1
+        });
+        let requested = serde_json::json!({
+            "a": "1",
+            "b": [{ "x": 2, "y": 1 }],
+            "contextSlot": 2
+        });
+        assert_eq!(canonical(&recorded), canonical(&requested));
+        assert_eq!(canonical(&requested), r#"{"a":"1","b":[{"x":2,"y":1}]}"#);
+    }
+}
@@ -0,0 +1,520 @@
+//! Recurring API, dollar cost averaging positions executed over time or based on price
+//!
+
+use std::collections::HashMap;
+
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_pubkey::Pubkey;
+
+use crate::{
+    metrics::Endpoint,
+    pagination::{paginate, Paginated},
+    serde_helpers::field_as_string,
+    swap::base64_serialize_deserialize,
+    ClientError, JupiterSwapApiClient,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeBasedParams {
+    /// Total amount of input mint to spend, have to factor in the token decimals.
+    pub in_amount: u64,
+    pub number_of_orders: u64,
+    /// Seconds between two orders
+    pub interval: u64,
+    /// Orders are skipped while the output mint price is out of range
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    /// Unix timestamp of the first order, starts immediately when not set
+    pub start_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceBasedParams {
+    /// Amount of input mint deposited, have to factor in the token decimals.
+    pub deposit_amount: u64,
+    /// USDC value the position should increase by every interval
+    pub increment_usdc_value: u64,
+    /// Seconds between two orders
+    pub interval: u64,
+    /// Unix timestamp of the first order, starts immediately when not set
+    pub start_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RecurringParams {
+    Time(TimeBasedParams),
+    Price(PriceBasedParams),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateRecurringRequest {
+    #[serde(with = "field_as_string")]
+    pub user: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub input_mint: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub output_mint: Pubkey,
+    pub params: RecurringParams,
+}
+
+/// Unsigned transaction to sign then pass to [`JupiterSwapApiClient::recurring_execute`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecurringTransactionResponse {
+    pub request_id: String,
+    #[serde(with = "base64_serialize_deserialize")]
+    pub transaction: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum RecurringType {
+    Time,
+    Price,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelRecurringRequest {
+    #[serde(with = "field_as_string")]
+    pub order: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub user: Pubkey,
+    pub recurring_type: RecurringType,
+}
+
+/// Deposit more input mint into a price based position
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceDepositRequest {
+    #[serde(with = "field_as_string")]
+    pub order: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum WithdrawMint {
+    In,
+    Out,
+}
+
+/// Withdraw from a price based position
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceWithdrawRequest {
+    #[serde(with = "field_as_string")]
+    pub order: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub user: Pubkey,
+    pub input_or_output: WithdrawMint,
+    /// Withdraws everything when not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecurringExecuteRequest {
+    #[serde(with = "base64_serialize_deserialize")]
+    pub signed_transaction: Vec<u8>,
+    pub request_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
+pub enum RecurringExecuteStatus {
+    Success,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecurringExecuteResponse {
+    pub status: RecurringExecuteStatus,
+    pub signature: Option<String>,
+    /// Account of the order, set when creating one
+    pub order: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum RecurringOrderStatus {
+    #[default]
+    Active,
+    History,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum RecurringTypeFilter {
+    Time,
+    Price,
+    #[default]
+    All,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRecurringOrdersRequest {
+    #[serde(with = "field_as_string")]
+    pub user: Pubkey,
+    pub order_status: RecurringOrderStatus,
+    pub recurring_type: RecurringTypeFilter,
+    /// Starts at 1
+    pub page: Option<u32>,
+    pub include_failed_tx: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecurringOrder {
+    #[serde(with = "field_as_string")]
+    pub user_pubkey: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub order_key: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub input_mint: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub output_mint: Pubkey,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Strategy specific fields, which differ between time and price based orders
+    #[serde(flatten)]
+    pub details: HashMap<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRecurringOrdersResponse {
+    #[serde(default)]
+    pub time: Vec<RecurringOrder>,
+    #[serde(default)]
+    pub price: Vec<RecurringOrder>,
+    #[serde(default)]
+    pub all: Vec<RecurringOrder>,
+    pub page: u32,
+    pub total_pages: u32,
+}
+
+impl JupiterSwapApiClient {
+    /// POST /createOrder
+    pub async fn create_recurring(
+        &self,
+        create_request: &CreateRecurringRequest,
+    ) -> Result<RecurringTransactionResponse, ClientError> {
+        self.post_recurring(
+            Endpoint::RecurringCreateOrder,
+            "createOrder",
+            create_request,
+        )
+        .await
+    }
+
+    /// POST /cancelOrder
+    pub async fn cancel_recurring(
+        &self,
+        cancel_request: &CancelRecurringRequest,
+    ) -> Result<RecurringTransactionResponse, ClientError> {
+        self.post_recurring(
+            Endpoint::RecurringCancelOrder,
+            "cancelOrder",
+            cancel_request,
+        )
+        .await
+    }
+
+    /// POST /priceDeposit
+    pub async fn deposit_recurring(
+        &self,
+        deposit_request: &PriceDepositRequest,
+    ) -> Result<RecurringTransactionResponse, ClientError> {
+        self.post_recurring(
+            Endpoint::RecurringPriceDeposit,
+            "priceDeposit",
+            deposit_request,
+        )
+        .await
+    }
+
+    /// POST /priceWithdraw
+    pub async fn withdraw_recurring(
+        &self,
+        withdraw_request: &PriceWithdrawRequest,
+    ) -> Result<RecurringTransactionResponse, ClientError> {
+        self.post_recurring(
+            Endpoint::RecurringPriceWithdraw,
+            "priceWithdraw",
+            withdraw_request,
+        )
+        .await
+    }
+
+    /// POST /execute, broadcast a signed recurring transaction
+    pub async fn recurring_execute(
+        &self,
+        execute_request: &RecurringExecuteRequest,
+    ) -> Result<RecurringExecuteResponse, ClientError> {
+        self.post_recurring(Endpoint::RecurringExecute, "execute", execute_request)
+            .await
+    }
+
+    /// GET /getRecurringOrders
+    pub async fn get_recurring_orders(
+        &self,
+        get_orders_request: &GetRecurringOrdersRequest,
+    ) -> Result<GetRecurringOrdersResponse, ClientError> {
+        self.get(
+            Endpoint::RecurringGetOrders,
+            &format!("{}/getRecurringOrders", self.recurring_base_path),
+            get_orders_request,
+        )
+        .await
+    }
+
+    /// Orders of every page of [`Self::get_recurring_orders`] starting at `get_orders_request.page`,
+    /// fetched lazily
+    pub fn get_recurring_orders_stream(
+        &self,
+        get_orders_request: GetRecurringOrdersRequest,
+    ) -> impl Stream<Item = Result<RecurringOrder, ClientError>> {
+        let client = self.clone();
+        paginate(get_orders_request.page.unwrap_or(1), move |page| {
+            let client = client.clone();
+            let get_orders_request = GetRecurringOrdersRequest {
+                page: Some(page),
+                ..get_orders_request.clone()
+            };
+            async move {
+                let response = client.get_recurring_orders(&get_orders_request).await?;
+                let orders = response
+                    .time
+                    .into_iter()
+                    .chain(response.price)
+                    .chain(response.all)
+                    .collect();
+                Ok(Paginated::new(orders, response.page, response.total_pages))
+            }
+        })
+    }
+
+    async fn post_recurring<B: Serialize, R: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: Endpoint,
+        path: &str,
+        body: &B,
+    ) -> Result<R, ClientError> {
+        self.post(
+            endpoint,
+            &format!("{}/{path}", self.recurring_base_path),
+            body,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::mints::{NATIVE_MINT, USDC_MINT};
+
+    fn transaction_response() -> ResponseTemplate {
+        ResponseTemplate::new(200)
+            .set_body_json(json!({ "requestId": "r1", "transaction": "AQID" }))
+    }
+
+    fn recurring_client(server: &MockServer) -> JupiterSwapApiClient {
+        let mut client = JupiterSwapApiClient::new(server.uri(), None);
+        client.recurring_base_path = server.uri();
+        client
+    }
+
+    fn order_fixture(order_key: Pubkey, user: Pubkey) -> Value {
+        json!({
+            "userPubkey": user.to_string(),
+            "orderKey": order_key.to_string(),
+            "inputMint": USDC_MINT.to_string(),
+            "outputMint": NATIVE_MINT.to_string(),
+            "createdAt": "2025-03-01T12:00:00Z",
+            "updatedAt": "2025-03-01T12:05:00Z",
+            "inDeposited": "100000000",
+            "cycleFrequency": 86400,
+            "trades": []
+        })
+    }
+
+    #[test]
+    fn recurring_order_keeps_strategy_fields() {
+        let order_key = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let order: RecurringOrder = serde_json::from_value(order_fixture(order_key, user)).unwrap();
+        assert_eq!(order.order_key, order_key);
+        assert_eq!(order.user_pubkey, user);
+        assert_eq!(order.input_mint, USDC_MINT);
+        assert_eq!(order.details["cycleFrequency"], 86400);
+        assert_eq!(order.details["inDeposited"], "100000000");
+        assert!(!order.details.contains_key("orderKey"));
+    }
+
+    #[test]
+    fn execute_response_fixtures() {
+        let created: RecurringExecuteResponse = serde_json::from_value(json!({
+            "status": "Success",
+            "signature": "signature",
+            "order": "order",
+            "error": null
+        }))
+        .unwrap();
+        assert_eq!(created.status, RecurringExecuteStatus::Success);
+        assert_eq!(created.order.as_deref(), Some("order"));
+
+        let failed: RecurringExecuteResponse = serde_json::from_value(json!({
+            "status": "Failed",
+            "error": "Insufficient balance"
+        }))
+        .unwrap();
+        assert_eq!(
+            failed,
+            RecurringExecuteResponse {
+                status: RecurringExecuteStatus::Failed,
+                signature: None,
+                order: None,
+                error: Some("Insufficient balance".to_string()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn time_based_order_is_created() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/createOrder"))
+            .respond_with(transaction_response())
+            .mount(&server)
+            .await;
+        let user = Pubkey::new_unique();
+
+        let response = recurring_client(&server)
+            .create_recurring(&CreateRecurringRequest {
+                user,
+                input_mint: USDC_MINT,
+                output_mint: NATIVE_MINT,
+                params: RecurringParams::Time(TimeBasedParams {
+                    in_amount: 100_000_000,
+                    number_of_orders: 10,
+                    interval: 86400,
+                    ..TimeBasedParams::default()
+                }),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            RecurringTransactionResponse {
+                request_id: "r1".to_string(),
+                transaction: vec![1, 2, 3],
+            }
+        );
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(
+            requests[0].body_json::<Value>().unwrap(),
+            json!({
+                "user": user.to_string(),
+                "inputMint": USDC_MINT.to_string(),
+                "outputMint": NATIVE_MINT.to_string(),
+                "params": {
+                    "time": {
+                        "inAmount": 100000000,
+                        "numberOfOrders": 10,
+                        "interval": 86400,
+                        "minPrice": null,
+                        "maxPrice": null,
+                        "startAt": null
+                    }
+                }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn order_is_cancelled() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/cancelOrder"))
+            .respond_with(transaction_response())
+            .mount(&server)
+            .await;
+        let order = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+
+        let response = recurring_client(&server)
+            .cancel_recurring(&CancelRecurringRequest {
+                order,
+                user,
+                recurring_type: RecurringType::Price,
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.transaction, [1, 2, 3]);
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(
+            requests[0].body_json::<Value>().unwrap(),
+            json!({
+                "order": order.to_string(),
+                "user": user.to_string(),
+                "recurringType": "price"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn orders_of_every_page_are_listed() {
+        let server = MockServer::start().await;
+        let user = Pubkey::new_unique();
+        let orders = [Pubkey::new_unique(), Pubkey::new_unique()];
+        for (page, order_key) in (1..).zip(orders) {
+            Mock::given(method("GET"))
+                .and(path("/getRecurringOrders"))
+                .and(query_param("user", user.to_string()))
+                .and(query_param("orderStatus", "history"))
+                .and(query_param("recurringType", "time"))
+                .and(query_param("page", page.to_string()))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "time": [order_fixture(order_key, user)],
+                    "page": page,
+                    "totalPages": 2
+                })))
+                .mount(&server)
+                .await;
+        }
+
+        let request = GetRecurringOrdersRequest {
+            user,
+            order_status: RecurringOrderStatus::History,
+            recurring_type: RecurringTypeFilter::Time,
+            ..GetRecurringOrdersRequest::default()
+        };
+        let listed: Vec<_> = recurring_client(&server)
+            .get_recurring_orders_stream(request)
+            .map(|order| order.unwrap().order_key)
+            .collect()
+            .await;
+        assert_eq!(listed, orders);
+    }
+}
@@ -0,0 +1,63 @@
+//! Per request options, overriding the client configuration for a single call
+//!
+
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequestOptions {
+    /// Api key sent instead of the client api key
+    pub api_key: Option<String>,
+    /// Send no api key at all, e.g. to check whether a 401 or 403 is related to the key
+    pub without_api_key: bool,
+    /// Sent as `x-idempotency-key` so retries of the same logical request can be deduplicated server side,
+    /// by Jupiter or a proxy in front of it
+    pub idempotency_key: Option<String>,
+    /// Sent on top of the client headers, replacing the ones with the same name including the api key header
+    pub headers: HeaderMap,
+    /// Appended to the query string of the request
+    pub query: Vec<(String, String)>,
+    /// Timeout of the whole request, overriding the client one
+    pub timeout: Option<Duration>,
+    /// Always fetch the quote, e.g. the one about to be swapped, rather than reuse a cached one.
+    /// The fresh quote still updates the cache. Only relevant with the `quote-cache` feature.
+    pub bypass_cache: bool,
+}
+
+impl RequestOptions {
+    pub fn api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    pub fn without_api_key(mut self) -> Self {
+        self.without_api_key = true;
+        self
+    }
+
+    pub fn idempotency_key(mut self, idempotency_key: String) -> Self {
+        self.idempotency_key = Some(idempotency_key);
+        self
+    }
+
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn query(mut self, key: String, value: String) -> Self {
+        self.query.push((key, value));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn bypass_cache(mut self) -> Self {
+        self.bypass_cache = true;
+        self
+    }
+}
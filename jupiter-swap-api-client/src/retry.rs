@@ -0,0 +1,184 @@
+//! Retries of requests failing with a timeout, a connection error, 429 or 5xx,
+//! see [`JupiterSwapApiClientBuilder::retry_policy`](crate::builder::JupiterSwapApiClientBuilder::retry_policy)
+//!
+//! Delays grow exponentially from `base_delay` up to `max_delay` and are jittered so that clients
+//! retrying after a shared outage do not all hit the API at the same time.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::random::random_fraction;
+
+/// Turns the exponential backoff of a retry into the delay actually waited
+pub trait JitterStrategy: Send + Sync {
+    /// The result must not exceed `backoff`
+    fn jitter(&self, backoff: Duration) -> Duration;
+}
+
+impl fmt::Debug for dyn JitterStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("JitterStrategy")
+    }
+}
+
+/// Wait the backoff as is
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoJitter;
+
+impl JitterStrategy for NoJitter {
+    fn jitter(&self, backoff: Duration) -> Duration {
+        backoff
+    }
+}
+
+/// Wait a random delay in `[0, backoff]`, the default
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FullJitter;
+
+impl JitterStrategy for FullJitter {
+    fn jitter(&self, backoff: Duration) -> Duration {
+        backoff.mul_f64(random_fraction())
+    }
+}
+
+/// Wait half the backoff plus a random delay in `[0, backoff / 2]`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EqualJitter;
+
+impl JitterStrategy for EqualJitter {
+    fn jitter(&self, backoff: Duration) -> Duration {
+        let half = backoff / 2;
+        half + (backoff - half).mul_f64(random_fraction())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries on top of the first attempt, `0` disables retries
+    pub max_retries: u32,
+    /// Backoff of the first retry, doubled on every retry
+    pub base_delay: Duration,
+    /// Upper bound of the backoff
+    pub max_delay: Duration,
+    pub jitter: Arc<dyn JitterStrategy>,
+}
+
+impl Default for RetryPolicy {
+    /// 3 retries from 100ms up to 5s with full jitter
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: Arc::new(FullJitter),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// No retries, the policy of clients unless configured otherwise
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: Arc<dyn JitterStrategy>) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Backoff before retry `retry`, starting at 0, before jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay)
+    }
+
+    /// Delay waited before retry `retry`, starting at 0, never above `max_delay`
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+        self.jitter.jitter(backoff).min(backoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(jitter: Arc<dyn JitterStrategy>) -> RetryPolicy {
+        RetryPolicy::default()
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_secs(1))
+            .jitter(jitter)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_delay() {
+        let policy = policy(Arc::new(NoJitter));
+        let backoffs: Vec<u64> = (0..6)
+            .map(|retry| policy.backoff(retry).as_millis() as u64)
+            .collect();
+        assert_eq!(backoffs, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn full_jitter_stays_within_backoff() {
+        let policy = policy(Arc::new(FullJitter));
+        for retry in 0..8 {
+            let backoff = policy.backoff(retry);
+            for _ in 0..1_000 {
+                let delay = policy.delay(retry);
+                assert!(delay <= backoff, "{delay:?} above {backoff:?}");
+                assert!(delay <= policy.max_delay);
+            }
+        }
+        // Actually random
+        let delays: Vec<Duration> = (0..100).map(|_| policy.delay(4)).collect();
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+
+    #[test]
+    fn equal_jitter_waits_at_least_half_the_backoff() {
+        let policy = policy(Arc::new(EqualJitter));
+        for retry in 0..8 {
+            let backoff = policy.backoff(retry);
+            for _ in 0..1_000 {
+                let delay = policy.delay(retry);
+                assert!(delay >= backoff / 2 && delay <= backoff);
+            }
+        }
+    }
+
+    #[test]
+    fn delay_is_capped_whatever_the_jitter() {
+        struct Doubling;
+
+        impl JitterStrategy for Doubling {
+            fn jitter(&self, backoff: Duration) -> Duration {
+                backoff * 2
+            }
+        }
+
+        let policy = policy(Arc::new(Doubling));
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(10), Duration::from_secs(1));
+    }
+}
@@ -1,15 +1,28 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
 use serde::{Deserialize, Serialize};
-use solana_sdk::pubkey::Pubkey;
+use solana_pubkey::{ParsePubkeyError, Pubkey};
+use thiserror::Error;
 
-use crate::serde_helpers::field_as_string;
+use crate::{
+    mints::{NATIVE_MINT, USDC_MINT, USDT_MINT},
+    serde_helpers::field_as_string,
+};
 
 /// Topologically sorted DAG with additional metadata for rendering
 pub type RoutePlanWithMetadata = Vec<RoutePlanStep>;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct RoutePlanStep {
     pub swap_info: SwapInfo,
+    /// Percentage of the amount available in `swap_info.input_mint` routed through this step
     pub percent: u8,
 }
 
@@ -18,7 +31,8 @@ pub struct RoutePlanStep {
 pub struct SwapInfo {
     #[serde(with = "field_as_string")]
     pub amm_key: Pubkey,
-    pub label: String,
+    /// AMM of the step, suitable for grouping by AMM
+    pub label: AmmLabel,
     #[serde(with = "field_as_string")]
     pub input_mint: Pubkey,
     #[serde(with = "field_as_string")]
@@ -31,6 +45,434 @@ pub struct SwapInfo {
     pub out_amount: u64,
     #[serde(with = "field_as_string")]
     pub fee_amount: u64,
-    #[serde(with = "field_as_string")]
-    pub fee_mint: Pubkey,
+    /// Mint the fee is taken in, suitable for grouping fees by token
+    pub fee_mint: FeeMint,
+}
+
+/// Label of the AMM a route plan step goes through
+///
+/// Labels this crate does not know about are kept as [`AmmLabel::Other`], and [`fmt::Display`]
+/// always renders the label exactly as the API returned it.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
+pub enum AmmLabel {
+    Raydium,
+    RaydiumClmm,
+    RaydiumCp,
+    Whirlpool,
+    OrcaV2,
+    Meteora,
+    MeteoraDlmm,
+    Phoenix,
+    OpenbookV2,
+    LifinityV2,
+    Saber,
+    PumpFunAmm,
+    SolFi,
+    Other(String),
+}
+
+impl AmmLabel {
+    const KNOWN: [(AmmLabel, &'static str); 13] = [
+        (AmmLabel::Raydium, "Raydium"),
+        (AmmLabel::RaydiumClmm, "Raydium CLMM"),
+        (AmmLabel::RaydiumCp, "Raydium CP"),
+        (AmmLabel::Whirlpool, "Whirlpool"),
+        (AmmLabel::OrcaV2, "Orca V2"),
+        (AmmLabel::Meteora, "Meteora"),
+        (AmmLabel::MeteoraDlmm, "Meteora DLMM"),
+        (AmmLabel::Phoenix, "Phoenix"),
+        (AmmLabel::OpenbookV2, "OpenBook V2"),
+        (AmmLabel::LifinityV2, "Lifinity V2"),
+        (AmmLabel::Saber, "Saber"),
+        (AmmLabel::PumpFunAmm, "Pump.fun Amm"),
+        (AmmLabel::SolFi, "SolFi"),
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            AmmLabel::Other(label) => label,
+            known => Self::KNOWN
+                .iter()
+                .find(|(amm_label, _)| amm_label == known)
+                .map(|(_, label)| *label)
+                .expect("every known label has a string"),
+        }
+    }
+}
+
+impl FromStr for AmmLabel {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::KNOWN
+            .into_iter()
+            .find(|(_, label)| *label == s)
+            .map(|(amm_label, _)| amm_label)
+            .unwrap_or_else(|| AmmLabel::Other(s.to_string())))
+    }
+}
+
+impl fmt::Display for AmmLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for AmmLabel {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for AmmLabel {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let label = String::deserialize(deserializer)?;
+        Ok(label.parse().unwrap_or_else(|e: Infallible| match e {}))
+    }
+}
+
+impl Default for AmmLabel {
+    fn default() -> Self {
+        Self::Other(String::new())
+    }
+}
+
+/// Mint a route plan step takes its fee in
+///
+/// Equality, ordering and hashing are those of the mint address, so `FeeMint::Other` of a well known mint
+/// equals its variant, and [`fmt::Display`] renders the address.
+#[derive(Debug, Clone, Copy)]
+pub enum FeeMint {
+    Sol,
+    Usdc,
+    Usdt,
+    Other(Pubkey),
+}
+
+impl FeeMint {
+    const KNOWN: [(FeeMint, Pubkey); 3] = [
+        (FeeMint::Sol, NATIVE_MINT),
+        (FeeMint::Usdc, USDC_MINT),
+        (FeeMint::Usdt, USDT_MINT),
+    ];
+
+    pub fn pubkey(&self) -> Pubkey {
+        match self {
+            FeeMint::Sol => NATIVE_MINT,
+            FeeMint::Usdc => USDC_MINT,
+            FeeMint::Usdt => USDT_MINT,
+            FeeMint::Other(pubkey) => *pubkey,
+        }
+    }
+}
+
+impl Default for FeeMint {
+    fn default() -> Self {
+        Self::Other(Pubkey::default())
+    }
+}
+
+impl From<Pubkey> for FeeMint {
+    fn from(pubkey: Pubkey) -> Self {
+        Self::KNOWN
+            .into_iter()
+            .find(|(_, mint)| *mint == pubkey)
+            .map_or(FeeMint::Other(pubkey), |(fee_mint, _)| fee_mint)
+    }
+}
+
+impl From<FeeMint> for Pubkey {
+    fn from(fee_mint: FeeMint) -> Self {
+        fee_mint.pubkey()
+    }
+}
+
+impl PartialEq for FeeMint {
+    fn eq(&self, other: &Self) -> bool {
+        self.pubkey() == other.pubkey()
+    }
+}
+
+impl Eq for FeeMint {}
+
+impl PartialEq<Pubkey> for FeeMint {
+    fn eq(&self, other: &Pubkey) -> bool {
+        self.pubkey() == *other
+    }
+}
+
+impl PartialOrd for FeeMint {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FeeMint {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.pubkey().cmp(&other.pubkey())
+    }
+}
+
+impl Hash for FeeMint {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pubkey().hash(state);
+    }
+}
+
+impl FromStr for FeeMint {
+    type Err = ParsePubkeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Pubkey::from_str(s).map(Into::into)
+    }
+}
+
+impl fmt::Display for FeeMint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pubkey().fmt(f)
+    }
+}
+
+impl Serialize for FeeMint {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FeeMint {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fee_mint = String::deserialize(deserializer)?;
+        fee_mint.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Allowed deviation, in percentage points, from 100% when summing the splits of an input mint
+pub const SPLIT_PERCENT_TOLERANCE: u16 = 1;
+
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+pub enum RoutePlanError {
+    #[error("Route plan is empty")]
+    Empty,
+    #[error("Splits from {input_mint} sum to {total}%, expected 100%")]
+    InvalidSplitTotal { input_mint: Pubkey, total: u16 },
+    #[error("Step {index} swaps from {input_mint} which is neither the route input mint nor the output of a previous step")]
+    DisconnectedHop { index: usize, input_mint: Pubkey },
+}
+
+pub trait RoutePlanValidation {
+    /// Check that the splits of every input mint sum to 100% (within [`SPLIT_PERCENT_TOLERANCE`])
+    /// and that every step consumes either the route input mint or the output of a previous step
+    fn validate_splits(&self) -> Result<(), RoutePlanError>;
+}
+
+impl RoutePlanValidation for [RoutePlanStep] {
+    fn validate_splits(&self) -> Result<(), RoutePlanError> {
+        let first_step = self.first().ok_or(RoutePlanError::Empty)?;
+
+        let mut available_mints = vec![first_step.swap_info.input_mint];
+        // Keep the mints in order of appearance so errors are deterministic
+        let mut input_mints = Vec::new();
+        let mut totals: HashMap<Pubkey, u16> = HashMap::new();
+        for (index, step) in self.iter().enumerate() {
+            let input_mint = step.swap_info.input_mint;
+            if !available_mints.contains(&input_mint) {
+                return Err(RoutePlanError::DisconnectedHop { index, input_mint });
+            }
+            if !available_mints.contains(&step.swap_info.output_mint) {
+                available_mints.push(step.swap_info.output_mint);
+            }
+
+            let total = totals.entry(input_mint).or_insert_with(|| {
+                input_mints.push(input_mint);
+                0
+            });
+            // Saturating as a malformed plan can have enough steps to overflow
+            *total = total.saturating_add(u16::from(step.percent));
+        }
+
+        for input_mint in input_mints {
+            let total = totals[&input_mint];
+            if total.abs_diff(100) > SPLIT_PERCENT_TOLERANCE {
+                return Err(RoutePlanError::InvalidSplitTotal { input_mint, total });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pinned for downstream caches and comparisons
+    static_assertions::assert_impl_all!(RoutePlanStep: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(SwapInfo: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(AmmLabel: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(FeeMint: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(RoutePlanError: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+
+    fn step(input_mint: Pubkey, output_mint: Pubkey, percent: u8) -> RoutePlanStep {
+        RoutePlanStep {
+            swap_info: SwapInfo {
+                input_mint,
+                output_mint,
+                ..SwapInfo::default()
+            },
+            percent,
+        }
+    }
+
+    #[test]
+    fn amm_labels_round_trip() {
+        for (amm_label, label) in AmmLabel::KNOWN {
+            assert_eq!(label.parse::<AmmLabel>(), Ok(amm_label.clone()));
+            assert_eq!(amm_label.to_string(), label);
+        }
+        let unknown: AmmLabel = "Brand New AMM".parse().unwrap();
+        assert_eq!(unknown, AmmLabel::Other("Brand New AMM".to_string()));
+        assert_eq!(unknown.to_string(), "Brand New AMM");
+        // Labels are matched exactly, other casings are kept as is
+        assert_eq!(
+            "raydium".parse::<AmmLabel>(),
+            Ok(AmmLabel::Other("raydium".to_string()))
+        );
+    }
+
+    #[test]
+    fn fee_mints_compare_by_address() {
+        assert_eq!(FeeMint::from(NATIVE_MINT), FeeMint::Sol);
+        assert!(matches!(FeeMint::from(USDC_MINT), FeeMint::Usdc));
+        assert_eq!(FeeMint::Other(USDT_MINT), FeeMint::Usdt);
+        assert_eq!(FeeMint::Usdt, USDT_MINT);
+        let other = Pubkey::new_unique();
+        assert_eq!(FeeMint::from(other), FeeMint::Other(other));
+        assert_ne!(FeeMint::from(other), FeeMint::Sol);
+
+        let mut fees: HashMap<FeeMint, u64> = HashMap::new();
+        for fee_mint in [
+            FeeMint::Sol,
+            FeeMint::Other(NATIVE_MINT),
+            FeeMint::from(other),
+        ] {
+            *fees.entry(fee_mint).or_default() += 1;
+        }
+        assert_eq!(fees[&FeeMint::Sol], 2);
+        assert_eq!(fees[&FeeMint::Other(other)], 1);
+    }
+
+    #[test]
+    fn swap_info_deserializes_typed() {
+        let unknown_mint = Pubkey::new_unique();
+        let json = serde_json::json!({
+            "ammKey": Pubkey::new_unique().to_string(),
+            "label": "Meteora DLMM",
+            "inputMint": NATIVE_MINT.to_string(),
+            "outputMint": unknown_mint.to_string(),
+            "inAmount": "1000",
+            "outAmount": "2000",
+            "feeAmount": "3",
+            "feeMint": USDC_MINT.to_string(),
+        });
+        let swap_info: SwapInfo = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(swap_info.label, AmmLabel::MeteoraDlmm);
+        assert!(matches!(swap_info.fee_mint, FeeMint::Usdc));
+        assert_eq!(serde_json::to_value(&swap_info).unwrap(), json);
+
+        let mut json = json;
+        json["label"] = "Future AMM".into();
+        json["feeMint"] = unknown_mint.to_string().into();
+        let swap_info: SwapInfo = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(swap_info.label, AmmLabel::Other("Future AMM".to_string()));
+        assert_eq!(swap_info.fee_mint, FeeMint::Other(unknown_mint));
+        assert_eq!(serde_json::to_value(&swap_info).unwrap(), json);
+
+        json["feeMint"] = "not a mint".into();
+        assert!(serde_json::from_value::<SwapInfo>(json).is_err());
+    }
+
+    #[test]
+    fn validates_split_route() {
+        let [a, b, c] = [(); 3].map(|_| Pubkey::new_unique());
+        let route_plan = [step(a, b, 60), step(a, c, 40), step(c, b, 100)];
+        assert_eq!(route_plan.validate_splits(), Ok(()));
+        // Within the tolerance
+        let route_plan = [step(a, b, 33), step(a, c, 66), step(c, b, 100)];
+        assert_eq!(route_plan.validate_splits(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_empty_route() {
+        assert_eq!(
+            Vec::<RoutePlanStep>::new().validate_splits(),
+            Err(RoutePlanError::Empty)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_split_total() {
+        let [a, b, c] = [(); 3].map(|_| Pubkey::new_unique());
+        let route_plan = [step(a, b, 60), step(a, c, 60), step(c, b, 100)];
+        assert_eq!(
+            route_plan.validate_splits(),
+            Err(RoutePlanError::InvalidSplitTotal {
+                input_mint: a,
+                total: 120
+            })
+        );
+    }
+
+    #[test]
+    fn saturates_split_total() {
+        let [a, b] = [(); 2].map(|_| Pubkey::new_unique());
+        let route_plan = vec![step(a, b, u8::MAX); 300];
+        assert_eq!(
+            route_plan.validate_splits(),
+            Err(RoutePlanError::InvalidSplitTotal {
+                input_mint: a,
+                total: u16::MAX
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_disconnected_hop() {
+        let [a, b, c, d] = [(); 4].map(|_| Pubkey::new_unique());
+        let route_plan = [step(a, b, 100), step(c, d, 100)];
+        assert_eq!(
+            route_plan.validate_splits(),
+            Err(RoutePlanError::DisconnectedHop {
+                index: 1,
+                input_mint: c
+            })
+        );
+    }
+
+    #[test]
+    fn route_plan_step_round_trips() {
+        for (label, fee_mint) in [
+            ("Whirlpool", FeeMint::Usdc),
+            ("Some New Amm", FeeMint::Other(Pubkey::new_unique())),
+        ] {
+            let step = RoutePlanStep {
+                swap_info: SwapInfo {
+                    amm_key: Pubkey::new_unique(),
+                    label: label.parse().unwrap(),
+                    input_mint: Pubkey::new_unique(),
+                    output_mint: Pubkey::new_unique(),
+                    in_amount: u64::MAX,
+                    out_amount: 1,
+                    fee_amount: 0,
+                    fee_mint,
+                },
+                percent: 100,
+            };
+            let serialized = serde_json::to_value(&step).unwrap();
+            assert_eq!(serialized["swapInfo"]["label"], label);
+            assert_eq!(serialized["swapInfo"]["inAmount"], u64::MAX.to_string());
+            assert_eq!(
+                serde_json::from_value::<RoutePlanStep>(serialized).unwrap(),
+                step
+            );
+        }
+    }
 }
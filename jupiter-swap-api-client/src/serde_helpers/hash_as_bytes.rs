@@ -0,0 +1,54 @@
+use {
+    serde::{de, Deserialize, Deserializer, Serialize, Serializer},
+    solana_hash::{Hash, HASH_BYTES},
+};
+
+/// Serialize a hash as its array of bytes, as done by the swap instructions blockhash
+pub fn serialize<S>(hash: &Hash, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    hash.to_bytes().serialize(serializer)
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Hash, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let bytes: Vec<u8> = Vec::deserialize(deserializer)?;
+    let bytes: [u8; HASH_BYTES] = bytes.try_into().map_err(|bytes: Vec<u8>| {
+        de::Error::custom(format!(
+            "expected {HASH_BYTES} bytes for a hash, got {}",
+            bytes.len()
+        ))
+    })?;
+    Ok(Hash::new_from_array(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Blockhash(#[serde(with = "super")] Hash);
+
+    #[test]
+    fn hash_round_trips_as_bytes() {
+        let hash = Hash::new_from_array(std::array::from_fn(|index| index as u8));
+        let value = serde_json::to_value(Blockhash(hash)).unwrap();
+        assert_eq!(value, json!((0..32).collect::<Vec<u8>>()));
+        assert_eq!(
+            serde_json::from_value::<Blockhash>(value).unwrap(),
+            Blockhash(hash)
+        );
+    }
+
+    #[test]
+    fn wrong_length_is_rejected() {
+        let error = serde_json::from_value::<Blockhash>(json!([1, 2, 3])).unwrap_err();
+        assert_eq!(error.to_string(), "expected 32 bytes for a hash, got 3");
+        assert!(serde_json::from_value::<Blockhash>(json!(vec![256; 32])).is_err());
+    }
+}
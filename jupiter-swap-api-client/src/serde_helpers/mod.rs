@@ -1,2 +1,6 @@
 pub mod field_as_string;
+pub mod hash_as_bytes;
+pub mod option_base64;
 pub mod option_field_as_string;
+pub mod vec_base64;
+pub mod vec_field_as_string;
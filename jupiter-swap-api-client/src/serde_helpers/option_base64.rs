@@ -0,0 +1,30 @@
+use {
+    base64::{engine::general_purpose::STANDARD, Engine},
+    serde::{de, Deserialize, Deserializer, Serialize, Serializer},
+};
+
+pub fn serialize<S>(v: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if let Some(v) = v {
+        STANDARD.encode(v).serialize(serializer)
+    } else {
+        serializer.serialize_none()
+    }
+}
+
+/// Both a missing value and an empty string deserialize to `None`
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let opt: Option<String> = Option::deserialize(deserializer)?;
+    match opt {
+        Some(s) if !s.is_empty() => STANDARD
+            .decode(s)
+            .map(Some)
+            .map_err(|e| de::Error::custom(format!("base64 decoding error: {:?}", e))),
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,30 @@
+use {
+    base64::{engine::general_purpose::STANDARD, Engine},
+    serde::{de, ser::SerializeSeq, Deserialize, Deserializer, Serializer},
+};
+
+pub fn serialize<S>(v: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut seq = serializer.serialize_seq(Some(v.len()))?;
+    for bytes in v {
+        seq.serialize_element(&STANDARD.encode(bytes))?;
+    }
+    seq.end()
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let strings: Vec<String> = Vec::deserialize(deserializer)?;
+    strings
+        .into_iter()
+        .map(|s| {
+            STANDARD
+                .decode(s)
+                .map_err(|e| de::Error::custom(format!("base64 decoding error: {:?}", e)))
+        })
+        .collect()
+}
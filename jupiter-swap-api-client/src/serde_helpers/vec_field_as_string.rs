@@ -0,0 +1,32 @@
+use {
+    serde::{de, ser::SerializeSeq, Deserialize, Deserializer, Serializer},
+    std::str::FromStr,
+};
+
+pub fn serialize<T, S>(t: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: ToString,
+    S: Serializer,
+{
+    let mut seq = serializer.serialize_seq(Some(t.len()))?;
+    for item in t {
+        seq.serialize_element(&item.to_string())?;
+    }
+    seq.end()
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    T: FromStr,
+    D: Deserializer<'de>,
+    <T as FromStr>::Err: std::fmt::Debug,
+{
+    let strings: Vec<String> = Vec::deserialize(deserializer)?;
+    strings
+        .into_iter()
+        .map(|s| {
+            s.parse()
+                .map_err(|e| de::Error::custom(format!("Parse error: {:?}", e)))
+        })
+        .collect()
+}
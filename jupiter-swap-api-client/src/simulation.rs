@@ -0,0 +1,251 @@
+//! Simulation of swap transactions, the simulation itself requires the `rpc` feature
+//!
+//! The logs helpers work on the logs of any transaction, e.g. from `getTransaction`.
+
+use solana_pubkey::{pubkey, Pubkey};
+
+/// Jupiter aggregator v6 program, invoked by the swap transactions and instructions of the Swap API
+pub const JUPITER_PROGRAM_ID: Pubkey = pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+
+const SLIPPAGE_TOLERANCE_EXCEEDED: u32 = 6001;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum JupiterProgramError {
+    /// The output amount is below the slippage threshold of the quote, quote again or raise the slippage
+    SlippageToleranceExceeded,
+    /// Custom error code without a dedicated variant
+    Other(u32),
+}
+
+impl JupiterProgramError {
+    pub fn from_code(code: u32) -> Self {
+        match code {
+            SLIPPAGE_TOLERANCE_EXCEEDED => Self::SlippageToleranceExceeded,
+            code => Self::Other(code),
+        }
+    }
+}
+
+/// Error the Jupiter program failed with according to `logs`,
+/// `None` when it did not fail or failed without a custom error code
+pub fn jupiter_error_from_logs(logs: &[String]) -> Option<JupiterProgramError> {
+    let prefix = format!("Program {JUPITER_PROGRAM_ID} failed: custom program error: 0x");
+    logs.iter()
+        .find_map(|log| log.strip_prefix(&prefix))
+        .and_then(|code| u32::from_str_radix(code.trim(), 16).ok())
+        .map(JupiterProgramError::from_code)
+}
+
+#[cfg(feature = "rpc")]
+mod rpc {
+    use solana_client::{
+        nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig,
+    };
+    use solana_sdk::transaction::{TransactionError, VersionedTransaction};
+
+    use super::{jupiter_error_from_logs, JupiterProgramError};
+    use crate::{
+        swap::{SwapRequest, SwapResponse},
+        ClientError, ErrorKind, JupiterSwapApiClient,
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct SimulationResult {
+        pub units_consumed: Option<u64>,
+        pub logs: Vec<String>,
+        /// `None` when the simulation succeeded
+        pub error: Option<TransactionError>,
+        /// Decoded from `logs` when the Jupiter program failed
+        pub jupiter_error: Option<JupiterProgramError>,
+    }
+
+    impl SimulationResult {
+        pub fn is_success(&self) -> bool {
+            self.error.is_none()
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct SimulatedSwap {
+        pub response: SwapResponse,
+        pub simulation: SimulationResult,
+    }
+
+    impl JupiterSwapApiClient {
+        /// POST /swap then simulate the returned transaction as is, against its own blockhash and without
+        /// verifying signatures. A failed simulation is returned in [`SimulationResult::error`], not as an error.
+        /// Only `swap_transaction` is simulated, which fails if it depends on `additional_transactions`.
+        pub async fn swap_and_simulate(
+            &self,
+            swap_request: &SwapRequest,
+            rpc: &RpcClient,
+        ) -> Result<SimulatedSwap, ClientError> {
+            let response = self.swap(swap_request, None).await?;
+            let transaction: VersionedTransaction =
+                bincode::deserialize(&response.swap_transaction)
+                    .map_err(ErrorKind::InvalidTransaction)?;
+            let result = rpc
+                .simulate_transaction_with_config(
+                    &transaction,
+                    RpcSimulateTransactionConfig {
+                        sig_verify: false,
+                        replace_recent_blockhash: false,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .map_err(|e| ErrorKind::RpcError(Box::new(e)))?
+                .value;
+            let logs = result.logs.unwrap_or_default();
+            Ok(SimulatedSwap {
+                response,
+                simulation: SimulationResult {
+                    units_consumed: result.units_consumed,
+                    jupiter_error: jupiter_error_from_logs(&logs),
+                    logs,
+                    error: result.err,
+                },
+            })
+        }
+    }
+}
+
+#[cfg(feature = "rpc")]
+pub use rpc::{SimulatedSwap, SimulationResult};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed_swap_logs(failure: &str) -> Vec<String> {
+        [
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]".to_string(),
+            "Program ComputeBudget111111111111111111111111111111 success".to_string(),
+            format!("Program {JUPITER_PROGRAM_ID} invoke [1]"),
+            "Program log: Instruction: Route".to_string(),
+            format!("Program {JUPITER_PROGRAM_ID} consumed 51234 of 1399700 compute units"),
+            format!("Program {JUPITER_PROGRAM_ID} failed: {failure}"),
+        ]
+        .to_vec()
+    }
+
+    #[test]
+    fn slippage_error_is_decoded() {
+        let logs = failed_swap_logs("custom program error: 0x1771");
+        assert_eq!(
+            jupiter_error_from_logs(&logs),
+            Some(JupiterProgramError::SlippageToleranceExceeded)
+        );
+    }
+
+    #[test]
+    fn other_error_codes_are_kept() {
+        // 6008, not enough account keys
+        let logs = failed_swap_logs("custom program error: 0x1778");
+        assert_eq!(
+            jupiter_error_from_logs(&logs),
+            Some(JupiterProgramError::Other(6008))
+        );
+        assert_eq!(
+            JupiterProgramError::from_code(6001),
+            JupiterProgramError::SlippageToleranceExceeded
+        );
+    }
+
+    #[test]
+    fn logs_without_a_jupiter_custom_error_decode_to_none() {
+        assert_eq!(jupiter_error_from_logs(&[]), None);
+        let logs = failed_swap_logs("Program failed to complete");
+        assert_eq!(jupiter_error_from_logs(&logs), None);
+
+        // The custom error of another program is not the one of Jupiter
+        let logs = vec![
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA failed: custom program error: 0x1"
+                .to_string(),
+        ];
+        assert_eq!(jupiter_error_from_logs(&logs), None);
+        let logs = failed_swap_logs("custom program error: 0xzz");
+        assert_eq!(jupiter_error_from_logs(&logs), None);
+    }
+
+    #[cfg(feature = "rpc")]
+    #[tokio::test]
+    async fn failed_simulation_is_returned_with_the_jupiter_error() {
+        use std::collections::HashMap;
+
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        use serde_json::json;
+        use solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::RpcRequest};
+        use solana_sdk::{
+            hash::Hash,
+            instruction::InstructionError,
+            message::Message,
+            transaction::{Transaction, TransactionError, VersionedTransaction},
+        };
+        use wiremock::{
+            matchers::{method, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        use crate::{swap::SwapRequest, JupiterSwapApiClient};
+
+        let payer = Pubkey::new_unique();
+        let message = Message::new_with_blockhash(&[], Some(&payer), &Hash::new_unique());
+        let transaction = VersionedTransaction::from(Transaction::new_unsigned(message));
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/swap"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "swapTransaction": STANDARD.encode(bincode::serialize(&transaction).unwrap()),
+                "lastValidBlockHeight": 301_000_150,
+                "prioritizationFeeLamports": 5_000,
+                "computeUnitLimit": 200_000
+            })))
+            .mount(&server)
+            .await;
+        let logs = failed_swap_logs("custom program error: 0x1771");
+        let rpc = RpcClient::new_mock_with_mocks(
+            "succeeds".to_string(),
+            HashMap::from([(
+                RpcRequest::SimulateTransaction,
+                json!({
+                    "context": { "slot": 1 },
+                    "value": {
+                        "err": { "InstructionError": [2, { "Custom": 6001 }] },
+                        "logs": logs,
+                        "accounts": null,
+                        "unitsConsumed": 51234,
+                        "returnData": null
+                    }
+                }),
+            )]),
+        );
+
+        let simulated = JupiterSwapApiClient::new(server.uri(), None)
+            .swap_and_simulate(
+                &SwapRequest {
+                    user_public_key: payer,
+                    ..SwapRequest::default()
+                },
+                &rpc,
+            )
+            .await
+            .unwrap();
+        let simulation = simulated.simulation;
+        assert!(!simulation.is_success());
+        assert_eq!(
+            simulation.error,
+            Some(TransactionError::InstructionError(
+                2,
+                InstructionError::Custom(6001)
+            ))
+        );
+        assert_eq!(
+            simulation.jupiter_error,
+            Some(JupiterProgramError::SlippageToleranceExceeded)
+        );
+        assert_eq!(simulation.units_consumed, Some(51234));
+        assert_eq!(simulation.logs, logs);
+        assert_eq!(simulated.response.last_valid_block_height, 301_000_150);
+    }
+}
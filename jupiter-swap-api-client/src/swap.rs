@@ -1,14 +1,34 @@
 use crate::{
-    quote::QuoteResponse, serde_helpers::field_as_string, transaction_config::TransactionConfig,
+    ata::{ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_PROGRAM_ID},
+    compute_budget::{self, ComputeBudget},
+    quote::{QuoteResponse, SwapMode},
+    route_plan_with_metadata::{RoutePlanError, RoutePlanValidation},
+    serde_helpers::{field_as_string, hash_as_bytes},
+    transaction_config::{TransactionConfig, TransactionConfigError},
+    ExpectedFields,
 };
+#[cfg(feature = "solana-sdk")]
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use solana_hash::Hash;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_packet::PACKET_DATA_SIZE;
+use solana_pubkey::Pubkey;
+#[cfg(feature = "solana-sdk")]
 use solana_sdk::{
-    instruction::{AccountMeta, Instruction},
-    pubkey::Pubkey,
+    address_lookup_table::AddressLookupTableAccount,
+    message::{v0, CompileError, Message, VersionedMessage},
+    signature::{Keypair, Signature, Signer},
+    signer::SignerError,
+    transaction::{Transaction, VersionedTransaction},
 };
+#[cfg(feature = "solana-sdk")]
+use solana_system_interface::instruction as system_instruction;
+use thiserror::Error;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SwapRequest {
     #[serde(with = "field_as_string")]
@@ -18,7 +38,123 @@ pub struct SwapRequest {
     pub config: TransactionConfig,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Debug, Error, PartialEq, Clone)]
+pub enum SwapRequestError {
+    #[error("user_public_key is not set")]
+    InvalidPublicKey,
+    #[error("fee_account is set but the quote was requested without platform_fee_bps")]
+    FeeAccountWithoutPlatformFee,
+    #[error("use_token_ledger only supports ExactIn quotes")]
+    TokenLedgerWithExactOut,
+    #[error("other_amount_threshold {other_amount_threshold} is not a {swap_mode} threshold of a quote from {in_amount} to {out_amount}")]
+    ThresholdNotMatchingSwapMode {
+        swap_mode: SwapMode,
+        other_amount_threshold: u64,
+        in_amount: u64,
+        out_amount: u64,
+    },
+    #[error(transparent)]
+    InvalidConfig(#[from] TransactionConfigError),
+    #[error("Invalid route plan: {0}")]
+    InvalidRoutePlan(#[from] RoutePlanError),
+}
+
+impl SwapRequest {
+    /// Start a request swapping `quote` for `user_public_key`, with the default config
+    pub fn from_quote(quote: QuoteResponse, user_public_key: Pubkey) -> SwapRequestBuilder {
+        SwapRequestBuilder {
+            request: SwapRequest {
+                user_public_key,
+                quote_response: quote,
+                config: TransactionConfig::default(),
+            },
+            validate_route_plan: false,
+        }
+    }
+
+    /// Check locally for mistakes the API would reject the request for,
+    /// see [`Self::validate_route_plan`] to also check the splits of the route
+    pub fn validate(&self) -> Result<(), SwapRequestError> {
+        if self.user_public_key == Pubkey::default() {
+            return Err(SwapRequestError::InvalidPublicKey);
+        }
+        let has_platform_fee = self
+            .quote_response
+            .platform_fee
+            .as_ref()
+            .is_some_and(|platform_fee| platform_fee.fee_bps > 0);
+        if self.config.fee_account.is_some() && !has_platform_fee {
+            return Err(SwapRequestError::FeeAccountWithoutPlatformFee);
+        }
+        if self.config.compute_unit_price_micro_lamports.is_some()
+            && self.config.prioritization_fee_lamports.is_some()
+        {
+            return Err(TransactionConfigError::ComputeUnitPriceWithPrioritizationFee.into());
+        }
+        let quote = &self.quote_response;
+        if quote.swap_mode == SwapMode::ExactOut && self.config.use_token_ledger == Some(true) {
+            return Err(SwapRequestError::TokenLedgerWithExactOut);
+        }
+        let threshold_matches = match quote.swap_mode {
+            SwapMode::ExactIn => quote.other_amount_threshold <= quote.out_amount,
+            SwapMode::ExactOut => quote.other_amount_threshold >= quote.in_amount,
+        };
+        if !threshold_matches {
+            return Err(SwapRequestError::ThresholdNotMatchingSwapMode {
+                swap_mode: quote.swap_mode,
+                other_amount_threshold: quote.other_amount_threshold,
+                in_amount: quote.in_amount,
+                out_amount: quote.out_amount,
+            });
+        }
+        Ok(())
+    }
+
+    /// [`Self::validate`] and check the splits of the route plan add up, stricter than the API
+    /// which accepts the rounding of some routes
+    pub fn validate_route_plan(&self) -> Result<(), SwapRequestError> {
+        self.validate()?;
+        self.quote_response.route_plan.validate_splits()?;
+        Ok(())
+    }
+}
+
+/// Builds a [`SwapRequest`] checked with [`SwapRequest::validate`], see [`SwapRequest::from_quote`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapRequestBuilder {
+    request: SwapRequest,
+    validate_route_plan: bool,
+}
+
+impl SwapRequestBuilder {
+    /// Replace the whole config, including a fee account set before
+    pub fn config(mut self, config: TransactionConfig) -> Self {
+        self.request.config = config;
+        self
+    }
+
+    pub fn fee_account(mut self, fee_account: Pubkey) -> Self {
+        self.request.config.fee_account = Some(fee_account);
+        self
+    }
+
+    /// Check the route plan with [`SwapRequest::validate_route_plan`] as well
+    pub fn validate_route_plan(mut self, validate_route_plan: bool) -> Self {
+        self.validate_route_plan = validate_route_plan;
+        self
+    }
+
+    pub fn build(self) -> Result<SwapRequest, SwapRequestError> {
+        if self.validate_route_plan {
+            self.request.validate_route_plan()?;
+        } else {
+            self.request.validate()?;
+        }
+        Ok(self.request)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum PrioritizationType {
     #[serde(rename_all = "camelCase")]
@@ -26,38 +162,190 @@ pub enum PrioritizationType {
     #[serde(rename_all = "camelCase")]
     ComputeBudget {
         micro_lamports: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
         estimated_micro_lamports: Option<u64>,
     },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct DynamicSlippageReport {
     pub slippage_bps: u16,
+    /// `other_amount_threshold` of the swap once the dynamic slippage is applied,
+    /// the minimum output for ExactIn or the maximum input for ExactOut
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub other_amount: Option<u64>,
     /// Signed to convey positive and negative slippage
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub simulated_incurred_slippage_bps: Option<i16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub amplification_ratio: Option<Decimal>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct UiSimulationError {
     error_code: String,
     error: String,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SwapResponse {
     #[serde(with = "base64_serialize_deserialize")]
     pub swap_transaction: Vec<u8>,
+    /// Transactions to send and confirm in order before `swap_transaction`, e.g. setup,
+    /// for routes too large for a single transaction. Empty for the usual single transaction swap.
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        with = "crate::serde_helpers::vec_base64"
+    )]
+    pub additional_transactions: Vec<Vec<u8>>,
+    /// Last block height at which the blockhash of `swap_transaction` is valid,
+    /// confirmation can be given up on past it without fetching the blockhash again
     pub last_valid_block_height: u64,
+    /// Prioritization fee Jupiter set on `swap_transaction`
     pub prioritization_fee_lamports: u64,
     pub compute_unit_limit: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub prioritization_type: Option<PrioritizationType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dynamic_slippage_report: Option<DynamicSlippageReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub simulation_error: Option<UiSimulationError>,
+    /// Request ids of the response, see [`ClientError::request_ids`](crate::ClientError::request_ids)
+    #[serde(skip)]
+    pub request_ids: Vec<String>,
+    /// Fields the response is missing or does not model, only collected with
+    /// [`JupiterSwapApiClientBuilder::warn_on_field_drift`](crate::builder::JupiterSwapApiClientBuilder::warn_on_field_drift)
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+impl ExpectedFields for SwapResponse {
+    const EXPECTED_FIELDS: &'static [&'static str] = &["prioritizationType"];
+}
+
+impl SwapResponse {
+    /// `additional_transactions` then `swap_transaction`, the order to send them in
+    pub fn transactions(&self) -> impl Iterator<Item = &[u8]> {
+        self.additional_transactions
+            .iter()
+            .chain([&self.swap_transaction])
+            .map(Vec::as_slice)
+    }
+}
+
+#[cfg(feature = "solana-sdk")]
+#[derive(Debug, Error)]
+pub enum SignError {
+    #[error("Failed to decode the swap transaction: {0}")]
+    InvalidTransaction(#[from] bincode::Error),
+    #[error("{0} is not a signer of the swap transaction")]
+    NotASigner(Pubkey),
+}
+
+#[cfg(feature = "solana-sdk")]
+#[derive(Debug, Error)]
+pub enum SwapTransactionDecodeError {
+    #[error("Failed to decode the swap transaction: {0}")]
+    InvalidTransaction(#[from] bincode::Error),
+    #[error("The swap transaction is a versioned transaction, request both the quote and the swap with as_legacy_transaction")]
+    NotLegacy,
+}
+
+#[cfg(feature = "solana-sdk")]
+impl SwapResponse {
+    /// Decode `swap_transaction` as a legacy transaction, for swaps requested with `as_legacy_transaction`
+    pub fn legacy_transaction(&self) -> Result<Transaction, SwapTransactionDecodeError> {
+        decode_legacy_transaction(&self.swap_transaction)
+    }
+
+    /// Same as [`Self::legacy_transaction`] for all the [`Self::transactions`], in send order
+    pub fn legacy_transactions(&self) -> Result<Vec<Transaction>, SwapTransactionDecodeError> {
+        self.transactions().map(decode_legacy_transaction).collect()
+    }
+
+    /// Decode all the [`Self::transactions`], in send order
+    pub fn versioned_transactions(&self) -> Result<Vec<VersionedTransaction>, bincode::Error> {
+        self.transactions().map(bincode::deserialize).collect()
+    }
+
+    /// Sign `swap_transaction` with `signers` and encode it in base64, ready for `sendTransaction`.
+    /// Signatures already on the transaction are kept, e.g. the ones of Jupiter as fee payer.
+    /// Use [`Self::sign_all_with`] when the swap has `additional_transactions`.
+    pub fn sign_with(&self, signers: &[&Keypair]) -> Result<String, SignError> {
+        sign_transaction(&self.swap_transaction, signers)
+    }
+
+    /// Same as [`Self::sign_with`] for all the [`Self::transactions`], in send order.
+    /// Every signer must be a signer of every transaction.
+    pub fn sign_all_with(&self, signers: &[&Keypair]) -> Result<Vec<String>, SignError> {
+        self.transactions()
+            .map(|transaction| sign_transaction(transaction, signers))
+            .collect()
+    }
+
+    /// Rebuild the [`Self::transactions`] against `recent_blockhash`, e.g. the blockhash of a bundle, as the API
+    /// does not take one. Signatures already on the transactions no longer match and are cleared,
+    /// `last_valid_block_height` becomes the one of `recent_blockhash`.
+    pub fn with_recent_blockhash(
+        mut self,
+        recent_blockhash: Hash,
+        last_valid_block_height: u64,
+    ) -> Result<Self, bincode::Error> {
+        self.swap_transaction = with_recent_blockhash(&self.swap_transaction, recent_blockhash)?;
+        for transaction in &mut self.additional_transactions {
+            *transaction = with_recent_blockhash(transaction, recent_blockhash)?;
+        }
+        self.last_valid_block_height = last_valid_block_height;
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "solana-sdk")]
+fn decode_legacy_transaction(
+    transaction: &[u8],
+) -> Result<Transaction, SwapTransactionDecodeError> {
+    let transaction: VersionedTransaction = bincode::deserialize(transaction)?;
+    transaction
+        .into_legacy_transaction()
+        .ok_or(SwapTransactionDecodeError::NotLegacy)
+}
+
+#[cfg(feature = "solana-sdk")]
+fn sign_transaction(transaction: &[u8], signers: &[&Keypair]) -> Result<String, SignError> {
+    let mut transaction: VersionedTransaction = bincode::deserialize(transaction)?;
+    let message = transaction.message.serialize();
+    let num_required_signatures = usize::from(transaction.message.header().num_required_signatures);
+    transaction
+        .signatures
+        .resize(num_required_signatures, Signature::default());
+    let signer_keys = &transaction.message.static_account_keys()[..num_required_signatures];
+    for signer in signers {
+        let position = signer_keys
+            .iter()
+            .position(|key| *key == signer.pubkey())
+            .ok_or_else(|| SignError::NotASigner(signer.pubkey()))?;
+        transaction.signatures[position] = signer.sign_message(&message);
+    }
+    Ok(STANDARD.encode(bincode::serialize(&transaction)?))
+}
+
+/// Signatures no longer match and are cleared
+#[cfg(feature = "solana-sdk")]
+fn with_recent_blockhash(
+    transaction: &[u8],
+    recent_blockhash: Hash,
+) -> Result<Vec<u8>, bincode::Error> {
+    let mut transaction: VersionedTransaction = bincode::deserialize(transaction)?;
+    transaction.message.set_recent_blockhash(recent_blockhash);
+    transaction
+        .signatures
+        .iter_mut()
+        .for_each(|signature| *signature = Signature::default());
+    bincode::serialize(&transaction)
 }
 
 pub mod base64_serialize_deserialize {
@@ -81,13 +369,20 @@ pub mod base64_serialize_deserialize {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Serializes to the same JSON as returned by the API
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(
+    from = "SwapInstructionsResponseInternal",
+    into = "SwapInstructionsResponseInternal"
+)]
 pub struct SwapInstructionsResponse {
     pub token_ledger_instruction: Option<Instruction>,
     pub compute_budget_instructions: Vec<Instruction>,
+    /// Create the associated token accounts of the swap and wrap SOL when needed
     pub setup_instructions: Vec<Instruction>,
     /// Instruction performing the action of swapping
     pub swap_instruction: Instruction,
+    /// Unwrap SOL when needed
     pub cleanup_instruction: Option<Instruction>,
     /// Other instructions that should be included in the transaction.
     /// Now, it should only have the Jito tip instruction.
@@ -98,17 +393,524 @@ pub struct SwapInstructionsResponse {
     pub prioritization_type: Option<PrioritizationType>,
     pub dynamic_slippage_report: Option<DynamicSlippageReport>,
     pub simulation_error: Option<UiSimulationError>,
+    /// Blockhash the instructions were simulated against, `None` on older deployments
+    pub blockhash_with_metadata: Option<BlockhashWithMetadata>,
+    /// Request ids of the response, see [`ClientError::request_ids`](crate::ClientError::request_ids).
+    /// Not serialized.
+    pub request_ids: Vec<String>,
+    /// Fields the response is missing or does not model, not serialized, only collected with
+    /// [`JupiterSwapApiClientBuilder::warn_on_field_drift`](crate::builder::JupiterSwapApiClientBuilder::warn_on_field_drift)
+    pub warnings: Vec<String>,
 }
 
-// Duplicate for deserialization
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockhashWithMetadata {
+    pub blockhash: Hash,
+    /// The transaction expires once the block height exceeds it
+    pub last_valid_block_height: u64,
+    /// When the server fetched the blockhash
+    pub fetched_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(feature = "solana-sdk")]
+#[derive(Debug, Error, PartialEq, Clone)]
+pub enum BuildError {
+    #[error("Address lookup table {0} is used by the swap but was not provided")]
+    MissingAddressLookupTable(Pubkey),
+    #[error("The swap uses address lookup tables which cannot be used in a legacy transaction")]
+    LegacyWithAddressLookupTables,
+    #[error("The response has no blockhash, the deployment is too old to report it")]
+    MissingBlockhash,
+    #[error("Failed to compile the message: {0}")]
+    CompileError(#[from] CompileError),
+}
+
+#[cfg(feature = "solana-sdk")]
+#[derive(Debug, Error, PartialEq)]
+pub enum BuildTxError {
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    #[error("Nonce authority {0} is not among the signers")]
+    NonceAuthorityNotASigner(Pubkey),
+    #[error("Failed to sign the transaction: {0}")]
+    SigningError(#[from] SignerError),
+}
+
+impl SwapInstructionsResponse {
+    /// All instructions in transaction order:
+    /// compute budget, setup, token ledger, swap, cleanup then other instructions
+    pub fn instructions(&self) -> Vec<Instruction> {
+        self.compute_budget_instructions
+            .iter()
+            .chain(&self.setup_instructions)
+            .chain(&self.token_ledger_instruction)
+            .chain(std::iter::once(&self.swap_instruction))
+            .chain(&self.cleanup_instruction)
+            .chain(&self.other_instructions)
+            .cloned()
+            .collect()
+    }
+
+    /// [`Self::instructions`] without the setup and cleanup instructions, for callers assembling their own
+    ///
+    /// The caller must guarantee that the associated token accounts of the swap exist and that wrapped SOL
+    /// is funded and unwrapped as needed, otherwise the swap fails.
+    pub fn core_instructions_only(&self) -> Vec<Instruction> {
+        self.compute_budget_instructions
+            .iter()
+            .chain(&self.token_ledger_instruction)
+            .chain(std::iter::once(&self.swap_instruction))
+            .chain(&self.other_instructions)
+            .cloned()
+            .collect()
+    }
+
+    /// The setup instructions wrap SOL into the wSOL account of the user, with a `SyncNative` instruction
+    pub fn wraps_sol(&self) -> bool {
+        self.setup_instructions
+            .iter()
+            .any(|instruction| is_token_instruction(instruction, SYNC_NATIVE_DISCRIMINATOR))
+    }
+
+    /// The cleanup instruction unwraps SOL by closing the wSOL account of the user
+    pub fn unwraps_sol(&self) -> bool {
+        self.cleanup_instruction
+            .as_ref()
+            .is_some_and(|instruction| {
+                is_token_instruction(instruction, CLOSE_ACCOUNT_DISCRIMINATOR)
+            })
+    }
+
+    /// Units requested by the compute budget instructions, `None` when no limit is set
+    pub fn compute_unit_limit(&self) -> Option<u32> {
+        self.compute_budget_instructions
+            .iter()
+            .find_map(compute_budget::compute_unit_limit)
+    }
+
+    /// Compute unit price set by the compute budget instructions, `None` when no price is set
+    pub fn compute_unit_price_micro_lamports(&self) -> Option<u64> {
+        self.compute_budget_instructions
+            .iter()
+            .find_map(compute_budget::compute_unit_price_micro_lamports)
+    }
+
+    /// Compute unit limit and price set by the compute budget instructions
+    pub fn compute_budget(&self) -> ComputeBudget {
+        ComputeBudget::from_instructions(&self.compute_budget_instructions)
+    }
+
+    /// Replace the compute budget instructions with ones setting `compute_budget`
+    pub fn with_compute_budget(mut self, compute_budget: ComputeBudget) -> Self {
+        self.compute_budget_instructions = compute_budget.instructions();
+        self
+    }
+
+    /// Assemble [`Self::instructions`] into a message paid by `payer`.
+    /// A legacy message is built for swaps requested with `as_legacy_transaction`, which do not use address lookup tables,
+    /// otherwise a v0 message using `address_lookup_table_accounts`, which must contain the tables of `address_lookup_table_addresses`.
+    #[cfg(feature = "solana-sdk")]
+    pub fn build_message(
+        &self,
+        payer: &Pubkey,
+        address_lookup_table_accounts: &[AddressLookupTableAccount],
+        recent_blockhash: Hash,
+        legacy: bool,
+    ) -> Result<VersionedMessage, BuildError> {
+        self.compile_message(
+            payer,
+            &self.instructions(),
+            address_lookup_table_accounts,
+            recent_blockhash,
+            legacy,
+        )
+    }
+
+    /// Assemble [`Self::instructions`] into a v0 message paid by `payer` against the blockhash of
+    /// `blockhash_with_metadata`, compiled with `address_lookup_table_accounts`, which must contain
+    /// the tables of `address_lookup_table_addresses`. See [`Self::build_message`] for another blockhash.
+    #[cfg(feature = "solana-sdk")]
+    pub fn into_versioned_message(
+        &self,
+        payer: &Pubkey,
+        address_lookup_table_accounts: &[AddressLookupTableAccount],
+    ) -> Result<VersionedMessage, BuildError> {
+        let recent_blockhash = self
+            .blockhash_with_metadata
+            .as_ref()
+            .ok_or(BuildError::MissingBlockhash)?
+            .blockhash;
+        self.build_message(
+            payer,
+            address_lookup_table_accounts,
+            recent_blockhash,
+            false,
+        )
+    }
+
+    /// Assemble and sign a v0 transaction using the durable nonce `nonce_account`, whose current value is `nonce_hash`.
+    /// The advance nonce instruction comes first, as required for the runtime to accept the nonce as blockhash.
+    /// `signers` must contain the payer and `nonce_authority`.
+    #[cfg(feature = "solana-sdk")]
+    pub fn build_with_durable_nonce(
+        &self,
+        payer: &Pubkey,
+        nonce_account: &Pubkey,
+        nonce_authority: &Pubkey,
+        nonce_hash: Hash,
+        address_lookup_table_accounts: &[AddressLookupTableAccount],
+        signers: &[&dyn Signer],
+    ) -> Result<VersionedTransaction, BuildTxError> {
+        if !signers
+            .iter()
+            .any(|signer| signer.pubkey() == *nonce_authority)
+        {
+            return Err(BuildTxError::NonceAuthorityNotASigner(*nonce_authority));
+        }
+        let instructions: Vec<Instruction> = std::iter::once(
+            system_instruction::advance_nonce_account(nonce_account, nonce_authority),
+        )
+        .chain(self.instructions())
+        .collect();
+        let message = self.compile_message(
+            payer,
+            &instructions,
+            address_lookup_table_accounts,
+            nonce_hash,
+            false,
+        )?;
+        Ok(VersionedTransaction::try_new(message, signers)?)
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    fn compile_message(
+        &self,
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        address_lookup_table_accounts: &[AddressLookupTableAccount],
+        recent_blockhash: Hash,
+        legacy: bool,
+    ) -> Result<VersionedMessage, BuildError> {
+        if legacy {
+            if !self.address_lookup_table_addresses.is_empty() {
+                return Err(BuildError::LegacyWithAddressLookupTables);
+            }
+            return Ok(VersionedMessage::Legacy(Message::new_with_blockhash(
+                instructions,
+                Some(payer),
+                &recent_blockhash,
+            )));
+        }
+        if let Some(missing) = self.address_lookup_table_addresses.iter().find(|address| {
+            !address_lookup_table_accounts
+                .iter()
+                .any(|account| account.key == **address)
+        }) {
+            return Err(BuildError::MissingAddressLookupTable(*missing));
+        }
+        let message = v0::Message::try_compile(
+            payer,
+            instructions,
+            address_lookup_table_accounts,
+            recent_blockhash,
+        )?;
+        Ok(VersionedMessage::V0(message))
+    }
+
+    /// Writable accounts of all instructions, see [`Self::account_keys`],
+    /// the accounts to look up recent prioritization fees for
+    pub fn writable_accounts(&self) -> Vec<Pubkey> {
+        self.account_keys()
+            .into_iter()
+            .filter(|account_key| account_key.is_writable)
+            .map(|account_key| account_key.pubkey)
+            .collect()
+    }
+
+    /// Accounts and programs of all instructions in order of first appearance, deduplicated,
+    /// followed by the address lookup tables which are readonly.
+    /// An account is writable or signer if any instruction uses it as such.
+    pub fn account_keys(&self) -> Vec<AccountKeyMeta> {
+        let mut account_keys = self.instruction_account_keys();
+        for address in &self.address_lookup_table_addresses {
+            merge_account_key(&mut account_keys, AccountKeyMeta::readonly(*address));
+        }
+        account_keys
+    }
+
+    /// [`Self::account_keys`] without the address lookup tables, the accounts of the message
+    fn instruction_account_keys(&self) -> Vec<AccountKeyMeta> {
+        let mut account_keys = Vec::new();
+        for instruction in self.instructions() {
+            for account in &instruction.accounts {
+                merge_account_key(&mut account_keys, AccountKeyMeta::from(account));
+            }
+            merge_account_key(
+                &mut account_keys,
+                AccountKeyMeta::readonly(instruction.program_id),
+            );
+        }
+        account_keys
+    }
+
+    /// Number of distinct address lookup tables the swap uses
+    pub fn lookup_table_count(&self) -> usize {
+        let mut addresses = self.address_lookup_table_addresses.clone();
+        addresses.sort_unstable();
+        addresses.dedup();
+        addresses.len()
+    }
+
+    /// Lower bound of the size of the serialized v0 transaction, assuming every account which is neither
+    /// a signer nor a program is found in the lookup tables
+    pub fn estimated_transaction_size(&self) -> usize {
+        let account_keys = self.instruction_account_keys();
+        let programs: Vec<Pubkey> = self
+            .instructions()
+            .iter()
+            .map(|instruction| instruction.program_id)
+            .collect();
+        let signers = account_keys
+            .iter()
+            .filter(|account| account.is_signer)
+            .count();
+        let static_keys = account_keys
+            .iter()
+            .filter(|account| account.is_signer || programs.contains(&account.pubkey))
+            .count();
+        let looked_up = account_keys.len() - static_keys;
+        let lookup_tables = self.lookup_table_count();
+
+        let signatures = compact_len(signers) + signers * 64;
+        // Version prefix and header
+        let header = 1 + 3;
+        let static_keys = compact_len(static_keys) + static_keys * 32;
+        let blockhash = 32;
+        let instructions = self.instructions();
+        let instructions = compact_len(instructions.len())
+            + instructions
+                .iter()
+                .map(|instruction| {
+                    1 + compact_len(instruction.accounts.len())
+                        + instruction.accounts.len()
+                        + compact_len(instruction.data.len())
+                        + instruction.data.len()
+                })
+                .sum::<usize>();
+        // Table address and the lengths of its writable and readonly indexes
+        let lookups = compact_len(lookup_tables) + lookup_tables * (32 + 1 + 1) + looked_up;
+        signatures + header + static_keys + blockhash + instructions + lookups
+    }
+
+    /// [`Self::estimated_transaction_size`] is above the packet size, sending the transaction will fail:
+    /// quote again with a lower `max_accounts`
+    pub fn likely_exceeds_transaction_size(&self) -> bool {
+        self.estimated_transaction_size() > PACKET_DATA_SIZE
+    }
+
+    /// Drop the compute budget instructions, for callers setting their own budget
+    pub fn without_compute_budget_instructions(mut self) -> Self {
+        self.compute_budget_instructions.clear();
+        self
+    }
+}
+
+/// Account used by the instructions of a swap, with the flags of all its uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccountKeyMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl AccountKeyMeta {
+    fn readonly(pubkey: Pubkey) -> Self {
+        Self {
+            pubkey,
+            is_signer: false,
+            is_writable: false,
+        }
+    }
+}
+
+impl From<&AccountMeta> for AccountKeyMeta {
+    fn from(account: &AccountMeta) -> Self {
+        Self {
+            pubkey: account.pubkey,
+            is_signer: account.is_signer,
+            is_writable: account.is_writable,
+        }
+    }
+}
+
+/// Add `account` to `account_keys`, or merge its flags when already present, writable and signer winning
+fn merge_account_key(account_keys: &mut Vec<AccountKeyMeta>, account: AccountKeyMeta) {
+    match account_keys
+        .iter_mut()
+        .find(|account_key| account_key.pubkey == account.pubkey)
+    {
+        Some(account_key) => {
+            account_key.is_signer |= account.is_signer;
+            account_key.is_writable |= account.is_writable;
+        }
+        None => account_keys.push(account),
+    }
+}
+
+/// Bytes taken by the compact-u16 encoding of a length
+fn compact_len(len: usize) -> usize {
+    match len {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        _ => 3,
+    }
+}
+
+const CLOSE_ACCOUNT_DISCRIMINATOR: u8 = 9;
+const SYNC_NATIVE_DISCRIMINATOR: u8 = 17;
+
+/// SPL Token instruction of the given variant, wSOL is always owned by the SPL Token program
+fn is_token_instruction(instruction: &Instruction, discriminator: u8) -> bool {
+    instruction.program_id == TOKEN_PROGRAM_ID && instruction.data.first() == Some(&discriminator)
+}
+
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+pub enum SwapInstructionsBuilderError {
+    #[error("Setup instructions were skipped but create the associated token account {0}")]
+    SkippedTokenAccountCreation(Pubkey),
+}
+
+/// Assemble the instructions of a swap with the caller's own instructions around it
+///
+/// [`Self::build`] orders the instructions as: compute budget, setup, token ledger, prepended, swap,
+/// appended, cleanup and other instructions, the order of [`SwapInstructionsResponse::instructions`].
+/// Compute budget comes first, the prepended instructions run between the token ledger and the swap
+/// so that the token ledger accounts for the amount they provide, and cleanup comes after the appended
+/// instructions so that they can still use the wrapped SOL.
+#[derive(Debug, Clone)]
+pub struct SwapInstructionsBuilder {
+    response: SwapInstructionsResponse,
+    skip_setup: bool,
+    skip_cleanup: bool,
+    token_accounts_exist: bool,
+    prepended: Vec<Instruction>,
+    appended: Vec<Instruction>,
+}
+
+impl SwapInstructionsBuilder {
+    pub fn new(response: SwapInstructionsResponse) -> Self {
+        Self {
+            response,
+            skip_setup: false,
+            skip_cleanup: false,
+            token_accounts_exist: false,
+            prepended: Vec::new(),
+            appended: Vec::new(),
+        }
+    }
+
+    /// Drop the setup instructions.
+    /// [`Self::build`] fails if they create associated token accounts, unless [`Self::token_accounts_exist`] is set.
+    pub fn skip_setup(mut self) -> Self {
+        self.skip_setup = true;
+        self
+    }
+
+    /// Drop the cleanup instruction, leaving wrapped SOL to the caller
+    pub fn skip_cleanup(mut self) -> Self {
+        self.skip_cleanup = true;
+        self
+    }
+
+    /// The caller guarantees the associated token accounts of the swap exist when skipping setup
+    pub fn token_accounts_exist(mut self) -> Self {
+        self.token_accounts_exist = true;
+        self
+    }
+
+    /// Replace the compute budget instructions, dropping them when neither is set
+    pub fn replace_compute_budget(
+        mut self,
+        unit_limit: Option<u32>,
+        unit_price: Option<u64>,
+    ) -> Self {
+        self.response.compute_budget_instructions = ComputeBudget {
+            unit_limit,
+            unit_price,
+        }
+        .instructions();
+        self
+    }
+
+    /// Instructions to run after the setup and token ledger instructions and right before the swap instruction,
+    /// appended to the ones already prepended
+    pub fn prepend(mut self, instructions: Vec<Instruction>) -> Self {
+        self.prepended.extend(instructions);
+        self
+    }
+
+    /// Instructions to run right after the swap instruction, appended to the ones already appended
+    pub fn append(mut self, instructions: Vec<Instruction>) -> Self {
+        self.appended.extend(instructions);
+        self
+    }
+
+    pub fn build(self) -> Result<Vec<Instruction>, SwapInstructionsBuilderError> {
+        let response = self.response;
+        if self.skip_setup && !self.token_accounts_exist {
+            let token_account_creation = response
+                .setup_instructions
+                .iter()
+                .find(|instruction| instruction.program_id == ASSOCIATED_TOKEN_PROGRAM_ID);
+            if let Some(instruction) = token_account_creation {
+                // Create and CreateIdempotent both take the funding account then the associated token account
+                let token_account = instruction
+                    .accounts
+                    .get(1)
+                    .map_or(Pubkey::default(), |account| account.pubkey);
+                return Err(SwapInstructionsBuilderError::SkippedTokenAccountCreation(
+                    token_account,
+                ));
+            }
+        }
+
+        let setup_instructions = if self.skip_setup {
+            Vec::new()
+        } else {
+            response.setup_instructions
+        };
+        let cleanup_instruction = response.cleanup_instruction.filter(|_| !self.skip_cleanup);
+        Ok(response
+            .compute_budget_instructions
+            .into_iter()
+            .chain(setup_instructions)
+            .chain(response.token_ledger_instruction)
+            .chain(self.prepended)
+            .chain(std::iter::once(response.swap_instruction))
+            .chain(self.appended)
+            .chain(cleanup_instruction)
+            .chain(response.other_instructions)
+            .collect())
+    }
+}
+
+impl From<SwapInstructionsResponse> for SwapInstructionsBuilder {
+    fn from(response: SwapInstructionsResponse) -> Self {
+        Self::new(response)
+    }
+}
+
+// Duplicate for (de)serialization
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SwapInstructionsResponseInternal {
+    #[serde(skip_serializing_if = "Option::is_none")]
     token_ledger_instruction: Option<InstructionInternal>,
     compute_budget_instructions: Vec<InstructionInternal>,
     setup_instructions: Vec<InstructionInternal>,
     /// Instruction performing the action of swapping
     swap_instruction: InstructionInternal,
+    #[serde(skip_serializing_if = "Option::is_none")]
     cleanup_instruction: Option<InstructionInternal>,
     /// Other instructions that should be included in the transaction.
     /// Now, it should only have the Jito tip instruction.
@@ -116,12 +918,64 @@ pub struct SwapInstructionsResponseInternal {
     address_lookup_table_addresses: Vec<PubkeyInternal>,
     prioritization_fee_lamports: u64,
     compute_unit_limit: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
     prioritization_type: Option<PrioritizationType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     dynamic_slippage_report: Option<DynamicSlippageReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     simulation_error: Option<UiSimulationError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blockhash_with_metadata: Option<BlockhashWithMetadataInternal>,
+}
+
+impl ExpectedFields for SwapInstructionsResponseInternal {
+    const EXPECTED_FIELDS: &'static [&'static str] =
+        &["prioritizationType", "blockhashWithMetadata"];
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct BlockhashWithMetadataInternal {
+    #[serde(with = "hash_as_bytes")]
+    blockhash: Hash,
+    last_valid_block_height: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fetched_at: Option<SystemTimeInternal>,
+}
+
+/// Serde representation of a `SystemTime`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct SystemTimeInternal {
+    secs_since_epoch: i64,
+    nanos_since_epoch: u32,
+}
+
+impl From<BlockhashWithMetadata> for BlockhashWithMetadataInternal {
+    fn from(value: BlockhashWithMetadata) -> Self {
+        Self {
+            blockhash: value.blockhash,
+            last_valid_block_height: value.last_valid_block_height,
+            fetched_at: value.fetched_at.map(|fetched_at| SystemTimeInternal {
+                secs_since_epoch: fetched_at.timestamp(),
+                nanos_since_epoch: fetched_at.timestamp_subsec_nanos(),
+            }),
+        }
+    }
+}
+
+impl From<BlockhashWithMetadataInternal> for BlockhashWithMetadata {
+    fn from(value: BlockhashWithMetadataInternal) -> Self {
+        Self {
+            blockhash: value.blockhash,
+            last_valid_block_height: value.last_valid_block_height,
+            fetched_at: value.fetched_at.and_then(|fetched_at| {
+                DateTime::from_timestamp(fetched_at.secs_since_epoch, fetched_at.nanos_since_epoch)
+            }),
+        }
+    }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 struct InstructionInternal {
     #[serde(with = "field_as_string")]
@@ -131,7 +985,7 @@ struct InstructionInternal {
     pub data: Vec<u8>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct AccountMetaInternal {
     #[serde(with = "field_as_string")]
@@ -150,10 +1004,30 @@ impl From<AccountMetaInternal> for AccountMeta {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 struct PubkeyInternal(#[serde(with = "field_as_string")] Pubkey);
 
+impl From<AccountMeta> for AccountMetaInternal {
+    fn from(val: AccountMeta) -> Self {
+        AccountMetaInternal {
+            pubkey: val.pubkey,
+            is_signer: val.is_signer,
+            is_writable: val.is_writable,
+        }
+    }
+}
+
+impl From<Instruction> for InstructionInternal {
+    fn from(val: Instruction) -> Self {
+        InstructionInternal {
+            program_id: val.program_id,
+            accounts: val.accounts.into_iter().map(Into::into).collect(),
+            data: val.data,
+        }
+    }
+}
+
 impl From<InstructionInternal> for Instruction {
     fn from(val: InstructionInternal) -> Self {
         Instruction {
@@ -195,6 +1069,1111 @@ impl From<SwapInstructionsResponseInternal> for SwapInstructionsResponse {
             prioritization_type: value.prioritization_type,
             dynamic_slippage_report: value.dynamic_slippage_report,
             simulation_error: value.simulation_error,
+            blockhash_with_metadata: value.blockhash_with_metadata.map(Into::into),
+            request_ids: Vec::new(),
+            warnings: Vec::new(),
         }
     }
 }
+
+impl From<SwapInstructionsResponse> for SwapInstructionsResponseInternal {
+    fn from(value: SwapInstructionsResponse) -> Self {
+        Self {
+            token_ledger_instruction: value.token_ledger_instruction.map(Into::into),
+            compute_budget_instructions: value
+                .compute_budget_instructions
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            setup_instructions: value
+                .setup_instructions
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            swap_instruction: value.swap_instruction.into(),
+            cleanup_instruction: value.cleanup_instruction.map(Into::into),
+            other_instructions: value
+                .other_instructions
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            address_lookup_table_addresses: value
+                .address_lookup_table_addresses
+                .into_iter()
+                .map(PubkeyInternal)
+                .collect(),
+            prioritization_fee_lamports: value.prioritization_fee_lamports,
+            compute_unit_limit: value.compute_unit_limit,
+            prioritization_type: value.prioritization_type,
+            dynamic_slippage_report: value.dynamic_slippage_report,
+            simulation_error: value.simulation_error,
+            blockhash_with_metadata: value.blockhash_with_metadata.map(Into::into),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{quote::PlatformFee, route_plan_with_metadata::RoutePlanStep};
+
+    // Pinned for downstream caches and comparisons
+    static_assertions::assert_impl_all!(SwapRequest: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(SwapRequestBuilder: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+    static_assertions::assert_impl_all!(PrioritizationType: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+    static_assertions::assert_impl_all!(DynamicSlippageReport: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(UiSimulationError: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+    static_assertions::assert_impl_all!(SwapResponse: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(SwapInstructionsResponse: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+    static_assertions::assert_impl_all!(BlockhashWithMetadata: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+    static_assertions::assert_impl_all!(AccountKeyMeta: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+    static_assertions::assert_impl_all!(SwapRequestError: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+
+    fn instruction(program_id: Pubkey, accounts: Vec<AccountMeta>, data: &[u8]) -> Instruction {
+        Instruction::new_with_bytes(program_id, data, accounts)
+    }
+
+    const JUPITER_PROGRAM: Pubkey = Pubkey::new_from_array([4; 32]);
+
+    /// Instruction of a program of its own, identified by `tag`
+    fn tagged(tag: u8) -> Instruction {
+        instruction(Pubkey::new_from_array([tag; 32]), Vec::new(), &[tag])
+    }
+
+    fn tags(instructions: &[Instruction]) -> Vec<u8> {
+        instructions
+            .iter()
+            .map(|instruction| instruction.data[0])
+            .collect()
+    }
+
+    fn create_token_account(token_account: Pubkey) -> Instruction {
+        instruction(
+            ASSOCIATED_TOKEN_PROGRAM_ID,
+            vec![
+                AccountMeta::new(Pubkey::new_unique(), true),
+                AccountMeta::new(token_account, false),
+            ],
+            &[1],
+        )
+    }
+
+    /// Response whose instructions are tagged in the order of [`SwapInstructionsResponse::instructions`]
+    fn swap_instructions_response() -> SwapInstructionsResponse {
+        SwapInstructionsResponse {
+            token_ledger_instruction: Some(tagged(3)),
+            compute_budget_instructions: vec![tagged(1)],
+            setup_instructions: vec![tagged(2)],
+            swap_instruction: tagged(4),
+            cleanup_instruction: Some(tagged(5)),
+            other_instructions: vec![tagged(6)],
+            address_lookup_table_addresses: Vec::new(),
+            prioritization_fee_lamports: 0,
+            compute_unit_limit: 0,
+            prioritization_type: None,
+            dynamic_slippage_report: None,
+            simulation_error: None,
+            blockhash_with_metadata: None,
+            request_ids: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn account_keys_merge_writable() {
+        let shared = Pubkey::new_unique();
+        let response = SwapInstructionsResponse {
+            setup_instructions: vec![instruction(
+                Pubkey::new_from_array([2; 32]),
+                vec![AccountMeta::new_readonly(shared, false)],
+                &[2],
+            )],
+            swap_instruction: instruction(
+                JUPITER_PROGRAM,
+                vec![AccountMeta::new(shared, false)],
+                &[4],
+            ),
+            ..swap_instructions_response()
+        };
+        let account_keys = response.account_keys();
+        let shared_meta = account_keys
+            .iter()
+            .find(|account_key| account_key.pubkey == shared)
+            .unwrap();
+        assert!(shared_meta.is_writable);
+        assert!(!shared_meta.is_signer);
+        assert_eq!(
+            account_keys
+                .iter()
+                .filter(|account_key| account_key.pubkey == shared)
+                .count(),
+            1
+        );
+        assert_eq!(response.writable_accounts(), [shared]);
+    }
+
+    #[test]
+    fn account_keys_merge_signer() {
+        let user = Pubkey::new_unique();
+        let response = SwapInstructionsResponse {
+            setup_instructions: vec![instruction(
+                Pubkey::new_from_array([2; 32]),
+                vec![AccountMeta::new(user, false)],
+                &[2],
+            )],
+            swap_instruction: instruction(
+                JUPITER_PROGRAM,
+                vec![AccountMeta::new_readonly(user, true)],
+                &[4],
+            ),
+            ..swap_instructions_response()
+        };
+        let account_keys = response.account_keys();
+        let user_meta = account_keys
+            .iter()
+            .find(|account_key| account_key.pubkey == user);
+        assert_eq!(
+            user_meta,
+            Some(&AccountKeyMeta {
+                pubkey: user,
+                is_signer: true,
+                is_writable: true,
+            })
+        );
+    }
+
+    #[test]
+    fn account_keys_include_programs_and_lookup_tables() {
+        let writable = Pubkey::new_unique();
+        let lookup_table = Pubkey::new_unique();
+        let response = SwapInstructionsResponse {
+            swap_instruction: instruction(
+                JUPITER_PROGRAM,
+                vec![AccountMeta::new(writable, false)],
+                &[4],
+            ),
+            address_lookup_table_addresses: vec![lookup_table, lookup_table],
+            ..swap_instructions_response()
+        };
+        let account_keys = response.account_keys();
+        let pubkeys: Vec<Pubkey> = account_keys
+            .iter()
+            .map(|account_key| account_key.pubkey)
+            .collect();
+        assert_eq!(
+            pubkeys,
+            [
+                Pubkey::new_from_array([1; 32]),
+                Pubkey::new_from_array([2; 32]),
+                Pubkey::new_from_array([3; 32]),
+                writable,
+                JUPITER_PROGRAM,
+                Pubkey::new_from_array([5; 32]),
+                Pubkey::new_from_array([6; 32]),
+                lookup_table,
+            ]
+        );
+        assert_eq!(
+            account_keys.last(),
+            Some(&AccountKeyMeta::readonly(lookup_table))
+        );
+        // Programs are readonly, the lookup tables are not accounts of the message
+        assert_eq!(response.writable_accounts(), [writable]);
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    fn blockhash_with_metadata() -> BlockhashWithMetadata {
+        BlockhashWithMetadata {
+            blockhash: Hash::new_unique(),
+            last_valid_block_height: 1_000,
+            fetched_at: None,
+        }
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    fn into_versioned_message_uses_response_blockhash() {
+        let payer = Pubkey::new_unique();
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique()],
+        };
+        let response = SwapInstructionsResponse {
+            address_lookup_table_addresses: vec![lookup_table.key],
+            blockhash_with_metadata: Some(blockhash_with_metadata()),
+            ..swap_instructions_response()
+        };
+        let message = response
+            .into_versioned_message(&payer, std::slice::from_ref(&lookup_table))
+            .unwrap();
+        let VersionedMessage::V0(message) = &message else {
+            panic!("expected a v0 message");
+        };
+        assert_eq!(
+            message.recent_blockhash,
+            response.blockhash_with_metadata.as_ref().unwrap().blockhash
+        );
+        assert_eq!(message.account_keys[0], payer);
+        let data: Vec<u8> = message
+            .instructions
+            .iter()
+            .map(|instruction| instruction.data[0])
+            .collect();
+        assert_eq!(data, tags(&response.instructions()));
+        // Same message as built with the blockhash given explicitly
+        assert_eq!(
+            response
+                .build_message(&payer, &[lookup_table], message.recent_blockhash, false)
+                .unwrap(),
+            VersionedMessage::V0(message.clone())
+        );
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    fn into_versioned_message_requires_blockhash_and_lookup_tables() {
+        let payer = Pubkey::new_unique();
+        let lookup_table = Pubkey::new_unique();
+        assert_eq!(
+            swap_instructions_response().into_versioned_message(&payer, &[]),
+            Err(BuildError::MissingBlockhash)
+        );
+        let response = SwapInstructionsResponse {
+            address_lookup_table_addresses: vec![lookup_table],
+            blockhash_with_metadata: Some(blockhash_with_metadata()),
+            ..swap_instructions_response()
+        };
+        assert_eq!(
+            response.into_versioned_message(&payer, &[]),
+            Err(BuildError::MissingAddressLookupTable(lookup_table))
+        );
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    fn durable_nonce_advance_comes_first_and_replaces_the_blockhash() {
+        let payer = Keypair::new();
+        let nonce_authority = Keypair::new();
+        let nonce_account = Pubkey::new_unique();
+        let nonce_hash = Hash::new_unique();
+        let response = SwapInstructionsResponse {
+            // Ignored in favour of the nonce
+            blockhash_with_metadata: Some(blockhash_with_metadata()),
+            ..swap_instructions_response()
+        };
+
+        let transaction = response
+            .build_with_durable_nonce(
+                &payer.pubkey(),
+                &nonce_account,
+                &nonce_authority.pubkey(),
+                nonce_hash,
+                &[],
+                &[&payer, &nonce_authority],
+            )
+            .unwrap();
+        assert_eq!(*transaction.message.recent_blockhash(), nonce_hash);
+        assert_eq!(transaction.signatures.len(), 2);
+        assert!(transaction.verify_with_results().iter().all(|valid| *valid));
+
+        let account_keys = transaction.message.static_account_keys();
+        let instructions = transaction.message.instructions();
+        let advance_nonce =
+            system_instruction::advance_nonce_account(&nonce_account, &nonce_authority.pubkey());
+        assert_eq!(
+            account_keys[usize::from(instructions[0].program_id_index)],
+            advance_nonce.program_id
+        );
+        assert_eq!(instructions[0].data, advance_nonce.data);
+        let data: Vec<u8> = instructions[1..]
+            .iter()
+            .map(|instruction| instruction.data[0])
+            .collect();
+        assert_eq!(data, tags(&response.instructions()));
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    fn durable_nonce_authority_must_sign() {
+        let payer = Keypair::new();
+        let nonce_authority = Pubkey::new_unique();
+        assert_eq!(
+            swap_instructions_response().build_with_durable_nonce(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                &nonce_authority,
+                Hash::new_unique(),
+                &[],
+                &[&payer],
+            ),
+            Err(BuildTxError::NonceAuthorityNotASigner(nonce_authority))
+        );
+
+        // The payer may be the nonce authority
+        let transaction = swap_instructions_response()
+            .build_with_durable_nonce(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                &payer.pubkey(),
+                Hash::new_unique(),
+                &[],
+                &[&payer],
+            )
+            .unwrap();
+        assert_eq!(transaction.signatures.len(), 1);
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    fn swap_response_fixture(transactions: &[VersionedTransaction]) -> SwapResponse {
+        let encoded: Vec<String> = transactions
+            .iter()
+            .map(|transaction| STANDARD.encode(bincode::serialize(transaction).unwrap()))
+            .collect();
+        let (swap_transaction, additional_transactions) = encoded.split_last().unwrap();
+        serde_json::from_value(serde_json::json!({
+            "swapTransaction": swap_transaction,
+            "additionalTransactions": additional_transactions,
+            "lastValidBlockHeight": 301_000_150,
+            "prioritizationFeeLamports": 5_000,
+            "computeUnitLimit": 200_000
+        }))
+        .unwrap()
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    fn legacy_transaction(payer: &Pubkey, tag: u8) -> Transaction {
+        let instruction = instruction(Pubkey::new_unique(), Vec::new(), &[tag]);
+        let message = Message::new_with_blockhash(&[instruction], Some(payer), &Hash::new_unique());
+        Transaction::new_unsigned(message)
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    fn legacy_transactions_are_decoded_in_send_order() {
+        let payer = Pubkey::new_unique();
+        let setup = legacy_transaction(&payer, 1);
+        let swap = legacy_transaction(&payer, 2);
+        let response = swap_response_fixture(&[
+            VersionedTransaction::from(setup.clone()),
+            VersionedTransaction::from(swap.clone()),
+        ]);
+
+        assert_eq!(response.legacy_transaction().unwrap(), swap);
+        assert_eq!(response.legacy_transactions().unwrap(), [setup, swap]);
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    fn versioned_or_invalid_transactions_are_not_decoded_as_legacy() {
+        let payer = Pubkey::new_unique();
+        let message = swap_instructions_response()
+            .build_message(&payer, &[], Hash::new_unique(), false)
+            .unwrap();
+        let versioned = VersionedTransaction {
+            signatures: vec![Signature::default()],
+            message,
+        };
+        let response = swap_response_fixture(std::slice::from_ref(&versioned));
+        assert!(matches!(
+            response.legacy_transaction(),
+            Err(SwapTransactionDecodeError::NotLegacy)
+        ));
+        assert_eq!(response.versioned_transactions().unwrap(), [versioned]);
+
+        // One versioned transaction among legacy ones fails them all
+        let response = swap_response_fixture(&[
+            VersionedTransaction::from(legacy_transaction(&payer, 1)),
+            response.versioned_transactions().unwrap().remove(0),
+        ]);
+        assert!(matches!(
+            response.legacy_transactions(),
+            Err(SwapTransactionDecodeError::NotLegacy)
+        ));
+
+        let response = SwapResponse {
+            swap_transaction: vec![1, 2, 3],
+            ..SwapResponse::default()
+        };
+        assert!(matches!(
+            response.legacy_transaction(),
+            Err(SwapTransactionDecodeError::InvalidTransaction(_))
+        ));
+    }
+
+    /// Legacy transaction paid by `payer` also signed by `signer`, with the signature of `payer` on it
+    #[cfg(feature = "solana-sdk")]
+    fn partially_signed_transaction(payer: &Keypair, signer: &Pubkey) -> VersionedTransaction {
+        let instruction = instruction(
+            Pubkey::new_unique(),
+            vec![AccountMeta::new(*signer, true)],
+            &[1],
+        );
+        let message =
+            Message::new_with_blockhash(&[instruction], Some(&payer.pubkey()), &Hash::new_unique());
+        let mut transaction = Transaction::new_unsigned(message);
+        transaction.partial_sign(&[payer], transaction.message.recent_blockhash);
+        VersionedTransaction::from(transaction)
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    fn decode_signed(signed: &str) -> VersionedTransaction {
+        bincode::deserialize(&STANDARD.decode(signed).unwrap()).unwrap()
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    fn sign_with_adds_a_signature_over_the_message() {
+        let jupiter = Keypair::new();
+        let user = Keypair::new();
+        let response =
+            swap_response_fixture(&[partially_signed_transaction(&jupiter, &user.pubkey())]);
+
+        let signed = decode_signed(&response.sign_with(&[&user]).unwrap());
+        let message = signed.message.serialize();
+        assert!(signed.signatures[1].verify(user.pubkey().as_ref(), &message));
+        // The signature of the fee payer is kept
+        assert!(signed.signatures[0].verify(jupiter.pubkey().as_ref(), &message));
+        assert!(signed
+            .verify_with_results()
+            .into_iter()
+            .all(|verified| verified));
+
+        let stranger = Keypair::new();
+        assert!(matches!(
+            response.sign_with(&[&stranger]),
+            Err(SignError::NotASigner(key)) if key == stranger.pubkey()
+        ));
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    fn sign_all_with_signs_every_transaction_in_send_order() {
+        let jupiter = Keypair::new();
+        let user = Keypair::new();
+        let transactions = [
+            partially_signed_transaction(&jupiter, &user.pubkey()),
+            partially_signed_transaction(&jupiter, &user.pubkey()),
+        ];
+        let response = swap_response_fixture(&transactions);
+
+        let signed: Vec<_> = response
+            .sign_all_with(&[&user])
+            .unwrap()
+            .iter()
+            .map(|signed| decode_signed(signed))
+            .collect();
+        assert_eq!(signed.len(), 2);
+        for (signed, transaction) in signed.iter().zip(&transactions) {
+            assert_eq!(signed.message, transaction.message);
+            assert!(signed
+                .verify_with_results()
+                .into_iter()
+                .all(|verified| verified));
+        }
+        assert_eq!(
+            decode_signed(&response.sign_with(&[&user]).unwrap()),
+            signed[1]
+        );
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    fn recent_blockhash_is_replaced_and_signatures_cleared() {
+        let jupiter = Keypair::new();
+        let user = Pubkey::new_unique();
+        let transactions = [
+            partially_signed_transaction(&jupiter, &user),
+            partially_signed_transaction(&jupiter, &user),
+        ];
+        let recent_blockhash = Hash::new_unique();
+
+        let response = swap_response_fixture(&transactions)
+            .with_recent_blockhash(recent_blockhash, 301_000_300)
+            .unwrap();
+        assert_eq!(response.last_valid_block_height, 301_000_300);
+        let rebuilt = response.versioned_transactions().unwrap();
+        assert_eq!(rebuilt.len(), 2);
+        for (rebuilt, transaction) in rebuilt.iter().zip(&transactions) {
+            assert_eq!(*rebuilt.message.recent_blockhash(), recent_blockhash);
+            assert_ne!(
+                rebuilt.message.recent_blockhash(),
+                transaction.message.recent_blockhash()
+            );
+            assert_eq!(
+                rebuilt.message.instructions(),
+                transaction.message.instructions()
+            );
+            assert_eq!(rebuilt.signatures, [Signature::default(); 2]);
+        }
+    }
+
+    /// Response whose swap instruction is signed by `user`, uses `accounts` and carries `data_len` bytes
+    fn sized_swap_instructions_response(
+        user: Pubkey,
+        accounts: &[Pubkey],
+        data_len: usize,
+    ) -> SwapInstructionsResponse {
+        let accounts = std::iter::once(AccountMeta::new(user, true))
+            .chain(
+                accounts
+                    .iter()
+                    .map(|account| AccountMeta::new(*account, false)),
+            )
+            .collect();
+        SwapInstructionsResponse {
+            swap_instruction: instruction(JUPITER_PROGRAM, accounts, &vec![0; data_len]),
+            ..swap_instructions_response()
+        }
+    }
+
+    #[test]
+    fn transaction_size_limit_is_the_packet_size() {
+        let user = Pubkey::new_unique();
+        let base_size =
+            sized_swap_instructions_response(user, &[], 200).estimated_transaction_size();
+        let data_len = 200 + PACKET_DATA_SIZE - base_size;
+
+        let response = sized_swap_instructions_response(user, &[], data_len);
+        assert_eq!(response.estimated_transaction_size(), 1232);
+        assert!(!response.likely_exceeds_transaction_size());
+
+        let response = sized_swap_instructions_response(user, &[], data_len + 1);
+        assert_eq!(response.estimated_transaction_size(), 1233);
+        assert!(response.likely_exceeds_transaction_size());
+    }
+
+    #[test]
+    fn looked_up_accounts_cost_an_index() {
+        let user = Pubkey::new_unique();
+        let accounts: Vec<_> = (0..20).map(|_| Pubkey::new_unique()).collect();
+        let without_accounts = sized_swap_instructions_response(user, &[], 1);
+        let mut with_accounts = sized_swap_instructions_response(user, &accounts, 1);
+        // An index in the lookup table and one in the instruction, instead of a 32 bytes static key
+        assert_eq!(
+            with_accounts.estimated_transaction_size(),
+            without_accounts.estimated_transaction_size() + 20 * 2
+        );
+
+        with_accounts.address_lookup_table_addresses = vec![Pubkey::new_unique(); 2];
+        assert_eq!(with_accounts.lookup_table_count(), 1);
+        assert_eq!(
+            with_accounts.estimated_transaction_size(),
+            without_accounts.estimated_transaction_size() + 20 * 2 + 1 + 32 + 1
+        );
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    fn estimated_transaction_size_matches_the_compiled_transaction() {
+        let user = Pubkey::new_unique();
+        let accounts: Vec<_> = (0..20).map(|_| Pubkey::new_unique()).collect();
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: accounts.clone(),
+        };
+        let mut response = sized_swap_instructions_response(user, &accounts, 100);
+        response.address_lookup_table_addresses = vec![lookup_table.key];
+        let transaction_size =
+            |response: &SwapInstructionsResponse, lookup_tables: &[AddressLookupTableAccount]| {
+                let message = response
+                    .build_message(&user, lookup_tables, Hash::new_unique(), false)
+                    .unwrap();
+                bincode::serialized_size(&VersionedTransaction {
+                    signatures: vec![Signature::default()],
+                    message,
+                })
+                .unwrap() as usize
+            };
+
+        assert_eq!(
+            response.estimated_transaction_size(),
+            transaction_size(&response, std::slice::from_ref(&lookup_table))
+        );
+        // Without the table every account is a static key
+        let without_lookup_table = SwapInstructionsResponse {
+            address_lookup_table_addresses: Vec::new(),
+            ..response.clone()
+        };
+        assert_eq!(
+            transaction_size(&without_lookup_table, &[]),
+            response.estimated_transaction_size() + 20 * 31 - (1 + 32 + 1)
+        );
+    }
+
+    fn token_instruction(program_id: Pubkey, discriminator: u8) -> Instruction {
+        instruction(
+            program_id,
+            vec![AccountMeta::new(Pubkey::new_unique(), false)],
+            &[discriminator],
+        )
+    }
+
+    #[test]
+    fn sol_wrapping_is_detected_from_the_token_instructions() {
+        let response = swap_instructions_response();
+        assert!(!response.wraps_sol());
+        assert!(!response.unwraps_sol());
+
+        let response = SwapInstructionsResponse {
+            setup_instructions: vec![
+                create_token_account(Pubkey::new_unique()),
+                token_instruction(TOKEN_PROGRAM_ID, SYNC_NATIVE_DISCRIMINATOR),
+            ],
+            cleanup_instruction: Some(token_instruction(
+                TOKEN_PROGRAM_ID,
+                CLOSE_ACCOUNT_DISCRIMINATOR,
+            )),
+            ..swap_instructions_response()
+        };
+        assert!(response.wraps_sol());
+        assert!(response.unwraps_sol());
+
+        // wSOL is never a Token-2022 account, and other token instructions do not wrap
+        let response = SwapInstructionsResponse {
+            setup_instructions: vec![
+                token_instruction(crate::ata::TOKEN_2022_PROGRAM_ID, SYNC_NATIVE_DISCRIMINATOR),
+                token_instruction(TOKEN_PROGRAM_ID, CLOSE_ACCOUNT_DISCRIMINATOR),
+            ],
+            cleanup_instruction: Some(token_instruction(
+                TOKEN_PROGRAM_ID,
+                SYNC_NATIVE_DISCRIMINATOR,
+            )),
+            ..swap_instructions_response()
+        };
+        assert!(!response.wraps_sol());
+        assert!(!response.unwraps_sol());
+    }
+
+    #[test]
+    fn builder_keeps_all_instructions() {
+        let response = swap_instructions_response();
+        let instructions = SwapInstructionsBuilder::new(response.clone())
+            .build()
+            .unwrap();
+        assert_eq!(tags(&instructions), [1, 2, 3, 4, 5, 6]);
+        // Same order as the response
+        assert_eq!(instructions, response.instructions());
+    }
+
+    #[test]
+    fn builder_orders_prepended_and_appended_instructions() {
+        let instructions = SwapInstructionsBuilder::new(swap_instructions_response())
+            .prepend(vec![tagged(10)])
+            .prepend(vec![tagged(11)])
+            .append(vec![tagged(20)])
+            .append(vec![tagged(21)])
+            .build()
+            .unwrap();
+        assert_eq!(tags(&instructions), [1, 2, 3, 10, 11, 4, 20, 21, 5, 6]);
+    }
+
+    #[test]
+    fn builder_without_token_ledger_prepends_before_swap() {
+        let response = SwapInstructionsResponse {
+            token_ledger_instruction: None,
+            ..swap_instructions_response()
+        };
+        let instructions = SwapInstructionsBuilder::new(response)
+            .prepend(vec![tagged(10)])
+            .build()
+            .unwrap();
+        assert_eq!(tags(&instructions), [1, 2, 10, 4, 5, 6]);
+    }
+
+    #[test]
+    fn builder_skips_setup_and_cleanup() {
+        let instructions = SwapInstructionsBuilder::new(swap_instructions_response())
+            .skip_setup()
+            .skip_cleanup()
+            .append(vec![tagged(20)])
+            .build()
+            .unwrap();
+        assert_eq!(tags(&instructions), [1, 3, 4, 20, 6]);
+    }
+
+    #[test]
+    fn builder_rejects_skipped_token_account_creation() {
+        let token_account = Pubkey::new_unique();
+        let mut response = swap_instructions_response();
+        response
+            .setup_instructions
+            .push(create_token_account(token_account));
+        assert_eq!(
+            SwapInstructionsBuilder::new(response.clone())
+                .skip_setup()
+                .build(),
+            Err(SwapInstructionsBuilderError::SkippedTokenAccountCreation(
+                token_account
+            ))
+        );
+        let instructions = SwapInstructionsBuilder::new(response.clone())
+            .skip_setup()
+            .token_accounts_exist()
+            .build()
+            .unwrap();
+        assert_eq!(tags(&instructions), [1, 3, 4, 5, 6]);
+        // Kept setup instructions create the account themselves
+        let instructions = SwapInstructionsBuilder::from(response).build().unwrap();
+        assert_eq!(instructions.len(), 7);
+    }
+
+    #[test]
+    fn builder_replaces_compute_budget() {
+        let instructions = SwapInstructionsBuilder::new(swap_instructions_response())
+            .replace_compute_budget(Some(300_000), Some(1_000))
+            .build()
+            .unwrap();
+        assert_eq!(
+            compute_budget::compute_unit_limit(&instructions[0]),
+            Some(300_000)
+        );
+        assert_eq!(
+            compute_budget::compute_unit_price_micro_lamports(&instructions[1]),
+            Some(1_000)
+        );
+        assert_eq!(tags(&instructions[2..]), [2, 3, 4, 5, 6]);
+
+        let instructions = SwapInstructionsBuilder::new(swap_instructions_response())
+            .replace_compute_budget(None, None)
+            .build()
+            .unwrap();
+        assert_eq!(tags(&instructions), [2, 3, 4, 5, 6]);
+    }
+
+    fn exact_in_quote() -> QuoteResponse {
+        QuoteResponse {
+            in_amount: 1_000,
+            out_amount: 2_000,
+            other_amount_threshold: 1_990,
+            swap_mode: SwapMode::ExactIn,
+            route_plan: vec![RoutePlanStep {
+                percent: 100,
+                ..RoutePlanStep::default()
+            }],
+            ..QuoteResponse::default()
+        }
+    }
+
+    fn swap_request(quote_response: QuoteResponse) -> SwapRequest {
+        SwapRequest {
+            user_public_key: Pubkey::new_unique(),
+            quote_response,
+            config: TransactionConfig::default(),
+        }
+    }
+
+    #[test]
+    fn from_quote_carries_the_quote_and_user_over() {
+        let user = Pubkey::new_unique();
+        let request = SwapRequest::from_quote(exact_in_quote(), user)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request,
+            SwapRequest {
+                user_public_key: user,
+                quote_response: exact_in_quote(),
+                config: TransactionConfig::default(),
+            }
+        );
+        assert!(request.config.wrap_and_unwrap_sol);
+        assert_eq!(request.config.fee_account, None);
+    }
+
+    #[test]
+    fn from_quote_config_replaces_the_defaults() {
+        let fee_account = Pubkey::new_unique();
+        let config = TransactionConfig {
+            wrap_and_unwrap_sol: false,
+            ..TransactionConfig::default()
+        };
+        let mut quote = exact_in_quote();
+        quote.platform_fee = Some(PlatformFee {
+            amount: 1,
+            fee_bps: 10,
+            fee_mint: None,
+        });
+
+        let request = SwapRequest::from_quote(quote.clone(), Pubkey::new_unique())
+            .config(config.clone())
+            .fee_account(fee_account)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.config,
+            TransactionConfig {
+                fee_account: Some(fee_account),
+                ..config.clone()
+            }
+        );
+        // A config set last replaces the fee account
+        let request = SwapRequest::from_quote(quote, Pubkey::new_unique())
+            .fee_account(fee_account)
+            .config(config.clone())
+            .build()
+            .unwrap();
+        assert_eq!(request.config, config);
+        assert_eq!(
+            SwapRequest::from_quote(exact_in_quote(), Pubkey::default()).build(),
+            Err(SwapRequestError::InvalidPublicKey)
+        );
+    }
+
+    #[test]
+    fn validate_accepts_a_consistent_request() {
+        assert_eq!(swap_request(exact_in_quote()).validate(), Ok(()));
+        let exact_out = QuoteResponse {
+            swap_mode: SwapMode::ExactOut,
+            other_amount_threshold: 1_010,
+            ..exact_in_quote()
+        };
+        assert_eq!(swap_request(exact_out).validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_missing_user_and_platform_fee() {
+        let mut request = swap_request(exact_in_quote());
+        request.user_public_key = Pubkey::default();
+        assert_eq!(request.validate(), Err(SwapRequestError::InvalidPublicKey));
+
+        let builder = SwapRequest::from_quote(exact_in_quote(), Pubkey::new_unique())
+            .fee_account(Pubkey::new_unique());
+        assert_eq!(
+            builder.clone().build(),
+            Err(SwapRequestError::FeeAccountWithoutPlatformFee)
+        );
+        let mut quote = exact_in_quote();
+        quote.platform_fee = Some(PlatformFee {
+            amount: 1,
+            fee_bps: 10,
+            fee_mint: None,
+        });
+        let request = SwapRequest::from_quote(quote, Pubkey::new_unique())
+            .fee_account(Pubkey::new_unique())
+            .build();
+        assert!(request.is_ok());
+    }
+
+    #[test]
+    fn validate_checks_the_quote_swap_mode() {
+        // An ExactOut quote relabelled as ExactIn
+        let relabelled = QuoteResponse {
+            other_amount_threshold: 2_010,
+            ..exact_in_quote()
+        };
+        let mut exact_out = relabelled.clone();
+        exact_out.swap_mode = SwapMode::ExactOut;
+        exact_out.other_amount_threshold = 990;
+        assert_eq!(
+            swap_request(relabelled).validate(),
+            Err(SwapRequestError::ThresholdNotMatchingSwapMode {
+                swap_mode: SwapMode::ExactIn,
+                other_amount_threshold: 2_010,
+                in_amount: 1_000,
+                out_amount: 2_000,
+            })
+        );
+        assert!(matches!(
+            swap_request(exact_out).validate(),
+            Err(SwapRequestError::ThresholdNotMatchingSwapMode {
+                swap_mode: SwapMode::ExactOut,
+                ..
+            })
+        ));
+
+        let mut request = swap_request(QuoteResponse {
+            swap_mode: SwapMode::ExactOut,
+            other_amount_threshold: 1_010,
+            ..exact_in_quote()
+        });
+        request.config.use_token_ledger = Some(true);
+        assert_eq!(
+            request.validate(),
+            Err(SwapRequestError::TokenLedgerWithExactOut)
+        );
+    }
+
+    #[test]
+    fn validate_checks_the_config_against_the_quote() {
+        let mut request = swap_request(exact_in_quote());
+        request.config.compute_unit_price_micro_lamports =
+            Some(crate::transaction_config::ComputeUnitPriceMicroLamports::Auto);
+        request.config.prioritization_fee_lamports =
+            Some(crate::transaction_config::PrioritizationFeeLamports::Auto);
+        assert_eq!(
+            request.validate(),
+            Err(TransactionConfigError::ComputeUnitPriceWithPrioritizationFee.into())
+        );
+    }
+
+    #[test]
+    fn route_plan_splits_are_only_checked_when_opted_in() {
+        let mut quote = exact_in_quote();
+        quote.route_plan[0].percent = 90;
+        let user = Pubkey::new_unique();
+        let request = SwapRequest::from_quote(quote.clone(), user)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            request.validate_route_plan(),
+            Err(SwapRequestError::InvalidRoutePlan(_))
+        ));
+        assert!(matches!(
+            SwapRequest::from_quote(quote, user)
+                .validate_route_plan(true)
+                .build(),
+            Err(SwapRequestError::InvalidRoutePlan(_))
+        ));
+    }
+
+    #[test]
+    fn compute_budget_accessors_parse_the_instructions() {
+        let response = SwapInstructionsResponse {
+            compute_budget_instructions: vec![
+                // RequestHeapFrame, skipped
+                Instruction::new_with_bytes(
+                    solana_compute_budget_interface::id(),
+                    &[1, 0, 0, 4, 0],
+                    Vec::new(),
+                ),
+                Instruction::new_with_bytes(
+                    solana_compute_budget_interface::id(),
+                    &[2, 0xc0, 0x27, 0x09, 0x00],
+                    Vec::new(),
+                ),
+                Instruction::new_with_bytes(
+                    solana_compute_budget_interface::id(),
+                    &[3, 0x10, 0x27, 0, 0, 0, 0, 0, 0],
+                    Vec::new(),
+                ),
+            ],
+            ..swap_instructions_response()
+        };
+        assert_eq!(response.compute_unit_limit(), Some(600_000));
+        assert_eq!(response.compute_unit_price_micro_lamports(), Some(10_000));
+
+        let response = response.without_compute_budget_instructions();
+        assert!(response.compute_budget_instructions.is_empty());
+        assert_eq!(response.compute_unit_limit(), None);
+        assert_eq!(response.compute_unit_price_micro_lamports(), None);
+    }
+
+    fn swap_instructions_fixture(
+        blockhash_with_metadata: Option<serde_json::Value>,
+    ) -> serde_json::Value {
+        let instruction = serde_json::json!({
+            "programId": "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4",
+            "accounts": [],
+            "data": ""
+        });
+        let mut fixture = serde_json::json!({
+            "computeBudgetInstructions": [],
+            "setupInstructions": [],
+            "swapInstruction": instruction,
+            "otherInstructions": [],
+            "addressLookupTableAddresses": [],
+            "prioritizationFeeLamports": 0,
+            "computeUnitLimit": 200000
+        });
+        if let Some(blockhash_with_metadata) = blockhash_with_metadata {
+            fixture["blockhashWithMetadata"] = blockhash_with_metadata;
+        }
+        fixture
+    }
+
+    fn parse_swap_instructions(fixture: serde_json::Value) -> SwapInstructionsResponse {
+        serde_json::from_value::<SwapInstructionsResponseInternal>(fixture)
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn blockhash_with_metadata_is_parsed() {
+        let bytes: Vec<u8> = (1..=32).collect();
+        let response =
+            parse_swap_instructions(swap_instructions_fixture(Some(serde_json::json!({
+                "blockhash": bytes,
+                "lastValidBlockHeight": 301_000_150,
+                "fetchedAt": { "secs_since_epoch": 1_740_000_000, "nanos_since_epoch": 500 }
+            }))));
+        let blockhash_with_metadata = response.blockhash_with_metadata.clone().unwrap();
+        assert_eq!(
+            blockhash_with_metadata.blockhash,
+            Hash::new_from_array(bytes.try_into().unwrap())
+        );
+        assert_eq!(blockhash_with_metadata.last_valid_block_height, 301_000_150);
+        assert_eq!(
+            blockhash_with_metadata.fetched_at,
+            DateTime::from_timestamp(1_740_000_000, 500)
+        );
+
+        // Serialized back in the server format
+        let internal = SwapInstructionsResponseInternal::from(response.clone());
+        assert_eq!(
+            parse_swap_instructions(serde_json::to_value(internal).unwrap()),
+            response
+        );
+    }
+
+    #[test]
+    fn blockhash_with_metadata_is_optional() {
+        let response = parse_swap_instructions(swap_instructions_fixture(None));
+        assert_eq!(response.blockhash_with_metadata, None);
+
+        let response =
+            parse_swap_instructions(swap_instructions_fixture(Some(serde_json::json!({
+                "blockhash": vec![7; 32],
+                "lastValidBlockHeight": 10
+            }))));
+        assert_eq!(response.blockhash_with_metadata.unwrap().fetched_at, None);
+    }
+
+    #[test]
+    fn swap_request_round_trips_without_nulls() {
+        let quote_response: QuoteResponse =
+            serde_json::from_value(crate::quote::tests::quote_response_fixture()).unwrap();
+        let mut request = SwapRequest::from_quote(quote_response, Pubkey::new_unique())
+            .build()
+            .unwrap();
+        request.config.compute_unit_price_micro_lamports =
+            Some(crate::transaction_config::ComputeUnitPriceMicroLamports::MicroLamports(500));
+
+        let serialized = serde_json::to_value(&request).unwrap();
+        crate::quote::tests::assert_no_nulls(&serialized);
+        // The config is flattened into the body
+        assert_eq!(serialized["computeUnitPriceMicroLamports"], 500);
+        assert_eq!(
+            serialized["quoteResponse"],
+            crate::quote::tests::quote_response_fixture()
+        );
+        assert_eq!(
+            serde_json::from_value::<SwapRequest>(serialized).unwrap(),
+            request
+        );
+    }
+
+    #[test]
+    fn additional_transactions_are_base64_and_omitted_when_empty() {
+        let swap_response = SwapResponse {
+            swap_transaction: vec![3],
+            additional_transactions: vec![vec![1, 2], vec![0xff]],
+            ..SwapResponse::default()
+        };
+        let serialized = serde_json::to_value(&swap_response).unwrap();
+        assert_eq!(
+            serialized["additionalTransactions"],
+            serde_json::json!(["AQI=", "/w=="])
+        );
+        assert_eq!(
+            serde_json::from_value::<SwapResponse>(serialized).unwrap(),
+            swap_response
+        );
+
+        let serialized = serde_json::to_value(SwapResponse::default()).unwrap();
+        assert!(serialized.get("additionalTransactions").is_none());
+        assert!(serde_json::from_value::<SwapResponse>(serialized)
+            .unwrap()
+            .additional_transactions
+            .is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+//! Mock Jupiter server returning canned responses, to test code using [`JupiterSwapApiClient`]
+//!
+//! Requires the `test-utils` feature.
+
+use serde::Serialize;
+use solana_pubkey::Pubkey;
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::{
+    quote::QuoteResponse,
+    swap::{SwapInstructionsResponse, SwapResponse},
+    JupiterSwapApiClient,
+};
+
+pub struct MockJupiterServer {
+    server: MockServer,
+}
+
+impl MockJupiterServer {
+    pub async fn start() -> Self {
+        Self {
+            server: MockServer::start().await,
+        }
+    }
+
+    /// Base path to point a client at
+    pub fn base_path(&self) -> String {
+        self.server.uri()
+    }
+
+    /// Client without api key pointed at the server
+    pub fn client(&self) -> JupiterSwapApiClient {
+        JupiterSwapApiClient::new(self.base_path(), None)
+    }
+
+    /// Access the underlying server, e.g. to mount custom mocks or inspect received requests
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Answer every GET /quote with `quote_response`
+    pub async fn mock_quote(&self, quote_response: &QuoteResponse) {
+        self.mount_json("GET", "/quote", quote_response).await;
+    }
+
+    /// Answer GET /quote for the given mints with `quote_response`
+    pub async fn mock_quote_for(
+        &self,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+        quote_response: &QuoteResponse,
+    ) {
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .and(query_param("inputMint", input_mint.to_string()))
+            .and(query_param("outputMint", output_mint.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(quote_response))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Fail every GET /quote with `status` and a Jupiter error body, e.g. `COULD_NOT_FIND_ANY_ROUTE`
+    pub async fn mock_quote_error(&self, status: u16, error_code: &str, message: &str) {
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(
+                ResponseTemplate::new(status).set_body_json(serde_json::json!({
+                    "error": message,
+                    "errorCode": error_code,
+                })),
+            )
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answer every POST /swap with `swap_response`
+    pub async fn mock_swap(&self, swap_response: &SwapResponse) {
+        self.mount_json("POST", "/swap", swap_response).await;
+    }
+
+    /// Answer every POST /swap-instructions with `swap_instructions_response`
+    pub async fn mock_swap_instructions(
+        &self,
+        swap_instructions_response: &SwapInstructionsResponse,
+    ) {
+        self.mount_json("POST", "/swap-instructions", swap_instructions_response)
+            .await;
+    }
+
+    /// Fail every request to `endpoint`, e.g. `/swap`, with `status` and `body`
+    pub async fn mock_error(&self, endpoint: &str, status: u16, body: &str) {
+        Mock::given(path(endpoint))
+            .respond_with(ResponseTemplate::new(status).set_body_string(body))
+            .mount(&self.server)
+            .await;
+    }
+
+    async fn mount_json<T: Serialize>(&self, http_method: &str, endpoint: &str, body: &T) {
+        Mock::given(method(http_method))
+            .and(path(endpoint))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&self.server)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+
+    use super::*;
+    use crate::{
+        mints::{NATIVE_MINT, USDC_MINT},
+        quote::{QuoteError, QuoteRequest},
+        swap::SwapRequest,
+        ErrorKind,
+    };
+
+    fn quote_request(output_mint: Pubkey) -> QuoteRequest {
+        QuoteRequest {
+            input_mint: NATIVE_MINT,
+            output_mint,
+            amount: 1_000,
+            ..QuoteRequest::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn quotes_are_answered_for_the_mocked_mints() {
+        let server = MockJupiterServer::start().await;
+        let quote_response = QuoteResponse {
+            input_mint: NATIVE_MINT,
+            output_mint: USDC_MINT,
+            in_amount: 1_000,
+            out_amount: 146,
+            ..QuoteResponse::default()
+        };
+        server
+            .mock_quote_for(&NATIVE_MINT, &USDC_MINT, &quote_response)
+            .await;
+        let client = server.client();
+
+        let quoted = client.quote(&quote_request(USDC_MINT)).await.unwrap();
+        assert_eq!(quoted.out_amount, 146);
+        let error = client
+            .quote(&quote_request(Pubkey::new_unique()))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+        assert_eq!(server.server().received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn quote_errors_are_mapped_by_the_client() {
+        let server = MockJupiterServer::start().await;
+        server
+            .mock_quote_error(400, "COULD_NOT_FIND_ANY_ROUTE", "Could not find any route")
+            .await;
+
+        let error = server
+            .client()
+            .quote(&quote_request(USDC_MINT))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                error.kind(),
+                ErrorKind::QuoteFailed { error: QuoteError::NoRoute, message }
+                    if message == "Could not find any route"
+            ),
+            "{error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn swaps_are_answered_with_the_mocked_responses() {
+        let server = MockJupiterServer::start().await;
+        let swap_response = SwapResponse {
+            swap_transaction: vec![1, 2, 3],
+            last_valid_block_height: 301_000_150,
+            ..SwapResponse::default()
+        };
+        let swap_instructions_response: SwapInstructionsResponse =
+            serde_json::from_value(serde_json::json!({
+                "computeBudgetInstructions": [],
+                "setupInstructions": [],
+                "swapInstruction": {
+                    "programId": "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4",
+                    "accounts": [],
+                    "data": "AQ=="
+                },
+                "otherInstructions": [],
+                "addressLookupTableAddresses": [],
+                "prioritizationFeeLamports": 5_000,
+                "computeUnitLimit": 200_000
+            }))
+            .unwrap();
+        server.mock_swap(&swap_response).await;
+        server
+            .mock_swap_instructions(&swap_instructions_response)
+            .await;
+        let client = server.client();
+        let swap_request = SwapRequest {
+            user_public_key: Pubkey::new_unique(),
+            ..SwapRequest::default()
+        };
+
+        assert_eq!(
+            client.swap(&swap_request, None).await.unwrap(),
+            swap_response
+        );
+        assert_eq!(
+            client
+                .swap_instructions(&swap_request, None)
+                .await
+                .unwrap()
+                .swap_instruction,
+            swap_instructions_response.swap_instruction
+        );
+    }
+
+    #[tokio::test]
+    async fn errors_are_returned_for_the_mocked_endpoint() {
+        let server = MockJupiterServer::start().await;
+        server.mock_error("/swap", 503, "unavailable").await;
+        let swap_request = SwapRequest {
+            user_public_key: Pubkey::new_unique(),
+            ..SwapRequest::default()
+        };
+
+        let error = server.client().swap(&swap_request, None).await.unwrap_err();
+        assert!(
+            matches!(
+                error.kind(),
+                ErrorKind::RequestFailed { status: StatusCode::SERVICE_UNAVAILABLE, body }
+                    if body == "unavailable"
+            ),
+            "{error:?}"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Tokens API, mint metadata such as symbol and decimals
+//!
+//! Lookups can be cached per client, see [`JupiterSwapApiClientBuilder::token_cache`](crate::builder::JupiterSwapApiClientBuilder::token_cache).
+
+use serde::{Deserialize, Serialize};
+use solana_pubkey::Pubkey;
+
+use crate::{
+    cache::TtlCache,
+    conditional::ConditionalResponse,
+    metrics::Endpoint,
+    serde_helpers::{field_as_string, option_field_as_string},
+    ClientError, JupiterSwapApiClient,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenInfo {
+    /// Mint of the token
+    #[serde(with = "field_as_string")]
+    pub id: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// SPL Token or Token-2022
+    #[serde(
+        default,
+        with = "option_field_as_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub token_program: Option<Pubkey>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// TTL cache of token infos by mint shared by the clones of a client
+pub(crate) type TokenCache = TtlCache<Pubkey, TokenInfo>;
+
+impl JupiterSwapApiClient {
+    /// GET /search, mints unknown to Jupiter are omitted, bypasses the token cache
+    pub async fn token_info(&self, mints: &[Pubkey]) -> Result<Vec<TokenInfo>, ClientError> {
+        let query = mints
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        self.get(
+            Endpoint::TokenSearch,
+            &format!("{}/search", self.tokens_base_path),
+            &[("query", query)],
+        )
+        .await
+    }
+
+    /// GET /tag, the token list of `tag` such as `verified` or `lst`,
+    /// revalidated with the `ETag` of the last response
+    pub async fn tokens_by_tag(
+        &self,
+        tag: &str,
+    ) -> Result<ConditionalResponse<Vec<TokenInfo>>, ClientError> {
+        self.get_conditional(
+            Endpoint::TokenTag,
+            &format!("{}/tag", self.tokens_base_path),
+            &[("query", tag)],
+        )
+        .await
+    }
+
+    /// Token info of `mint` from the token cache, fetched when missing or expired.
+    /// Always fetched when the client has no token cache, `None` when the mint is unknown to Jupiter.
+    pub async fn cached_token_info(&self, mint: &Pubkey) -> Result<Option<TokenInfo>, ClientError> {
+        if let Some(token_info) = self.token_cache.as_ref().and_then(|cache| cache.get(mint)) {
+            return Ok(Some(token_info));
+        }
+        self.refresh_token_info(mint).await
+    }
+
+    /// Fetch the token info of `mint` and update the token cache
+    pub async fn refresh_token_info(
+        &self,
+        mint: &Pubkey,
+    ) -> Result<Option<TokenInfo>, ClientError> {
+        let token_info = self
+            .token_info(std::slice::from_ref(mint))
+            .await?
+            .into_iter()
+            .find(|token_info| token_info.id == *mint);
+        if let (Some(cache), Some(token_info)) = (&self.token_cache, &token_info) {
+            cache.insert(token_info.id, token_info.clone());
+        }
+        Ok(token_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::mints::USDC_MINT;
+
+    async fn mock_search() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/search"))
+            .and(query_param("query", USDC_MINT.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+                "id": USDC_MINT.to_string(),
+                "name": "USD Coin",
+                "symbol": "USDC",
+                "decimals": 6,
+                "tags": ["verified"]
+            }])))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn client(server: &MockServer, ttl: Duration) -> JupiterSwapApiClient {
+        JupiterSwapApiClient::builder(server.uri())
+            .tokens_base_path(server.uri())
+            .token_cache(ttl, 16)
+            .build()
+            .unwrap()
+    }
+
+    async fn requests(server: &MockServer) -> usize {
+        server.received_requests().await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn back_to_back_lookups_hit_the_network_once() {
+        let server = mock_search().await;
+        let client = client(&server, Duration::from_secs(60));
+
+        let token_info = client.cached_token_info(&USDC_MINT).await.unwrap().unwrap();
+        assert_eq!(
+            (token_info.symbol.as_str(), token_info.decimals),
+            ("USDC", 6)
+        );
+        // Shared by the clones of the client
+        let cached = client.clone().cached_token_info(&USDC_MINT).await.unwrap();
+        assert_eq!(cached, Some(token_info));
+        assert_eq!(requests(&server).await, 1);
+
+        client.refresh_token_info(&USDC_MINT).await.unwrap();
+        assert_eq!(requests(&server).await, 2);
+        client.cached_token_info(&USDC_MINT).await.unwrap();
+        assert_eq!(requests(&server).await, 2);
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_fetched_again() {
+        let server = mock_search().await;
+        let client = client(&server, Duration::from_secs(60));
+
+        client.cached_token_info(&USDC_MINT).await.unwrap();
+        // Only paused while moving past the ttl, so the requests do not time out on an auto-advanced clock
+        tokio::time::pause();
+        tokio::time::advance(Duration::from_secs(61)).await;
+        tokio::time::resume();
+        client.cached_token_info(&USDC_MINT).await.unwrap();
+        assert_eq!(requests(&server).await, 2);
+    }
+
+    #[tokio::test]
+    async fn lookups_without_cache_always_fetch() {
+        let server = mock_search().await;
+        let mut client = JupiterSwapApiClient::new(server.uri(), None);
+        client.tokens_base_path = server.uri();
+
+        client.cached_token_info(&USDC_MINT).await.unwrap();
+        client.cached_token_info(&USDC_MINT).await.unwrap();
+        assert_eq!(requests(&server).await, 2);
+        // Unknown mints are not an error
+        Mock::given(method("GET"))
+            .and(path("/search"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&server)
+            .await;
+        let unknown = Pubkey::new_unique();
+        assert_eq!(client.cached_token_info(&unknown).await.unwrap(), None);
+    }
+}
@@ -1,11 +1,12 @@
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
-use solana_account_decoder::UiAccount;
-use solana_sdk::pubkey::Pubkey;
+use solana_account_decoder_client_types::UiAccount;
+use solana_pubkey::Pubkey;
+use thiserror::Error;
 
-use crate::serde_helpers::option_field_as_string;
+use crate::{quote::QuoteRequest, serde_helpers::option_field_as_string};
 
-#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
 #[serde(untagged)]
 pub enum ComputeUnitPriceMicroLamports {
@@ -14,6 +15,18 @@ pub enum ComputeUnitPriceMicroLamports {
     Auto,
 }
 
+impl Serialize for ComputeUnitPriceMicroLamports {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::MicroLamports(micro_lamports) => serializer.serialize_u64(*micro_lamports),
+            Self::Auto => serializer.serialize_str("auto"),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
 #[serde(rename_all = "camelCase")]
 pub enum PriorityLevel {
@@ -128,10 +141,12 @@ where
     Ok(())
 }
 
-#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DynamicSlippageSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub min_bps: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub max_bps: Option<u16>,
 }
 
@@ -144,18 +159,30 @@ pub struct TransactionConfig {
     /// Allow optimized WSOL token account by using transfer, assign with seed, allocate with seed then initialize account 3 instead of the expensive associated token account process
     pub allow_optimized_wrapped_sol_token_account: bool,
     /// Fee token account for the output token, it is derived using the seeds = ["referral_ata", referral_account, mint] and the `REFER4ZgmyYx9c6He5XfaTMiGfdLwRnkV4RPp9t9iF3` referral contract (only pass in if you set a feeBps and make sure that the feeAccount has been created)
-    #[serde(with = "option_field_as_string")]
+    #[serde(
+        with = "option_field_as_string",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub fee_account: Option<Pubkey>,
     /// Public key of the token account that will be used to receive the token out of the swap. If not provided, the user's ATA will be used. If provided, we assume that the token account is already initialized.
-    #[serde(with = "option_field_as_string")]
+    #[serde(
+        with = "option_field_as_string",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub destination_token_account: Option<Pubkey>,
-    /// Add a readonly, non signer tracking account that isn't used by jupiter
-    #[serde(with = "option_field_as_string")]
+    /// Add a readonly, non signer tracking account that isn't used by jupiter,
+    /// to identify the swaps of an integrator on-chain. Sent as `trackingAccount` only when set.
+    #[serde(
+        with = "option_field_as_string",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub tracking_account: Option<Pubkey>,
     /// compute unit price to prioritize the transaction, the additional fee will be compute unit consumed * computeUnitPriceMicroLamports
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub compute_unit_price_micro_lamports: Option<ComputeUnitPriceMicroLamports>,
     /// Prioritization fee lamports paid for the transaction in addition to the signatures fee.
     /// Mutually exclusive with `compute_unit_price_micro_lamports`.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub prioritization_fee_lamports: Option<PrioritizationFeeLamports>,
     /// When enabled, it will do a swap simulation to get the compute unit used and set it in ComputeBudget's compute unit limit.
     /// This will increase latency slightly since there will be one extra RPC call to simulate this. Default is false.
@@ -168,23 +195,30 @@ pub struct TransactionConfig {
     /// But it also means that the likelihood of hot accounts is higher.
     ///
     /// Default: Optimized internally
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub use_shared_accounts: Option<bool>,
     /// This is useful when the instruction before the swap has a transfer that increases the input token amount.
     /// Then, the swap will just use the difference between the token ledger token amount and post token amount.
+    /// The caller must include the returned `token_ledger_instruction` in its transaction, before the instruction increasing the input token amount.
     ///
     /// Default: false
-    pub use_token_ledger: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_token_ledger: Option<bool>,
     /// Skip RPC calls and assume the user account do not exist,
     /// as a result all setup instruction will be populated but no RPC call will be done for user related accounts (token accounts, openbook open orders...)
     pub skip_user_accounts_rpc_calls: bool,
     /// Providing keyed ui accounts allow loading AMMs that are not in the market cache
     /// If a keyed ui account is the AMM state, it has to be provided with its params according to the market cache format
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub keyed_ui_accounts: Option<Vec<KeyedUiAccount>>,
     /// The program authority ID
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub program_authority_id: Option<u8>,
     /// Dynamic slippage
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dynamic_slippage: Option<DynamicSlippageSettings>,
     /// Slots to expiry of the blockhash
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub blockhash_slots_to_expiry: Option<u8>,
     /// Requests a correct last valid block height,
     /// this is to allow a smooth transition to agave 2.0 for all consumers, see https://github.com/solana-labs/solana/issues/24526
@@ -203,7 +237,7 @@ impl Default for TransactionConfig {
             prioritization_fee_lamports: None,
             as_legacy_transaction: false,
             use_shared_accounts: None,
-            use_token_ledger: false,
+            use_token_ledger: None,
             dynamic_compute_unit_limit: false,
             skip_user_accounts_rpc_calls: false,
             keyed_ui_accounts: None,
@@ -215,11 +249,441 @@ impl Default for TransactionConfig {
     }
 }
 
+impl TransactionConfig {
+    pub fn builder() -> TransactionConfigBuilder {
+        TransactionConfigBuilder::default()
+    }
+
+    /// Fail with [`TransactionConfigError::LegacyTransactionMismatch`] unless `quote_request` and this config
+    /// agree on `as_legacy_transaction`, a legacy swap of a versioned route may not fit in a transaction
+    pub fn check_quote_request(
+        &self,
+        quote_request: &QuoteRequest,
+    ) -> Result<(), TransactionConfigError> {
+        let quote = quote_request.as_legacy_transaction.unwrap_or_default();
+        if quote != self.as_legacy_transaction {
+            return Err(TransactionConfigError::LegacyTransactionMismatch {
+                quote,
+                swap: self.as_legacy_transaction,
+            });
+        }
+        Ok(())
+    }
+
+    /// Preset for swaps submitted through Jito bundles, the tip replaces any compute budget priority fee
+    pub fn for_jito(tip_lamports: u64) -> Self {
+        Self {
+            compute_unit_price_micro_lamports: None,
+            prioritization_fee_lamports: Some(PrioritizationFeeLamports::JitoTipLamports(
+                tip_lamports,
+            )),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+pub enum TransactionConfigError {
+    #[error(
+        "compute_unit_price_micro_lamports and prioritization_fee_lamports are mutually exclusive"
+    )]
+    ComputeUnitPriceWithPrioritizationFee,
+    #[error("A jito tip cannot be combined with {0}")]
+    JitoTipWithPrioritizationFee(&'static str),
+    #[error("wrap_and_unwrap_sol is ignored when destination_token_account is set")]
+    WrapAndUnwrapSolWithDestinationTokenAccount,
+    #[error("as_legacy_transaction is {quote} on the quote request but {swap} on the swap")]
+    LegacyTransactionMismatch { quote: bool, swap: bool },
+}
+
+/// Builds a [`TransactionConfig`] rejecting combinations the API does not support
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransactionConfigBuilder {
+    config: TransactionConfig,
+    wrap_and_unwrap_sol: Option<bool>,
+    jito_tip_lamports: Option<u64>,
+}
+
+impl TransactionConfigBuilder {
+    pub fn wrap_and_unwrap_sol(mut self, wrap_and_unwrap_sol: bool) -> Self {
+        self.wrap_and_unwrap_sol = Some(wrap_and_unwrap_sol);
+        self
+    }
+
+    pub fn allow_optimized_wrapped_sol_token_account(mut self, allow: bool) -> Self {
+        self.config.allow_optimized_wrapped_sol_token_account = allow;
+        self
+    }
+
+    pub fn fee_account(mut self, fee_account: Pubkey) -> Self {
+        self.config.fee_account = Some(fee_account);
+        self
+    }
+
+    pub fn destination_token_account(mut self, destination_token_account: Pubkey) -> Self {
+        self.config.destination_token_account = Some(destination_token_account);
+        self
+    }
+
+    pub fn tracking_account(mut self, tracking_account: Pubkey) -> Self {
+        self.config.tracking_account = Some(tracking_account);
+        self
+    }
+
+    pub fn compute_unit_price_micro_lamports(
+        mut self,
+        compute_unit_price_micro_lamports: ComputeUnitPriceMicroLamports,
+    ) -> Self {
+        self.config.compute_unit_price_micro_lamports = Some(compute_unit_price_micro_lamports);
+        self
+    }
+
+    pub fn prioritization_fee_lamports(
+        mut self,
+        prioritization_fee_lamports: PrioritizationFeeLamports,
+    ) -> Self {
+        self.config.prioritization_fee_lamports = Some(prioritization_fee_lamports);
+        self
+    }
+
+    /// Tip paid to Jito, exclusive with any other prioritization fee
+    pub fn jito_tip_lamports(mut self, jito_tip_lamports: u64) -> Self {
+        self.jito_tip_lamports = Some(jito_tip_lamports);
+        self
+    }
+
+    pub fn dynamic_compute_unit_limit(mut self, dynamic_compute_unit_limit: bool) -> Self {
+        self.config.dynamic_compute_unit_limit = dynamic_compute_unit_limit;
+        self
+    }
+
+    /// The quote has to be requested with `as_legacy_transaction` as well,
+    /// routes requiring address lookup tables cannot fit in a legacy transaction which is only detected server side
+    pub fn as_legacy_transaction(mut self, as_legacy_transaction: bool) -> Self {
+        self.config.as_legacy_transaction = as_legacy_transaction;
+        self
+    }
+
+    /// Left to the routing engine by default. Jupiter documents that shared accounts routes fail on some new AMMs,
+    /// which cannot be told client side, so forcing them is only warned about
+    pub fn use_shared_accounts(mut self, use_shared_accounts: bool) -> Self {
+        self.config.use_shared_accounts = Some(use_shared_accounts);
+        self
+    }
+
+    pub fn use_token_ledger(mut self, use_token_ledger: bool) -> Self {
+        self.config.use_token_ledger = Some(use_token_ledger);
+        self
+    }
+
+    pub fn skip_user_accounts_rpc_calls(mut self, skip_user_accounts_rpc_calls: bool) -> Self {
+        self.config.skip_user_accounts_rpc_calls = skip_user_accounts_rpc_calls;
+        self
+    }
+
+    pub fn keyed_ui_accounts(mut self, keyed_ui_accounts: Vec<KeyedUiAccount>) -> Self {
+        self.config.keyed_ui_accounts = Some(keyed_ui_accounts);
+        self
+    }
+
+    pub fn program_authority_id(mut self, program_authority_id: u8) -> Self {
+        self.config.program_authority_id = Some(program_authority_id);
+        self
+    }
+
+    pub fn dynamic_slippage(mut self, dynamic_slippage: DynamicSlippageSettings) -> Self {
+        self.config.dynamic_slippage = Some(dynamic_slippage);
+        self
+    }
+
+    pub fn blockhash_slots_to_expiry(mut self, blockhash_slots_to_expiry: u8) -> Self {
+        self.config.blockhash_slots_to_expiry = Some(blockhash_slots_to_expiry);
+        self
+    }
+
+    pub fn correct_last_valid_block_height(
+        mut self,
+        correct_last_valid_block_height: bool,
+    ) -> Self {
+        self.config.correct_last_valid_block_height = correct_last_valid_block_height;
+        self
+    }
+
+    pub fn build(self) -> Result<TransactionConfig, TransactionConfigError> {
+        let mut config = self.config;
+        if config.compute_unit_price_micro_lamports.is_some()
+            && config.prioritization_fee_lamports.is_some()
+        {
+            return Err(TransactionConfigError::ComputeUnitPriceWithPrioritizationFee);
+        }
+        if let Some(jito_tip_lamports) = self.jito_tip_lamports {
+            if config.compute_unit_price_micro_lamports.is_some() {
+                return Err(TransactionConfigError::JitoTipWithPrioritizationFee(
+                    "compute_unit_price_micro_lamports",
+                ));
+            }
+            match config.prioritization_fee_lamports {
+                Some(PrioritizationFeeLamports::PriorityLevelWithMaxLamports { .. }) => {
+                    return Err(TransactionConfigError::JitoTipWithPrioritizationFee(
+                        "a priority level",
+                    ))
+                }
+                Some(_) => {
+                    return Err(TransactionConfigError::JitoTipWithPrioritizationFee(
+                        "prioritization_fee_lamports",
+                    ))
+                }
+                None => {}
+            }
+            config.prioritization_fee_lamports = Some(PrioritizationFeeLamports::JitoTipLamports(
+                jito_tip_lamports,
+            ));
+        }
+        if let Some(wrap_and_unwrap_sol) = self.wrap_and_unwrap_sol {
+            if wrap_and_unwrap_sol && config.destination_token_account.is_some() {
+                return Err(TransactionConfigError::WrapAndUnwrapSolWithDestinationTokenAccount);
+            }
+            config.wrap_and_unwrap_sol = wrap_and_unwrap_sol;
+        }
+        if config.as_legacy_transaction {
+            log::warn!("as_legacy_transaction is set, routes requiring address lookup tables do not fit in a legacy transaction and are only rejected server side");
+        }
+        if config.use_shared_accounts == Some(true) {
+            log::warn!("use_shared_accounts is forced, routes through some new AMMs fail with shared accounts and are only rejected server side");
+        }
+        Ok(config)
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct KeyedUiAccount {
     pub pubkey: String,
     #[serde(flatten)]
     pub ui_account: UiAccount,
     /// Additional data an Amm requires, Amm dependent and decoded in the Amm implementation
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub params: Option<Value>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pinned for downstream caches and comparisons
+    static_assertions::assert_impl_all!(ComputeUnitPriceMicroLamports: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+    static_assertions::assert_impl_all!(PriorityLevel: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+    static_assertions::assert_impl_all!(PrioritizationFeeLamports: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(DynamicSlippageSettings: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(TransactionConfig: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(TransactionConfigBuilder: std::fmt::Debug, Clone, PartialEq, Default, Send, Sync);
+    static_assertions::assert_impl_all!(KeyedUiAccount: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+    static_assertions::assert_impl_all!(TransactionConfigError: std::fmt::Debug, Clone, PartialEq, Send, Sync);
+
+    #[test]
+    fn compute_unit_price_conflicts_with_prioritization_fee() {
+        let error = TransactionConfig::builder()
+            .compute_unit_price_micro_lamports(ComputeUnitPriceMicroLamports::Auto)
+            .prioritization_fee_lamports(PrioritizationFeeLamports::Lamports(1_000))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TransactionConfigError::ComputeUnitPriceWithPrioritizationFee
+        );
+    }
+
+    #[test]
+    fn jito_tip_conflicts_with_other_fees() {
+        let error = TransactionConfig::builder()
+            .jito_tip_lamports(1_000)
+            .prioritization_fee_lamports(PrioritizationFeeLamports::PriorityLevelWithMaxLamports {
+                priority_level: PriorityLevel::High,
+                max_lamports: 10_000,
+                global: false,
+            })
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TransactionConfigError::JitoTipWithPrioritizationFee("a priority level")
+        );
+        let error = TransactionConfig::builder()
+            .jito_tip_lamports(1_000)
+            .compute_unit_price_micro_lamports(ComputeUnitPriceMicroLamports::MicroLamports(1))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TransactionConfigError::JitoTipWithPrioritizationFee(
+                "compute_unit_price_micro_lamports"
+            )
+        );
+
+        let config = TransactionConfig::builder()
+            .jito_tip_lamports(1_000)
+            .build()
+            .unwrap();
+        assert_eq!(config, TransactionConfig::for_jito(1_000));
+    }
+
+    #[test]
+    fn wrap_and_unwrap_sol_conflicts_with_destination_token_account() {
+        let destination_token_account = Pubkey::new_unique();
+        let error = TransactionConfig::builder()
+            .destination_token_account(destination_token_account)
+            .wrap_and_unwrap_sol(true)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TransactionConfigError::WrapAndUnwrapSolWithDestinationTokenAccount
+        );
+        // Left to the default, it is ignored by the API
+        let config = TransactionConfig::builder()
+            .destination_token_account(destination_token_account)
+            .build()
+            .unwrap();
+        assert!(config.wrap_and_unwrap_sol);
+    }
+
+    #[test]
+    fn legacy_transaction_must_match_the_quote_request() {
+        let config = TransactionConfig::builder()
+            .as_legacy_transaction(true)
+            .build()
+            .unwrap();
+        let mut quote_request = QuoteRequest::default();
+        assert_eq!(
+            config.check_quote_request(&quote_request),
+            Err(TransactionConfigError::LegacyTransactionMismatch {
+                quote: false,
+                swap: true,
+            })
+        );
+        quote_request.as_legacy_transaction = Some(true);
+        assert_eq!(config.check_quote_request(&quote_request), Ok(()));
+        assert_eq!(
+            TransactionConfig::default().check_quote_request(&quote_request),
+            Err(TransactionConfigError::LegacyTransactionMismatch {
+                quote: true,
+                swap: false,
+            })
+        );
+    }
+
+    #[test]
+    fn shared_accounts_and_token_ledger_round_trip() {
+        let default = serde_json::to_value(TransactionConfig::default()).unwrap();
+        assert!(default.get("useSharedAccounts").is_none());
+        assert!(default.get("useTokenLedger").is_none());
+
+        for (use_shared_accounts, use_token_ledger) in [(true, false), (false, true)] {
+            let config = TransactionConfig {
+                use_shared_accounts: Some(use_shared_accounts),
+                use_token_ledger: Some(use_token_ledger),
+                ..TransactionConfig::default()
+            };
+            let value = serde_json::to_value(&config).unwrap();
+            assert_eq!(value["useSharedAccounts"], use_shared_accounts);
+            assert_eq!(value["useTokenLedger"], use_token_ledger);
+            assert_eq!(
+                serde_json::from_value::<TransactionConfig>(value).unwrap(),
+                config
+            );
+        }
+        // Absent flags are left to the API
+        let config: TransactionConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.use_shared_accounts, None);
+        assert_eq!(config.use_token_ledger, None);
+    }
+
+    #[test]
+    fn fee_variants_round_trip() {
+        let prioritization_fees = [
+            PrioritizationFeeLamports::AutoMultiplier(3),
+            PrioritizationFeeLamports::JitoTipLamports(10_000),
+            PrioritizationFeeLamports::PriorityLevelWithMaxLamports {
+                priority_level: PriorityLevel::VeryHigh,
+                max_lamports: 4_000_000,
+                global: true,
+            },
+            PrioritizationFeeLamports::Auto,
+            PrioritizationFeeLamports::Lamports(5_000),
+            PrioritizationFeeLamports::Disabled,
+        ];
+        for prioritization_fee in prioritization_fees {
+            let serialized = serde_json::to_value(prioritization_fee).unwrap();
+            assert_eq!(
+                serde_json::from_value::<PrioritizationFeeLamports>(serialized).unwrap(),
+                prioritization_fee
+            );
+        }
+        for compute_unit_price in [
+            ComputeUnitPriceMicroLamports::MicroLamports(1),
+            ComputeUnitPriceMicroLamports::Auto,
+        ] {
+            let serialized = serde_json::to_value(&compute_unit_price).unwrap();
+            assert_eq!(
+                serde_json::from_value::<ComputeUnitPriceMicroLamports>(serialized).unwrap(),
+                compute_unit_price
+            );
+        }
+    }
+
+    #[test]
+    fn transaction_config_round_trips_without_nulls() {
+        let default = serde_json::to_value(TransactionConfig::default()).unwrap();
+        crate::quote::tests::assert_no_nulls(&default);
+
+        let config = TransactionConfig {
+            wrap_and_unwrap_sol: false,
+            fee_account: Some(Pubkey::new_unique()),
+            destination_token_account: Some(Pubkey::new_unique()),
+            tracking_account: Some(Pubkey::new_unique()),
+            prioritization_fee_lamports: Some(PrioritizationFeeLamports::JitoTipLamports(1)),
+            dynamic_compute_unit_limit: true,
+            use_shared_accounts: Some(false),
+            program_authority_id: Some(2),
+            dynamic_slippage: Some(DynamicSlippageSettings {
+                min_bps: None,
+                max_bps: Some(300),
+            }),
+            blockhash_slots_to_expiry: Some(10),
+            correct_last_valid_block_height: true,
+            ..TransactionConfig::default()
+        };
+        let serialized = serde_json::to_value(&config).unwrap();
+        crate::quote::tests::assert_no_nulls(&serialized);
+        assert_eq!(
+            serialized["dynamicSlippage"],
+            serde_json::json!({ "maxBps": 300 })
+        );
+        assert_eq!(
+            serde_json::from_value::<TransactionConfig>(serialized).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn tracking_account_is_only_sent_when_set() {
+        let serialized = serde_json::to_value(TransactionConfig::default()).unwrap();
+        assert!(serialized.get("trackingAccount").is_none(), "{serialized}");
+
+        let tracking_account = Pubkey::new_unique();
+        let config = TransactionConfig::builder()
+            .tracking_account(tracking_account)
+            .build()
+            .unwrap();
+        let serialized = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            serialized["trackingAccount"],
+            serde_json::json!(tracking_account.to_string())
+        );
+        assert_eq!(
+            serde_json::from_value::<TransactionConfig>(serialized)
+                .unwrap()
+                .tracking_account,
+            Some(tracking_account)
+        );
+    }
+}
@@ -0,0 +1,577 @@
+//! Trigger API, limit orders filled once the taking amount can be met
+
+use futures::Stream;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use solana_pubkey::Pubkey;
+
+use crate::{
+    metrics::Endpoint,
+    pagination::{paginate, Paginated},
+    serde_helpers::{field_as_string, option_field_as_string, vec_base64, vec_field_as_string},
+    swap::base64_serialize_deserialize,
+    ClientError, ErrorKind, JupiterSwapApiClient,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerOrderParams {
+    /// Amount of input mint offered, have to factor in the token decimals.
+    #[serde(with = "field_as_string")]
+    pub making_amount: u64,
+    /// Amount of output mint expected, have to factor in the token decimals.
+    #[serde(with = "field_as_string")]
+    pub taking_amount: u64,
+    /// Unix timestamp after which the order can no longer be filled
+    #[serde(
+        default,
+        with = "option_field_as_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expired_at: Option<i64>,
+    #[serde(
+        default,
+        with = "option_field_as_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub slippage_bps: Option<u16>,
+    /// Integrator fee in basis points, requires `fee_account`
+    #[serde(
+        default,
+        with = "option_field_as_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub fee_bps: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTriggerOrderRequest {
+    #[serde(with = "field_as_string")]
+    pub input_mint: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub output_mint: Pubkey,
+    /// Owner of the order
+    #[serde(with = "field_as_string")]
+    pub maker: Pubkey,
+    /// Pays for the order account rent and the transaction fees
+    #[serde(with = "field_as_string")]
+    pub payer: Pubkey,
+    pub params: TriggerOrderParams,
+    /// Compute unit price in micro lamports or `auto`, defaults to `auto`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compute_unit_price: Option<String>,
+    #[serde(
+        default,
+        with = "option_field_as_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub fee_account: Option<Pubkey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrap_and_unwrap_sol: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTriggerOrderResponse {
+    /// Account of the created order
+    #[serde(with = "field_as_string")]
+    pub order: Pubkey,
+    /// Unsigned transaction creating the order
+    #[serde(with = "base64_serialize_deserialize")]
+    pub transaction: Vec<u8>,
+    pub request_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelTriggerOrderRequest {
+    #[serde(with = "field_as_string")]
+    pub maker: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub order: Pubkey,
+    /// Compute unit price in micro lamports or `auto`, defaults to `auto`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compute_unit_price: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelTriggerOrderResponse {
+    #[serde(with = "base64_serialize_deserialize")]
+    pub transaction: Vec<u8>,
+    pub request_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelTriggerOrdersRequest {
+    #[serde(with = "field_as_string")]
+    pub maker: Pubkey,
+    /// Orders to cancel, every open order of the maker is cancelled when empty
+    #[serde(
+        default,
+        with = "vec_field_as_string",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub orders: Vec<Pubkey>,
+    /// Compute unit price in micro lamports or `auto`, defaults to `auto`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compute_unit_price: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelTriggerOrdersResponse {
+    /// Unsigned transactions, cancellations are batched in several transactions
+    #[serde(with = "vec_base64")]
+    pub transactions: Vec<Vec<u8>>,
+    pub request_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerExecuteRequest {
+    #[serde(with = "base64_serialize_deserialize")]
+    pub signed_transaction: Vec<u8>,
+    pub request_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
+pub enum TriggerExecuteStatus {
+    Success,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerExecuteResponse {
+    pub status: TriggerExecuteStatus,
+    pub signature: Option<String>,
+    pub code: Option<i32>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum TriggerOrderStatus {
+    /// Open orders
+    #[default]
+    Active,
+    /// Filled, cancelled and expired orders
+    History,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTriggerOrdersRequest {
+    #[serde(with = "field_as_string")]
+    pub user: Pubkey,
+    pub order_status: TriggerOrderStatus,
+    /// Starts at 1
+    pub page: Option<u32>,
+    #[serde(with = "option_field_as_string")]
+    pub input_mint: Option<Pubkey>,
+    #[serde(with = "option_field_as_string")]
+    pub output_mint: Option<Pubkey>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerOrder {
+    #[serde(with = "field_as_string")]
+    pub user_pubkey: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub order_key: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub input_mint: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub output_mint: Pubkey,
+    /// UI amounts
+    #[serde(with = "field_as_string")]
+    pub making_amount: Decimal,
+    #[serde(with = "field_as_string")]
+    pub taking_amount: Decimal,
+    #[serde(with = "field_as_string")]
+    pub remaining_making_amount: Decimal,
+    #[serde(with = "field_as_string")]
+    pub remaining_taking_amount: Decimal,
+    /// Raw amounts
+    #[serde(with = "field_as_string")]
+    pub raw_making_amount: u64,
+    #[serde(with = "field_as_string")]
+    pub raw_taking_amount: u64,
+    #[serde(with = "field_as_string")]
+    pub raw_remaining_making_amount: u64,
+    #[serde(with = "field_as_string")]
+    pub raw_remaining_taking_amount: u64,
+    #[serde(default, with = "option_field_as_string")]
+    pub slippage_bps: Option<u16>,
+    pub expired_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Open, Completed or Cancelled
+    pub status: String,
+    pub open_tx: Option<String>,
+    pub close_tx: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTriggerOrdersResponse {
+    pub orders: Vec<TriggerOrder>,
+    pub total_pages: u32,
+    pub page: u32,
+}
+
+impl JupiterSwapApiClient {
+    /// POST /createOrder, the returned transaction has to be signed by the maker and payer then passed to [`Self::trigger_execute`]
+    pub async fn create_trigger_order(
+        &self,
+        create_order_request: &CreateTriggerOrderRequest,
+    ) -> Result<CreateTriggerOrderResponse, ClientError> {
+        self.post(
+            Endpoint::TriggerCreateOrder,
+            &format!("{}/createOrder", self.trigger_base_path),
+            create_order_request,
+        )
+        .await
+    }
+
+    /// POST /cancelOrder
+    pub async fn cancel_trigger_order(
+        &self,
+        cancel_order_request: &CancelTriggerOrderRequest,
+    ) -> Result<CancelTriggerOrderResponse, ClientError> {
+        self.post(
+            Endpoint::TriggerCancelOrder,
+            &format!("{}/cancelOrder", self.trigger_base_path),
+            cancel_order_request,
+        )
+        .await
+    }
+
+    /// POST /cancelOrders
+    pub async fn cancel_trigger_orders(
+        &self,
+        cancel_orders_request: &CancelTriggerOrdersRequest,
+    ) -> Result<CancelTriggerOrdersResponse, ClientError> {
+        self.post(
+            Endpoint::TriggerCancelOrders,
+            &format!("{}/cancelOrders", self.trigger_base_path),
+            cancel_orders_request,
+        )
+        .await
+    }
+
+    /// POST /execute, broadcast a signed create or cancel transaction,
+    /// a failed execution is returned as [`ErrorKind::TriggerExecuteFailed`]
+    pub async fn trigger_execute(
+        &self,
+        execute_request: &TriggerExecuteRequest,
+    ) -> Result<TriggerExecuteResponse, ClientError> {
+        let execute_response: TriggerExecuteResponse = self
+            .post(
+                Endpoint::TriggerExecute,
+                &format!("{}/execute", self.trigger_base_path),
+                execute_request,
+            )
+            .await?;
+        if execute_response.status == TriggerExecuteStatus::Failed {
+            return Err(ClientError::from(ErrorKind::TriggerExecuteFailed {
+                code: execute_response.code,
+                message: execute_response.error.unwrap_or_default(),
+                signature: execute_response.signature,
+            })
+            .with_endpoint(Endpoint::TriggerExecute));
+        }
+        Ok(execute_response)
+    }
+
+    /// Orders of every page of [`Self::get_trigger_orders`] starting at `get_orders_request.page`,
+    /// fetched lazily
+    pub fn get_trigger_orders_stream(
+        &self,
+        get_orders_request: GetTriggerOrdersRequest,
+    ) -> impl Stream<Item = Result<TriggerOrder, ClientError>> {
+        let client = self.clone();
+        paginate(get_orders_request.page.unwrap_or(1), move |page| {
+            let client = client.clone();
+            let get_orders_request = GetTriggerOrdersRequest {
+                page: Some(page),
+                ..get_orders_request.clone()
+            };
+            async move {
+                let response = client.get_trigger_orders(&get_orders_request).await?;
+                Ok(Paginated::new(
+                    response.orders,
+                    response.page,
+                    response.total_pages,
+                ))
+            }
+        })
+    }
+
+    /// GET /getTriggerOrders
+    pub async fn get_trigger_orders(
+        &self,
+        get_orders_request: &GetTriggerOrdersRequest,
+    ) -> Result<GetTriggerOrdersResponse, ClientError> {
+        self.get(
+            Endpoint::TriggerGetOrders,
+            &format!("{}/getTriggerOrders", self.trigger_base_path),
+            get_orders_request,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use futures::StreamExt;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::mints::{NATIVE_MINT, USDC_MINT};
+
+    const MAKER: &str = "8kzGq5Mp1Dc6rjh8YNBEWPvt3XrkTNnBSoDRLTc8Xr1B";
+    const ORDER: &str = "4xVGL8dwg3LvNnNyc2dSJyAiVqvRtkyEYz2moT4y4bCD";
+
+    fn pubkey(address: &str) -> Pubkey {
+        Pubkey::from_str(address).unwrap()
+    }
+
+    fn order_fixture() -> serde_json::Value {
+        json!({
+            "userPubkey": MAKER,
+            "orderKey": ORDER,
+            "inputMint": NATIVE_MINT.to_string(),
+            "outputMint": USDC_MINT.to_string(),
+            "makingAmount": "1.5",
+            "takingAmount": "300",
+            "remainingMakingAmount": "0.5",
+            "remainingTakingAmount": "100",
+            "rawMakingAmount": "1500000000",
+            "rawTakingAmount": "300000000",
+            "rawRemainingMakingAmount": "500000000",
+            "rawRemainingTakingAmount": "100000000",
+            "slippageBps": "0",
+            "expiredAt": null,
+            "createdAt": "2025-03-01T12:00:00Z",
+            "updatedAt": "2025-03-01T12:05:00Z",
+            "status": "Open",
+            "openTx": "5Uj6iKPheTJdD1TTMd2yvtin6C2rHnP7q6ptkD3yLCdoWedmSfVs2mbDrpPGR6E4py4xxBd3u3z1vALfQYbay8Ko",
+            "closeTx": null
+        })
+    }
+
+    #[test]
+    fn create_order_response_fixture() {
+        let response: CreateTriggerOrderResponse = serde_json::from_value(json!({
+            "order": ORDER,
+            "transaction": "AQID",
+            "requestId": "370100dd-1a85-421b-9278-27f0961ae5f4"
+        }))
+        .unwrap();
+        assert_eq!(
+            response,
+            CreateTriggerOrderResponse {
+                order: pubkey(ORDER),
+                transaction: vec![1, 2, 3],
+                request_id: "370100dd-1a85-421b-9278-27f0961ae5f4".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn cancel_order_response_fixtures() {
+        let response: CancelTriggerOrderResponse = serde_json::from_value(json!({
+            "transaction": "AQID",
+            "requestId": "r1"
+        }))
+        .unwrap();
+        assert_eq!(response.transaction, [1, 2, 3]);
+
+        let response: CancelTriggerOrdersResponse = serde_json::from_value(json!({
+            "transactions": ["AQID", "BAU="],
+            "requestId": "r2"
+        }))
+        .unwrap();
+        assert_eq!(response.transactions, [vec![1, 2, 3], vec![4, 5]]);
+        assert_eq!(response.request_id, "r2");
+    }
+
+    #[test]
+    fn execute_response_fixtures() {
+        let success: TriggerExecuteResponse = serde_json::from_value(json!({
+            "status": "Success",
+            "signature": "5Uj6iKPheTJdD1TTMd2yvtin6C2rHnP7q6ptkD3yLCdoWedmSfVs2mbDrpPGR6E4py4xxBd3u3z1vALfQYbay8Ko",
+            "code": 0
+        }))
+        .unwrap();
+        assert_eq!(success.status, TriggerExecuteStatus::Success);
+        assert_eq!(success.error, None);
+
+        let failed: TriggerExecuteResponse = serde_json::from_value(json!({
+            "status": "Failed",
+            "code": -1,
+            "error": "Transaction expired"
+        }))
+        .unwrap();
+        assert_eq!(
+            failed,
+            TriggerExecuteResponse {
+                status: TriggerExecuteStatus::Failed,
+                signature: None,
+                code: Some(-1),
+                error: Some("Transaction expired".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn trigger_orders_response_fixture() {
+        let response: GetTriggerOrdersResponse = serde_json::from_value(json!({
+            "orders": [order_fixture()],
+            "totalPages": 3,
+            "page": 1
+        }))
+        .unwrap();
+        assert_eq!((response.page, response.total_pages), (1, 3));
+        let order = &response.orders[0];
+        assert_eq!(order.order_key, pubkey(ORDER));
+        assert_eq!(order.making_amount, Decimal::new(15, 1));
+        assert_eq!(order.raw_remaining_making_amount, 500_000_000);
+        assert_eq!(order.slippage_bps, Some(0));
+        assert_eq!(order.expired_at, None);
+        assert_eq!(order.close_tx, None);
+    }
+
+    #[test]
+    fn create_order_request_sends_amounts_as_strings() {
+        let request = CreateTriggerOrderRequest {
+            input_mint: NATIVE_MINT,
+            output_mint: USDC_MINT,
+            maker: pubkey(MAKER),
+            payer: pubkey(MAKER),
+            params: TriggerOrderParams {
+                making_amount: 1_500_000_000,
+                taking_amount: 300_000_000,
+                ..TriggerOrderParams::default()
+            },
+            ..CreateTriggerOrderRequest::default()
+        };
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            value["params"],
+            json!({ "makingAmount": "1500000000", "takingAmount": "300000000" })
+        );
+        assert_eq!(value["maker"], MAKER);
+        assert!(value.get("feeAccount").is_none());
+    }
+
+    #[tokio::test]
+    async fn trigger_orders_are_listed_by_status_and_page() {
+        let server = MockServer::start().await;
+        for page in [1, 2] {
+            Mock::given(method("GET"))
+                .and(path("/getTriggerOrders"))
+                .and(query_param("user", MAKER))
+                .and(query_param("orderStatus", "history"))
+                .and(query_param("page", page.to_string()))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "orders": [order_fixture()],
+                    "totalPages": 2,
+                    "page": page
+                })))
+                .mount(&server)
+                .await;
+        }
+        let mut client = JupiterSwapApiClient::new(server.uri(), None);
+        client.trigger_base_path = server.uri();
+
+        let request = GetTriggerOrdersRequest {
+            user: pubkey(MAKER),
+            order_status: TriggerOrderStatus::History,
+            ..GetTriggerOrdersRequest::default()
+        };
+        let orders: Vec<_> = client.get_trigger_orders_stream(request).collect().await;
+        assert_eq!(orders.len(), 2);
+        assert!(orders.iter().all(Result::is_ok));
+    }
+
+    async fn execute(
+        server: &MockServer,
+        response: serde_json::Value,
+    ) -> Result<TriggerExecuteResponse, ClientError> {
+        Mock::given(method("POST"))
+            .and(path("/execute"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response))
+            .mount(server)
+            .await;
+        let mut client = JupiterSwapApiClient::new(server.uri(), None);
+        client.trigger_base_path = server.uri();
+        client
+            .trigger_execute(&TriggerExecuteRequest {
+                signed_transaction: vec![1, 2, 3],
+                request_id: "request".to_string(),
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn successful_execution_is_returned() {
+        let server = MockServer::start().await;
+        let execute_response = execute(
+            &server,
+            json!({ "status": "Success", "signature": "signature", "code": 0 }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(execute_response.signature.as_deref(), Some("signature"));
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(
+            requests[0].body_json::<serde_json::Value>().unwrap(),
+            json!({ "signedTransaction": "AQID", "requestId": "request" })
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_execution_is_an_error() {
+        let server = MockServer::start().await;
+        let error = execute(
+            &server,
+            json!({
+                "status": "Failed",
+                "signature": "signature",
+                "code": -1000,
+                "error": "Failed to land"
+            }),
+        )
+        .await
+        .unwrap_err();
+
+        let ErrorKind::TriggerExecuteFailed {
+            code,
+            message,
+            signature,
+        } = error.kind()
+        else {
+            panic!("{error:?}");
+        };
+        assert_eq!(*code, Some(-1000));
+        assert_eq!(message, "Failed to land");
+        assert_eq!(signature.as_deref(), Some("signature"));
+        assert_eq!(error.endpoint(), Some(Endpoint::TriggerExecute));
+        assert!(error
+            .to_string()
+            .starts_with("Trigger execute failed with code -1000: Failed to land"));
+    }
+}
@@ -0,0 +1,631 @@
+//! Ultra API, slippage, priority fees and broadcasting are handled server side
+
+use std::collections::HashMap;
+
+use reqwest::Method;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use solana_pubkey::Pubkey;
+
+use crate::{
+    amount, check_status_code_and_deserialize,
+    metrics::Endpoint,
+    quote::SwapMode,
+    request_options::RequestOptions,
+    route_plan_with_metadata::RoutePlanWithMetadata,
+    serde_helpers::{field_as_string, option_base64, option_field_as_string},
+    swap::base64_serialize_deserialize,
+    ClientError, ErrorKind, JupiterSwapApiClient,
+};
+
+#[derive(Serialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UltraOrderRequest {
+    #[serde(with = "field_as_string")]
+    pub input_mint: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub output_mint: Pubkey,
+    /// The amount to swap, have to factor in the token decimals.
+    #[serde(with = "field_as_string")]
+    pub amount: u64,
+    /// Wallet which will sign the transaction, the order only contains a transaction when set
+    #[serde(with = "option_field_as_string")]
+    pub taker: Option<Pubkey>,
+    /// Referral account collecting the referral fee
+    #[serde(with = "option_field_as_string")]
+    pub referral_account: Option<Pubkey>,
+    /// Referral fee in basis points
+    pub referral_fee: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UltraOrderResponse {
+    #[serde(with = "field_as_string")]
+    pub input_mint: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub in_amount: u64,
+    #[serde(with = "field_as_string")]
+    pub output_mint: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub out_amount: u64,
+    #[serde(with = "field_as_string")]
+    pub other_amount_threshold: u64,
+    pub swap_mode: SwapMode,
+    pub slippage_bps: u16,
+    pub price_impact_pct: Decimal,
+    pub route_plan: RoutePlanWithMetadata,
+    pub fee_bps: Option<u16>,
+    /// Unsigned transaction, only present when `taker` was set on the request
+    #[serde(default, with = "option_base64")]
+    pub transaction: Option<Vec<u8>>,
+    /// Identifies the order when executing it
+    pub request_id: String,
+    #[serde(default)]
+    pub gasless: bool,
+    pub prioritization_fee_lamports: Option<u64>,
+    pub router: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UltraExecuteRequest {
+    #[serde(with = "base64_serialize_deserialize")]
+    pub signed_transaction: Vec<u8>,
+    pub request_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
+pub enum UltraExecuteStatus {
+    Success,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UltraSwapEvent {
+    #[serde(with = "field_as_string")]
+    pub input_mint: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub input_amount: u64,
+    #[serde(with = "field_as_string")]
+    pub output_mint: Pubkey,
+    #[serde(with = "field_as_string")]
+    pub output_amount: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UltraExecuteResponse {
+    pub status: UltraExecuteStatus,
+    pub signature: Option<String>,
+    #[serde(default, with = "option_field_as_string")]
+    pub slot: Option<u64>,
+    pub code: i32,
+    pub error: Option<String>,
+    #[serde(default, with = "option_field_as_string")]
+    pub input_amount_result: Option<u64>,
+    #[serde(default, with = "option_field_as_string")]
+    pub output_amount_result: Option<u64>,
+    #[serde(default)]
+    pub swap_events: Vec<UltraSwapEvent>,
+}
+
+/// Amounts actually swapped by a landed Ultra order, across all its hops
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UltraSwapResult {
+    #[serde(with = "field_as_string")]
+    pub input_mint: Pubkey,
+    /// Input spent
+    #[serde(with = "field_as_string")]
+    pub input_amount_result: u64,
+    #[serde(with = "field_as_string")]
+    pub output_mint: Pubkey,
+    /// Output received
+    #[serde(with = "field_as_string")]
+    pub output_amount_result: u64,
+}
+
+impl UltraSwapResult {
+    /// Output UI amount received per UI amount of input,
+    /// `None` when no input was spent or the decimals are not supported
+    pub fn effective_price(&self, input_decimals: u8, output_decimals: u8) -> Option<Decimal> {
+        amount::checked_raw_to_ui(self.output_amount_result, output_decimals)
+            .ok()?
+            .checked_div(amount::checked_raw_to_ui(self.input_amount_result, input_decimals).ok()?)
+    }
+}
+
+impl UltraExecuteResponse {
+    /// Mints of the first and last swap events with the resulting amounts,
+    /// `None` when the order did not land or the response lacks them
+    pub fn swap_result(&self) -> Option<UltraSwapResult> {
+        Some(UltraSwapResult {
+            input_mint: self.swap_events.first()?.input_mint,
+            input_amount_result: self.input_amount_result?,
+            output_mint: self.swap_events.last()?.output_mint,
+            output_amount_result: self.output_amount_result?,
+        })
+    }
+}
+
+/// Error codes returned by a failed Ultra execute
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum UltraExecuteErrorCode {
+    /// The order expired or was never created
+    OrderNotFound,
+    InvalidSignedTransaction,
+    InvalidMessageBytes,
+    FailedToLand,
+    Unknown,
+    InvalidTransaction,
+    TransactionNotFullySigned,
+    InvalidBlockHeight,
+    SlippageToleranceExceeded,
+    NotEnoughAccountKeys,
+    IncorrectTokenProgramId,
+    ExactOutAmountNotMatched,
+    InsufficientFunds,
+    Other(i32),
+}
+
+impl From<i32> for UltraExecuteErrorCode {
+    fn from(code: i32) -> Self {
+        match code {
+            -1 => Self::OrderNotFound,
+            -2 => Self::InvalidSignedTransaction,
+            -3 => Self::InvalidMessageBytes,
+            -1000 | -2000 => Self::FailedToLand,
+            -1001 | -2001 => Self::Unknown,
+            -1002 => Self::InvalidTransaction,
+            -1003 => Self::TransactionNotFullySigned,
+            -1004 => Self::InvalidBlockHeight,
+            6001 => Self::SlippageToleranceExceeded,
+            6008 => Self::NotEnoughAccountKeys,
+            6014 => Self::IncorrectTokenProgramId,
+            6017 => Self::ExactOutAmountNotMatched,
+            6024 => Self::InsufficientFunds,
+            code => Self::Other(code),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UltraBalance {
+    #[serde(with = "field_as_string")]
+    pub amount: u64,
+    pub ui_amount: f64,
+    pub slot: u64,
+    pub is_frozen: bool,
+}
+
+/// Kind of a token safety warning, Jupiter adds new kinds over time which are kept in `Other`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum ShieldWarningKind {
+    NotVerified,
+    LowLiquidity,
+    LowOrganicActivity,
+    NewListing,
+    HasFreezeAuthority,
+    HasMintAuthority,
+    HasPermanentDelegate,
+    HasTransferFee,
+    NonTransferable,
+    HighSingleOwnership,
+    Other(String),
+}
+
+impl From<String> for ShieldWarningKind {
+    fn from(kind: String) -> Self {
+        match kind.as_str() {
+            "NOT_VERIFIED" => Self::NotVerified,
+            "LOW_LIQUIDITY" => Self::LowLiquidity,
+            "LOW_ORGANIC_ACTIVITY" => Self::LowOrganicActivity,
+            "NEW_LISTING" => Self::NewListing,
+            "HAS_FREEZE_AUTHORITY" => Self::HasFreezeAuthority,
+            "HAS_MINT_AUTHORITY" => Self::HasMintAuthority,
+            "HAS_PERMANENT_DELEGATE" => Self::HasPermanentDelegate,
+            "HAS_TRANSFER_FEE" => Self::HasTransferFee,
+            "NON_TRANSFERABLE" => Self::NonTransferable,
+            "HIGH_SINGLE_OWNERSHIP" => Self::HighSingleOwnership,
+            _ => Self::Other(kind),
+        }
+    }
+}
+
+impl From<ShieldWarningKind> for String {
+    fn from(kind: ShieldWarningKind) -> Self {
+        match kind {
+            ShieldWarningKind::NotVerified => "NOT_VERIFIED".into(),
+            ShieldWarningKind::LowLiquidity => "LOW_LIQUIDITY".into(),
+            ShieldWarningKind::LowOrganicActivity => "LOW_ORGANIC_ACTIVITY".into(),
+            ShieldWarningKind::NewListing => "NEW_LISTING".into(),
+            ShieldWarningKind::HasFreezeAuthority => "HAS_FREEZE_AUTHORITY".into(),
+            ShieldWarningKind::HasMintAuthority => "HAS_MINT_AUTHORITY".into(),
+            ShieldWarningKind::HasPermanentDelegate => "HAS_PERMANENT_DELEGATE".into(),
+            ShieldWarningKind::HasTransferFee => "HAS_TRANSFER_FEE".into(),
+            ShieldWarningKind::NonTransferable => "NON_TRANSFERABLE".into(),
+            ShieldWarningKind::HighSingleOwnership => "HIGH_SINGLE_OWNERSHIP".into(),
+            ShieldWarningKind::Other(kind) => kind,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShieldWarning {
+    #[serde(rename = "type")]
+    pub kind: ShieldWarningKind,
+    pub message: String,
+    pub severity: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ShieldResponse {
+    /// Warnings keyed by mint, mints without warnings may be omitted
+    pub warnings: HashMap<String, Vec<ShieldWarning>>,
+}
+
+impl JupiterSwapApiClient {
+    /// GET /order, the returned transaction has to be signed by the taker then passed to [`Self::ultra_execute`]
+    pub async fn ultra_order(
+        &self,
+        order_request: &UltraOrderRequest,
+    ) -> Result<UltraOrderResponse, ClientError> {
+        self.get(
+            Endpoint::UltraOrder,
+            &format!("{}/order", self.ultra_base_path),
+            order_request,
+        )
+        .await
+    }
+
+    /// POST /execute, a failed execution is returned as [`ErrorKind::UltraExecuteFailed`]
+    pub async fn ultra_execute(
+        &self,
+        execute_request: &UltraExecuteRequest,
+    ) -> Result<UltraExecuteResponse, ClientError> {
+        let execute_response: UltraExecuteResponse = self
+            .post(
+                Endpoint::UltraExecute,
+                &format!("{}/execute", self.ultra_base_path),
+                execute_request,
+            )
+            .await?;
+        if execute_response.status == UltraExecuteStatus::Failed {
+            return Err(ClientError::from(ErrorKind::UltraExecuteFailed {
+                code: execute_response.code.into(),
+                message: execute_response.error.unwrap_or_default(),
+                signature: execute_response.signature,
+            })
+            .with_endpoint(Endpoint::UltraExecute));
+        }
+        Ok(execute_response)
+    }
+
+    /// GET /balances/:wallet, keyed by mint except for native SOL which is keyed by `SOL`
+    pub async fn ultra_balances(
+        &self,
+        wallet: &Pubkey,
+    ) -> Result<HashMap<String, UltraBalance>, ClientError> {
+        let response = self
+            .send_request(
+                Endpoint::UltraBalances,
+                Method::GET,
+                &format!("{}/balances/{wallet}", self.ultra_base_path),
+                None::<&()>,
+                None::<&()>,
+                &RequestOptions::default(),
+            )
+            .await?;
+        check_status_code_and_deserialize(response).await
+    }
+
+    /// GET /shield, token safety warnings such as freeze authority or low liquidity
+    pub async fn ultra_shield(&self, mints: &[Pubkey]) -> Result<ShieldResponse, ClientError> {
+        let mints = mints
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        self.get(
+            Endpoint::UltraShield,
+            &format!("{}/shield", self.ultra_base_path),
+            &[("mints", mints)],
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{
+        mints::{NATIVE_MINT, USDC_MINT, USDT_MINT},
+        quote::tests::quote_response_fixture,
+    };
+
+    /// A landed SOL to USDC order routed through USDT
+    fn execute_response_fixture() -> serde_json::Value {
+        json!({
+            "status": "Success",
+            "signature": "5J7qXUfYBPrEQCHhmUhQ4UQfQ4K8fpV6Qj2h5Z9e6nLbCgVkHVnR6qzEoLZoE4n4m5xM6D5Qv5nZ7JrZ8y1hYdT9",
+            "slot": "325679512",
+            "code": 0,
+            "inputAmountResult": "1000000000",
+            "outputAmountResult": "146829450",
+            "swapEvents": [
+                {
+                    "inputMint": NATIVE_MINT.to_string(),
+                    "inputAmount": "1000000000",
+                    "outputMint": USDT_MINT.to_string(),
+                    "outputAmount": "146900000"
+                },
+                {
+                    "inputMint": USDT_MINT.to_string(),
+                    "inputAmount": "146900000",
+                    "outputMint": USDC_MINT.to_string(),
+                    "outputAmount": "146829450"
+                }
+            ]
+        })
+    }
+
+    #[test]
+    fn swap_result_spans_all_swap_events() {
+        let response: UltraExecuteResponse =
+            serde_json::from_value(execute_response_fixture()).unwrap();
+        assert_eq!(response.slot, Some(325679512));
+        assert_eq!(response.swap_events.len(), 2);
+
+        let result = response.swap_result().unwrap();
+        assert_eq!(
+            result,
+            UltraSwapResult {
+                input_mint: NATIVE_MINT,
+                input_amount_result: 1_000_000_000,
+                output_mint: USDC_MINT,
+                output_amount_result: 146_829_450,
+            }
+        );
+        assert_eq!(
+            result.effective_price(9, 6),
+            Some(Decimal::from_str("146.82945").unwrap())
+        );
+    }
+
+    #[test]
+    fn effective_price_is_none_without_input() {
+        let result = UltraSwapResult {
+            input_mint: NATIVE_MINT,
+            input_amount_result: 0,
+            output_mint: USDC_MINT,
+            output_amount_result: 146_829_450,
+        };
+        assert_eq!(result.effective_price(9, 6), None);
+    }
+
+    #[test]
+    fn effective_price_is_none_with_unsupported_decimals() {
+        let result = UltraSwapResult {
+            input_mint: NATIVE_MINT,
+            input_amount_result: 1_000_000_000,
+            output_mint: USDC_MINT,
+            output_amount_result: 146_829_450,
+        };
+        assert_eq!(result.effective_price(29, 6), None);
+        assert_eq!(result.effective_price(9, u8::MAX), None);
+    }
+
+    #[test]
+    fn absent_fields_deserialize_without_swap_result() {
+        let mut fixture = execute_response_fixture();
+        for field in ["swapEvents", "inputAmountResult", "outputAmountResult"] {
+            let mut without = fixture.clone();
+            without.as_object_mut().unwrap().remove(field);
+            let response: UltraExecuteResponse = serde_json::from_value(without).unwrap();
+            assert_eq!(response.swap_result(), None, "without {field}");
+        }
+
+        // A failed order only carries its status, code and error
+        fixture = json!({"status": "Failed", "code": -1000, "error": "Failed to land"});
+        let response: UltraExecuteResponse = serde_json::from_value(fixture).unwrap();
+        assert_eq!(response.status, UltraExecuteStatus::Failed);
+        assert_eq!(
+            UltraExecuteErrorCode::from(response.code),
+            UltraExecuteErrorCode::FailedToLand
+        );
+        assert!(response.swap_events.is_empty());
+        assert_eq!(response.slot, None);
+        assert_eq!(response.swap_result(), None);
+    }
+
+    fn ultra_client(server: &MockServer) -> JupiterSwapApiClient {
+        let mut client = JupiterSwapApiClient::new(server.uri(), None);
+        client.ultra_base_path = server.uri();
+        client
+    }
+
+    fn order_fixture() -> serde_json::Value {
+        json!({
+            "inputMint": NATIVE_MINT.to_string(),
+            "inAmount": "1000000000",
+            "outputMint": USDC_MINT.to_string(),
+            "outAmount": "146829450",
+            "otherAmountThreshold": "146094303",
+            "swapMode": "ExactIn",
+            "slippageBps": 50,
+            "priceImpactPct": "0.0001",
+            "routePlan": quote_response_fixture()["routePlan"],
+            "feeBps": 5,
+            "transaction": "AQID",
+            "requestId": "019a5f4e",
+            "gasless": false,
+            "prioritizationFeeLamports": 5000,
+            "router": "iris"
+        })
+    }
+
+    #[tokio::test]
+    async fn order_is_requested_for_the_taker() {
+        let server = MockServer::start().await;
+        let taker = Pubkey::new_unique();
+        Mock::given(method("GET"))
+            .and(path("/order"))
+            .and(query_param("inputMint", NATIVE_MINT.to_string()))
+            .and(query_param("outputMint", USDC_MINT.to_string()))
+            .and(query_param("amount", "1000000000"))
+            .and(query_param("taker", taker.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(order_fixture()))
+            .mount(&server)
+            .await;
+
+        let order = ultra_client(&server)
+            .ultra_order(&UltraOrderRequest {
+                input_mint: NATIVE_MINT,
+                output_mint: USDC_MINT,
+                amount: 1_000_000_000,
+                taker: Some(taker),
+                ..UltraOrderRequest::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(order.out_amount, 146_829_450);
+        assert_eq!(order.transaction, Some(vec![1, 2, 3]));
+        assert_eq!(order.request_id, "019a5f4e");
+        assert_eq!(order.route_plan.len(), 2);
+    }
+
+    async fn execute(
+        server: &MockServer,
+        response: serde_json::Value,
+    ) -> Result<UltraExecuteResponse, ClientError> {
+        Mock::given(method("POST"))
+            .and(path("/execute"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response))
+            .mount(server)
+            .await;
+        ultra_client(server)
+            .ultra_execute(&UltraExecuteRequest {
+                signed_transaction: vec![1, 2, 3],
+                request_id: "019a5f4e".to_string(),
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn successful_execution_is_returned() {
+        let server = MockServer::start().await;
+        let execute_response = execute(&server, execute_response_fixture()).await.unwrap();
+        assert_eq!(execute_response.status, UltraExecuteStatus::Success);
+        assert!(execute_response.swap_result().is_some());
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(
+            requests[0].body_json::<serde_json::Value>().unwrap(),
+            json!({ "signedTransaction": "AQID", "requestId": "019a5f4e" })
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_execution_is_an_error() {
+        let server = MockServer::start().await;
+        let error = execute(
+            &server,
+            json!({
+                "status": "Failed",
+                "signature": "signature",
+                "code": 6001,
+                "error": "Slippage tolerance exceeded"
+            }),
+        )
+        .await
+        .unwrap_err();
+
+        let ErrorKind::UltraExecuteFailed {
+            code,
+            message,
+            signature,
+        } = error.kind()
+        else {
+            panic!("{error:?}");
+        };
+        assert_eq!(*code, UltraExecuteErrorCode::SlippageToleranceExceeded);
+        assert_eq!(message, "Slippage tolerance exceeded");
+        assert_eq!(signature.as_deref(), Some("signature"));
+        assert_eq!(error.endpoint(), Some(Endpoint::UltraExecute));
+    }
+
+    #[tokio::test]
+    async fn balances_are_keyed_by_mint() {
+        let server = MockServer::start().await;
+        let wallet = Pubkey::new_unique();
+        Mock::given(method("GET"))
+            .and(path(format!("/balances/{wallet}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "SOL": { "amount": "1500000000", "uiAmount": 1.5, "slot": 325679512, "isFrozen": false },
+                USDC_MINT.to_string(): { "amount": "20000000", "uiAmount": 20.0, "slot": 325679512, "isFrozen": true }
+            })))
+            .mount(&server)
+            .await;
+
+        let balances = ultra_client(&server).ultra_balances(&wallet).await.unwrap();
+        assert_eq!(
+            balances["SOL"],
+            UltraBalance {
+                amount: 1_500_000_000,
+                ui_amount: 1.5,
+                slot: 325_679_512,
+                is_frozen: false,
+            }
+        );
+        assert!(balances[&USDC_MINT.to_string()].is_frozen);
+    }
+
+    #[tokio::test]
+    async fn shield_warnings_are_requested_for_all_mints() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/shield"))
+            .and(query_param("mints", format!("{NATIVE_MINT},{USDC_MINT}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "warnings": {
+                    USDC_MINT.to_string(): [
+                        { "type": "HAS_FREEZE_AUTHORITY", "message": "Freeze authority", "severity": "info" },
+                        { "type": "SOMETHING_NEW", "message": "New kind" }
+                    ]
+                }
+            })))
+            .mount(&server)
+            .await;
+
+        let shield = ultra_client(&server)
+            .ultra_shield(&[NATIVE_MINT, USDC_MINT])
+            .await
+            .unwrap();
+        let kinds: Vec<_> = shield.warnings[&USDC_MINT.to_string()]
+            .iter()
+            .map(|warning| warning.kind.clone())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ShieldWarningKind::HasFreezeAuthority,
+                ShieldWarningKind::Other("SOMETHING_NEW".to_string())
+            ]
+        );
+        assert!(!shield.warnings.contains_key(&NATIVE_MINT.to_string()));
+    }
+}
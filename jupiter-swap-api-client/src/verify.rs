@@ -0,0 +1,381 @@
+//! Verification of a swap transaction before signing it, as a defense against a compromised endpoint,
+//! requires the `solana-sdk` feature
+//!
+//! Only the programs invoked and the system program instructions are inspected: the instructions of the
+//! token programs, e.g. an SPL Token `Approve` or `SetAuthority` of the user's token accounts, are not.
+
+use solana_pubkey::Pubkey;
+use solana_sdk::{
+    instruction::CompiledInstruction, message::VersionedMessage, transaction::VersionedTransaction,
+};
+use solana_system_interface::{instruction::SystemInstruction, program as system_program};
+use thiserror::Error;
+
+use crate::{
+    ata::{
+        associated_token_address, ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+    },
+    jito::TIP_ACCOUNTS,
+    mints::NATIVE_MINT,
+    simulation::JUPITER_PROGRAM_ID,
+    swap::SwapResponse,
+};
+
+/// Programs the top level instructions of a swap transaction may invoke
+pub const ALLOWED_PROGRAMS: [Pubkey; 6] = [
+    solana_compute_budget_interface::ID,
+    system_program::ID,
+    TOKEN_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_TOKEN_PROGRAM_ID,
+    JUPITER_PROGRAM_ID,
+];
+
+/// `index` is the one of the top level instruction which failed the check, in the first transaction failing it
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error("Failed to decode the swap transaction: {0}")]
+    InvalidTransaction(#[from] bincode::Error),
+    #[error("The fee payer is {actual} instead of {expected}")]
+    FeePayerMismatch { expected: Pubkey, actual: Pubkey },
+    #[error("Instruction {index} invokes {program_id} which is not allowed")]
+    DisallowedProgram { index: usize, program_id: Pubkey },
+    #[error("Instruction {index} references an account the transaction does not contain")]
+    UnknownAccount { index: usize },
+    #[error(
+        "Instruction {index} moves lamports to an account loaded from an address lookup table"
+    )]
+    UnresolvedRecipient { index: usize },
+    #[error("Instruction {index} moves lamports to {recipient} which is not allowed")]
+    DisallowedRecipient { index: usize, recipient: Pubkey },
+    #[error("Instruction {index} is not a valid system program instruction")]
+    MalformedSystemInstruction { index: usize },
+    #[error("Instruction {index} is a system program instruction other than a lamports transfer")]
+    DisallowedInstruction { index: usize },
+}
+
+impl SwapResponse {
+    /// Check the [`transactions`](SwapResponse::transactions) before signing them: `expected_user` pays the fees,
+    /// the top level instructions only invoke [`ALLOWED_PROGRAMS`] and system program instructions
+    /// are only account creations and transfers moving lamports to `expected_user`, its wSOL account
+    /// or a Jito tip account
+    pub fn verify(&self, expected_user: &Pubkey) -> Result<(), VerifyError> {
+        self.verify_with(expected_user, &[])
+    }
+
+    /// Same as [`Self::verify`], also allowing lamports to be moved to `allowed_recipients`, e.g. a fee account
+    pub fn verify_with(
+        &self,
+        expected_user: &Pubkey,
+        allowed_recipients: &[Pubkey],
+    ) -> Result<(), VerifyError> {
+        self.transactions().try_for_each(|transaction| {
+            verify_transaction(transaction, expected_user, allowed_recipients)
+        })
+    }
+}
+
+fn verify_transaction(
+    transaction: &[u8],
+    expected_user: &Pubkey,
+    allowed_recipients: &[Pubkey],
+) -> Result<(), VerifyError> {
+    let transaction: VersionedTransaction = bincode::deserialize(transaction)?;
+    let account_keys = transaction.message.static_account_keys();
+    let fee_payer = account_keys.first().copied().unwrap_or_default();
+    if fee_payer != *expected_user {
+        return Err(VerifyError::FeePayerMismatch {
+            expected: *expected_user,
+            actual: fee_payer,
+        });
+    }
+    let wrapped_sol_account =
+        associated_token_address(expected_user, &NATIVE_MINT, &TOKEN_PROGRAM_ID);
+    let is_allowed_recipient = |recipient: &Pubkey| {
+        recipient == expected_user
+            || *recipient == wrapped_sol_account
+            || TIP_ACCOUNTS.contains(recipient)
+            || allowed_recipients.contains(recipient)
+    };
+
+    for (index, instruction) in transaction.message.instructions().iter().enumerate() {
+        // Programs cannot be loaded from address lookup tables
+        let program_id = *account_keys
+            .get(usize::from(instruction.program_id_index))
+            .ok_or(VerifyError::UnknownAccount { index })?;
+        if !ALLOWED_PROGRAMS.contains(&program_id) {
+            return Err(VerifyError::DisallowedProgram { index, program_id });
+        }
+        if program_id != system_program::ID {
+            continue;
+        }
+        let position = lamports_recipient_position(index, instruction)?;
+        let account_index = *instruction
+            .accounts
+            .get(position)
+            .ok_or(VerifyError::MalformedSystemInstruction { index })?;
+        let recipient = match account_keys.get(usize::from(account_index)) {
+            Some(recipient) => recipient,
+            None if is_looked_up(&transaction.message, account_index) => {
+                return Err(VerifyError::UnresolvedRecipient { index })
+            }
+            None => return Err(VerifyError::UnknownAccount { index }),
+        };
+        if !is_allowed_recipient(recipient) {
+            return Err(VerifyError::DisallowedRecipient {
+                index,
+                recipient: *recipient,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Position in the instruction accounts of the account a system program instruction moves lamports to,
+/// any instruction but the ones moving lamports is rejected, e.g. `Assign` handing an account to another program
+fn lamports_recipient_position(
+    index: usize,
+    instruction: &CompiledInstruction,
+) -> Result<usize, VerifyError> {
+    let system_instruction: SystemInstruction = bincode::deserialize(&instruction.data)
+        .map_err(|_| VerifyError::MalformedSystemInstruction { index })?;
+    match system_instruction {
+        SystemInstruction::CreateAccount { .. }
+        | SystemInstruction::CreateAccountWithSeed { .. }
+        | SystemInstruction::Transfer { .. }
+        | SystemInstruction::WithdrawNonceAccount(_) => Ok(1),
+        SystemInstruction::TransferWithSeed { .. } => Ok(2),
+        _ => Err(VerifyError::DisallowedInstruction { index }),
+    }
+}
+
+/// `account_index` points past the static keys into the addresses loaded from lookup tables
+fn is_looked_up(message: &VersionedMessage, account_index: u8) -> bool {
+    let looked_up = message.address_table_lookups().map_or(0, |lookups| {
+        lookups
+            .iter()
+            .map(|lookup| lookup.writable_indexes.len() + lookup.readonly_indexes.len())
+            .sum()
+    });
+    usize::from(account_index) < message.static_account_keys().len() + looked_up
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::{AccountMeta, Instruction};
+    use solana_sdk::{
+        address_lookup_table::AddressLookupTableAccount,
+        hash::Hash,
+        message::{v0, VersionedMessage},
+        signature::Signature,
+    };
+    use solana_system_interface::instruction as system_instruction;
+
+    use super::*;
+
+    fn swap_instruction(user: &Pubkey) -> Instruction {
+        Instruction::new_with_bytes(
+            JUPITER_PROGRAM_ID,
+            &[1, 2, 3],
+            vec![AccountMeta::new(*user, true)],
+        )
+    }
+
+    fn compile(
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        address_lookup_table_accounts: &[AddressLookupTableAccount],
+    ) -> VersionedMessage {
+        VersionedMessage::V0(
+            v0::Message::try_compile(
+                payer,
+                instructions,
+                address_lookup_table_accounts,
+                Hash::new_unique(),
+            )
+            .unwrap(),
+        )
+    }
+
+    fn swap_response(message: VersionedMessage) -> SwapResponse {
+        let transaction = VersionedTransaction {
+            signatures: vec![Signature::default(); message.header().num_required_signatures.into()],
+            message,
+        };
+        SwapResponse {
+            swap_transaction: bincode::serialize(&transaction).unwrap(),
+            ..SwapResponse::default()
+        }
+    }
+
+    fn verify(user: &Pubkey, instructions: &[Instruction]) -> Result<(), VerifyError> {
+        swap_response(compile(user, instructions, &[])).verify(user)
+    }
+
+    #[test]
+    fn accepts_swap_transaction() {
+        let user = Pubkey::new_unique();
+        let wrapped_sol_account = associated_token_address(&user, &NATIVE_MINT, &TOKEN_PROGRAM_ID);
+        let instructions = [
+            solana_compute_budget_interface::ComputeBudgetInstruction::set_compute_unit_limit(
+                200_000,
+            ),
+            system_instruction::transfer(&user, &wrapped_sol_account, 1_000),
+            swap_instruction(&user),
+            system_instruction::transfer(&user, &TIP_ACCOUNTS[0], 10_000),
+        ];
+        verify(&user, &instructions).unwrap();
+    }
+
+    #[test]
+    fn accepts_allowed_recipient() {
+        let user = Pubkey::new_unique();
+        let fee_account = Pubkey::new_unique();
+        let swap_response = swap_response(compile(
+            &user,
+            &[system_instruction::transfer(&user, &fee_account, 1_000)],
+            &[],
+        ));
+        assert!(matches!(
+            swap_response.verify(&user),
+            Err(VerifyError::DisallowedRecipient { index: 0, recipient }) if recipient == fee_account
+        ));
+        swap_response.verify_with(&user, &[fee_account]).unwrap();
+    }
+
+    #[test]
+    fn rejects_undecodable_transaction() {
+        let swap_response = SwapResponse {
+            swap_transaction: vec![1, 2, 3],
+            ..SwapResponse::default()
+        };
+        assert!(matches!(
+            swap_response.verify(&Pubkey::new_unique()),
+            Err(VerifyError::InvalidTransaction(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_fee_payer() {
+        let user = Pubkey::new_unique();
+        let fee_payer = Pubkey::new_unique();
+        let swap_response = swap_response(compile(&fee_payer, &[swap_instruction(&user)], &[]));
+        assert!(matches!(
+            swap_response.verify(&user),
+            Err(VerifyError::FeePayerMismatch { expected, actual })
+                if expected == user && actual == fee_payer
+        ));
+    }
+
+    #[test]
+    fn rejects_tampered_program_id() {
+        let user = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let mut instruction = swap_instruction(&user);
+        instruction.program_id = program_id;
+        assert!(matches!(
+            verify(&user, &[swap_instruction(&user), instruction]),
+            Err(VerifyError::DisallowedProgram { index: 1, program_id: disallowed })
+                if disallowed == program_id
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_account() {
+        let user = Pubkey::new_unique();
+        let VersionedMessage::V0(mut message) = compile(&user, &[swap_instruction(&user)], &[])
+        else {
+            unreachable!()
+        };
+        message.instructions[0].program_id_index = u8::MAX;
+        assert!(matches!(
+            swap_response(VersionedMessage::V0(message)).verify(&user),
+            Err(VerifyError::UnknownAccount { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn rejects_transfer_to_unknown_recipient() {
+        let user = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let instructions = [
+            swap_instruction(&user),
+            system_instruction::transfer(&user, &recipient, 1_000),
+        ];
+        assert!(matches!(
+            verify(&user, &instructions),
+            Err(VerifyError::DisallowedRecipient { index: 1, recipient: disallowed })
+                if disallowed == recipient
+        ));
+    }
+
+    #[test]
+    fn rejects_recipient_loaded_from_lookup_table() {
+        let user = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let address_lookup_table_account = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![recipient],
+        };
+        let swap_response = swap_response(compile(
+            &user,
+            &[system_instruction::transfer(&user, &recipient, 1_000)],
+            &[address_lookup_table_account],
+        ));
+        assert!(matches!(
+            swap_response.verify_with(&user, &[recipient]),
+            Err(VerifyError::UnresolvedRecipient { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_system_instruction() {
+        let user = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(
+            system_program::ID,
+            &[u8::MAX; 4],
+            vec![AccountMeta::new(user, true)],
+        );
+        assert!(matches!(
+            verify(&user, &[instruction]),
+            Err(VerifyError::MalformedSystemInstruction { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn rejects_system_instructions_other_than_transfers() {
+        let user = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        for instruction in [
+            system_instruction::assign(&account, &owner),
+            system_instruction::allocate(&account, 165),
+            system_instruction::authorize_nonce_account(&account, &user, &owner),
+        ] {
+            assert!(matches!(
+                verify(&user, &[swap_instruction(&user), instruction]),
+                Err(VerifyError::DisallowedInstruction { index: 1 })
+            ));
+        }
+    }
+
+    #[test]
+    fn verifies_additional_transactions() {
+        let user = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let setup = swap_response(compile(
+            &user,
+            &[system_instruction::transfer(&user, &recipient, 1_000)],
+            &[],
+        ));
+        let swap_response = SwapResponse {
+            additional_transactions: vec![setup.swap_transaction],
+            ..swap_response(compile(&user, &[swap_instruction(&user)], &[]))
+        };
+        assert!(matches!(
+            swap_response.verify(&user),
+            Err(VerifyError::DisallowedRecipient { index: 0, .. })
+        ));
+    }
+}